@@ -0,0 +1,235 @@
+//! Tempo estimation from an onset-strength envelope.
+//!
+//! The signal is reduced to a log-energy novelty curve, autocorrelated, and
+//! every candidate tempo in the configured range is scored against the first
+//! few multiples of its beat period. Restricting the search range is what
+//! keeps half- and double-time answers out.
+
+/// Analysis frame length in samples
+const FRAME_SIZE: usize = 1024;
+/// Hop between frames in samples
+const HOP_SIZE: usize = 128;
+/// Number of beat-period multiples each candidate is scored against
+const HARMONICS: usize = 8;
+/// Resolution of the tempo search
+const BPM_STEP: f32 = 0.05;
+/// How close double time has to score before it wins over the slower reading
+const DOUBLE_TIME_RATIO: f32 = 0.9;
+/// Energy floor (about -60 dBFS) so onsets out of digital silence don't swamp the envelope
+const ENERGY_FLOOR: f32 = 1e-6;
+/// Beats per bar assumed when looking for the downbeat
+const BEATS_PER_BAR: usize = 4;
+/// Fraction of the strongest onset that counts as the music starting
+const START_THRESHOLD: f32 = 0.3;
+
+/// Estimate the tempo of a mono signal, searching only between `min_bpm` and `max_bpm`
+pub fn estimate_bpm(samples: &[f32], sample_rate: u32, min_bpm: f32, max_bpm: f32) -> Option<f32> {
+    let envelope = onset_envelope(samples);
+    let frame_rate = sample_rate as f32 / HOP_SIZE as f32;
+
+    // Longest lag we need: the slowest tempo's period times the highest harmonic
+    let max_lag = ((60.0 * frame_rate / min_bpm) * HARMONICS as f32).ceil() as usize + 2;
+    if envelope.len() <= max_lag * 2 {
+        return None;
+    }
+
+    let ac = autocorrelation(&envelope, max_lag);
+    if ac[0] <= f32::EPSILON {
+        // Silence or a perfectly flat signal
+        return None;
+    }
+
+    let score = |bpm: f32| -> f32 {
+        let period = 60.0 * frame_rate / bpm;
+        (1..=HARMONICS)
+            .map(|k| interpolate(&ac, period * k as f32))
+            .sum()
+    };
+
+    let mut best: Option<(f32, f32)> = None;
+    let mut bpm = min_bpm;
+    while bpm <= max_bpm {
+        let candidate = score(bpm);
+        if best.is_none_or(|(_, s)| candidate > s) {
+            best = Some((bpm, candidate));
+        }
+        bpm += BPM_STEP;
+    }
+
+    let (mut bpm, best_score) = best?;
+
+    // A pulse at tempo T also lines up at T/2, so when double time is in range
+    // and explains the signal almost as well, it is the real tempo
+    if bpm * 2.0 <= max_bpm && score(bpm * 2.0) >= best_score * DOUBLE_TIME_RATIO {
+        bpm *= 2.0;
+    }
+
+    Some((bpm * 10.0).round() / 10.0)
+}
+
+/// Find the first downbeat (in seconds) of a track whose tempo is already known.
+///
+/// The beat phase is the offset whose grid collects the most onset energy; of
+/// the four beats in a bar, the one with the strongest accents is taken as the
+/// downbeat. The result is the first such downbeat once the music has started.
+pub fn first_downbeat(samples: &[f32], sample_rate: u32, bpm: f32) -> Option<f32> {
+    let mut envelope = onset_envelope(samples);
+    if envelope.is_empty() || bpm <= 0.0 {
+        return None;
+    }
+    // Only onsets matter here, not the dips below the mean
+    for value in &mut envelope {
+        *value = value.max(0.0);
+    }
+
+    let frame_rate = sample_rate as f32 / HOP_SIZE as f32;
+    let period = 60.0 * frame_rate / bpm;
+    let grid_energy = |offset: f32, step: f32| -> f32 {
+        let mut total = 0.0;
+        let mut position = offset;
+        while position < envelope.len() as f32 - 1.0 {
+            total += interpolate(&envelope, position);
+            position += step;
+        }
+        total
+    };
+
+    // Beat phase within one period
+    let phase = (0..period.ceil() as usize)
+        .map(|p| p as f32)
+        .max_by(|a, b| grid_energy(*a, period).total_cmp(&grid_energy(*b, period)))?;
+
+    // Which beat of the bar carries the downbeat accent
+    let bar = period * BEATS_PER_BAR as f32;
+    let bar_beat = (0..BEATS_PER_BAR).max_by(|a, b| {
+        let a = grid_energy(phase + *a as f32 * period, bar);
+        let b = grid_energy(phase + *b as f32 * period, bar);
+        a.total_cmp(&b)
+    })?;
+
+    // Skip any downbeats that fall before the music actually starts
+    let peak = envelope.iter().cloned().fold(0.0, f32::max);
+    let start = envelope.iter().position(|&v| v >= peak * START_THRESHOLD)? as f32;
+    let mut downbeat = phase + bar_beat as f32 * period;
+    while downbeat + period / 2.0 < start {
+        downbeat += bar;
+    }
+
+    Some(frame_to_seconds(downbeat, sample_rate))
+}
+
+/// Convert an onset-envelope index to seconds (onsets register as the frame window reaches them)
+fn frame_to_seconds(frame: f32, sample_rate: u32) -> f32 {
+    ((frame + 1.0) * HOP_SIZE as f32 + FRAME_SIZE as f32) / sample_rate as f32
+}
+
+/// Half-wave rectified log-energy difference, mean-removed
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+    if samples.len() < FRAME_SIZE {
+        return Vec::new();
+    }
+
+    let energies: Vec<f32> = samples
+        .windows(FRAME_SIZE)
+        .step_by(HOP_SIZE)
+        .map(|frame| {
+            let energy: f32 = frame.iter().map(|s| s * s).sum::<f32>() / FRAME_SIZE as f32;
+            (energy + ENERGY_FLOOR).ln()
+        })
+        .collect();
+
+    let mut envelope: Vec<f32> = energies
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).max(0.0))
+        .collect();
+
+    let mean = envelope.iter().sum::<f32>() / envelope.len().max(1) as f32;
+    for value in &mut envelope {
+        *value -= mean;
+    }
+
+    envelope
+}
+
+/// Autocorrelation for lags `0..=max_lag`, normalised by overlap so long lags aren't penalised
+fn autocorrelation(envelope: &[f32], max_lag: usize) -> Vec<f32> {
+    (0..=max_lag)
+        .map(|lag| {
+            let overlap = envelope.len() - lag;
+            let sum: f32 = envelope[..overlap]
+                .iter()
+                .zip(&envelope[lag..])
+                .map(|(a, b)| a * b)
+                .sum();
+            sum / overlap as f32
+        })
+        .collect()
+}
+
+/// Linear interpolation of the autocorrelation at a fractional lag
+fn interpolate(ac: &[f32], lag: f32) -> f32 {
+    let index = lag.floor() as usize;
+    if index + 1 >= ac.len() {
+        return 0.0;
+    }
+    let fraction = lag - index as f32;
+    ac[index] * (1.0 - fraction) + ac[index + 1] * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 22050;
+
+    /// A click track: a short decaying burst of noise on every beat
+    fn clicks(bpm: f32, seconds: f32) -> Vec<f32> {
+        let len = (RATE as f32 * seconds) as usize;
+        let period = 60.0 / bpm * RATE as f32;
+        let click = (RATE / 50) as usize;
+        let mut state = 1u32;
+        let mut out = vec![0.0; len];
+        let mut beat = 0.0;
+        while (beat as usize) < len {
+            let start = beat as usize;
+            for (i, sample) in out[start..].iter_mut().take(click).enumerate() {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                let noise = (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+                *sample = noise * (-(i as f32) / (click as f32 / 5.0)).exp();
+            }
+            beat += period;
+        }
+        out
+    }
+
+    fn assert_near(actual: Option<f32>, expected: f32) {
+        let actual = actual.expect("no tempo found");
+        assert!(
+            (actual - expected).abs() <= 0.5,
+            "expected {expected} BPM, got {actual}"
+        );
+    }
+
+    #[test]
+    fn finds_the_tempo_of_a_click_track() {
+        for bpm in [90.0, 128.0, 174.0] {
+            assert_near(estimate_bpm(&clicks(bpm, 30.0), RATE, 70.0, 180.0), bpm);
+        }
+    }
+
+    #[test]
+    fn folds_half_and_double_time_into_the_range() {
+        // Drum and bass read as half time when the range stops below it
+        assert_near(estimate_bpm(&clicks(174.0, 30.0), RATE, 70.0, 140.0), 87.0);
+        // A slow pulse reads as double time when the range starts above it
+        assert_near(estimate_bpm(&clicks(64.0, 30.0), RATE, 100.0, 180.0), 128.0);
+    }
+
+    #[test]
+    fn silence_has_no_tempo() {
+        assert_eq!(
+            estimate_bpm(&vec![0.0; RATE as usize * 10], RATE, 70.0, 180.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Acoustic fingerprints for spotting the same track under different names.
+//!
+//! In the spirit of Chromaprint: the first two minutes are folded into a chromagram,
+//! smoothed over time and reduced to one 32-bit code per frame by comparing pitch
+//! classes with each other and with a few frames earlier. Re-encodes, different
+//! bitrates and changes in level flip only a few bits, so two fingerprints are
+//! compared by their bit error rate once aligned. The alignment comes from voting
+//! on the offsets between identical codes, which copes with different intros.
+
+use color_eyre::{Result, eyre::eyre};
+use rustfft::{FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::decode_mono_head;
+
+/// Sample rate fingerprints are computed at; pitch up to ~3.5 kHz is all we look at
+pub const FINGERPRINT_SAMPLE_RATE: u32 = 11025;
+/// How much of each track is fingerprinted, in seconds
+const FINGERPRINT_SECONDS: u32 = 120;
+/// FFT size; ~2.7 Hz bins at 11.025 kHz
+const FFT_SIZE: usize = 4096;
+/// Hop between frames, about eight frames a second
+const HOP_SIZE: usize = 1365;
+/// Lowest frequency folded into the chromagram
+const MIN_FREQ: f32 = 28.0;
+/// Highest frequency folded into the chromagram
+const MAX_FREQ: f32 = 3520.0;
+/// Frames averaged together before coding, so small timing shifts don't flip bits
+const SMOOTHING: usize = 8;
+/// How many frames back the temporal bits look
+const TEMPORAL_LAG: usize = 4;
+/// Frames quieter than this are silence and left out of the fingerprint
+const SILENCE_FLOOR: f32 = 1e-3;
+/// Fingerprints have to line up over at least this many frames (~15 s) to count as a match
+const MIN_OVERLAP: usize = 120;
+/// Offsets tried when aligning two fingerprints, most voted first
+const OFFSET_CANDIDATES: usize = 3;
+
+/// One 32-bit code per frame; stored as a hex string to keep the history file small
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Fingerprint(pub Vec<u32>);
+
+impl From<Fingerprint> for String {
+    fn from(fingerprint: Fingerprint) -> Self {
+        fingerprint
+            .0
+            .iter()
+            .map(|code| format!("{code:08x}"))
+            .collect()
+    }
+}
+
+impl TryFrom<String> for Fingerprint {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        if !hex.is_ascii() || !hex.len().is_multiple_of(8) {
+            return Err("fingerprint is not a whole number of codes".to_string());
+        }
+        (0..hex.len())
+            .step_by(8)
+            .map(|i| u32::from_str_radix(&hex[i..i + 8], 16).map_err(|e| e.to_string()))
+            .collect::<Result<_, _>>()
+            .map(Fingerprint)
+    }
+}
+
+/// Decode the start of a file and fingerprint it
+pub async fn fingerprint(path: &Path) -> Result<Fingerprint> {
+    let samples = decode_mono_head(path, FINGERPRINT_SAMPLE_RATE, FINGERPRINT_SECONDS).await?;
+    let fingerprint =
+        tokio::task::spawn_blocking(move || from_samples(&samples, FINGERPRINT_SAMPLE_RATE))
+            .await?;
+    if fingerprint.0.len() < MIN_OVERLAP {
+        return Err(eyre!("{} is too short to fingerprint", path.display()));
+    }
+    Ok(fingerprint)
+}
+
+/// Fingerprint mono samples
+pub fn from_samples(samples: &[f32], sample_rate: u32) -> Fingerprint {
+    let chroma = chromagram(samples, sample_rate);
+
+    // Moving average over the last few frames; silent frames stay marked as silent
+    let smoothed: Vec<Option<[f32; 12]>> = (0..chroma.len())
+        .map(|t| {
+            chroma[t]?;
+            let window = &chroma[t.saturating_sub(SMOOTHING - 1)..=t];
+            let mut sum = [0.0f32; 12];
+            for frame in window.iter().flatten() {
+                for (s, c) in sum.iter_mut().zip(frame) {
+                    *s += c;
+                }
+            }
+            Some(sum)
+        })
+        .collect();
+
+    let codes = (0..smoothed.len())
+        .filter_map(|t| {
+            let now = smoothed[t]?;
+            let before = smoothed[t.saturating_sub(TEMPORAL_LAG)].unwrap_or(now);
+            Some(code(&now, &before))
+        })
+        .collect();
+    Fingerprint(codes)
+}
+
+/// 12 bits comparing each pitch class with the next, 12 with the one a fourth up, and
+/// 8 comparing pairs of pitch classes with how they were a moment earlier
+fn code(now: &[f32; 12], before: &[f32; 12]) -> u32 {
+    let mut code = 0u32;
+    for i in 0..12 {
+        if now[i] > now[(i + 1) % 12] {
+            code |= 1 << i;
+        }
+        if now[i] > now[(i + 5) % 12] {
+            code |= 1 << (12 + i);
+        }
+    }
+    for i in 0..8 {
+        let pair = |c: &[f32; 12]| c[i] + c[(i + 4) % 12];
+        if pair(now) > pair(before) {
+            code |= 1 << (24 + i);
+        }
+    }
+    code
+}
+
+/// Unit-length 12-bin chroma per frame; `None` for silent frames
+fn chromagram(samples: &[f32], sample_rate: u32) -> Vec<Option<[f32; 12]>> {
+    if samples.len() < FFT_SIZE {
+        return Vec::new();
+    }
+
+    // Precompute which pitch class each FFT bin belongs to
+    let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+    let bins: Vec<(usize, usize)> = (1..FFT_SIZE / 2)
+        .filter_map(|bin| {
+            let freq = bin as f32 * bin_hz;
+            if !(MIN_FREQ..=MAX_FREQ).contains(&freq) {
+                return None;
+            }
+            let midi = 69.0 + 12.0 * (freq / 440.0).log2();
+            Some((bin, (midi.round() as i32).rem_euclid(12) as usize))
+        })
+        .collect();
+
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32).cos())
+        .collect();
+    let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); FFT_SIZE];
+
+    (0..=(samples.len() - FFT_SIZE) / HOP_SIZE)
+        .map(|frame| {
+            let start = frame * HOP_SIZE;
+            for ((b, s), w) in buffer
+                .iter_mut()
+                .zip(&samples[start..start + FFT_SIZE])
+                .zip(&window)
+            {
+                *b = Complex::new(s * w, 0.0);
+            }
+            fft.process(&mut buffer);
+
+            let mut chroma = [0.0f32; 12];
+            for &(bin, class) in &bins {
+                chroma[class] += buffer[bin].norm();
+            }
+            let norm = chroma.iter().map(|c| c * c).sum::<f32>().sqrt();
+            if norm < SILENCE_FLOOR {
+                return None;
+            }
+            Some(chroma.map(|c| c / norm))
+        })
+        .collect()
+}
+
+/// A fingerprint indexed for comparing against many others
+pub struct Matcher<'a> {
+    codes: &'a [u32],
+    /// Frames each code appears at
+    index: HashMap<u32, Vec<usize>>,
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new(fingerprint: &'a Fingerprint) -> Self {
+        let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, code) in fingerprint.0.iter().enumerate() {
+            index.entry(*code).or_default().push(i);
+        }
+        Self {
+            codes: &fingerprint.0,
+            index,
+        }
+    }
+
+    /// How alike the two recordings are: 1.0 for the same audio, around 0.0 for unrelated tracks
+    pub fn similarity(&self, other: &Fingerprint) -> f32 {
+        // Identical codes vote for how far apart the two fingerprints start
+        let mut votes: HashMap<isize, u32> = HashMap::new();
+        for (j, code) in other.0.iter().enumerate() {
+            for &i in self.index.get(code).into_iter().flatten() {
+                *votes.entry(i as isize - j as isize).or_default() += 1;
+            }
+        }
+        let mut offsets: Vec<(isize, u32)> = votes.into_iter().collect();
+        offsets.sort_by_key(|&(offset, count)| (std::cmp::Reverse(count), offset.abs()));
+
+        offsets
+            .into_iter()
+            .take(OFFSET_CANDIDATES)
+            .map(|(offset, _)| offset)
+            .chain([0])
+            .filter_map(|offset| self.aligned_similarity(&other.0, offset))
+            .fold(0.0, f32::max)
+    }
+
+    /// Similarity with `other` shifted by `offset` frames; `None` when they barely overlap
+    fn aligned_similarity(&self, other: &[u32], offset: isize) -> Option<f32> {
+        let pairs: Vec<(u32, u32)> = other
+            .iter()
+            .enumerate()
+            .filter_map(|(j, b)| {
+                let i = usize::try_from(j as isize + offset).ok()?;
+                Some((*self.codes.get(i)?, *b))
+            })
+            .collect();
+        let required = MIN_OVERLAP.min(self.codes.len()).min(other.len());
+        if pairs.is_empty() || pairs.len() < required {
+            return None;
+        }
+        let errors: u32 = pairs.iter().map(|(a, b)| (a ^ b).count_ones()).sum();
+        let error_rate = errors as f32 / (pairs.len() * 32) as f32;
+        // Unrelated audio differs in about half the bits
+        Some((1.0 - 2.0 * error_rate).max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = FINGERPRINT_SAMPLE_RATE;
+
+    fn midi_freq(note: u8) -> f32 {
+        440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+    }
+
+    /// A chord progression, each chord held for `seconds`
+    fn synth(chords: &[[u8; 3]], seconds: f32) -> Vec<f32> {
+        let per_chord = (RATE as f32 * seconds) as usize;
+        let mut samples = Vec::with_capacity(per_chord * chords.len());
+        for chord in chords {
+            for n in 0..per_chord {
+                let t = n as f32 / RATE as f32;
+                let sample: f32 = chord
+                    .iter()
+                    .map(|note| (2.0 * std::f32::consts::PI * midi_freq(*note) * t).sin())
+                    .sum();
+                samples.push(sample * 0.2);
+            }
+        }
+        samples
+    }
+
+    /// Pseudo-random chords from a seed, so tests are repeatable
+    fn progression(seed: u64, len: usize) -> Vec<[u8; 3]> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let root = 48 + (state >> 33) as u8 % 12;
+                let minor = (state >> 40) & 1 == 1;
+                [root, root + if minor { 3 } else { 4 }, root + 7]
+            })
+            .collect()
+    }
+
+    fn noisy(samples: &[f32], gain: f32) -> Vec<f32> {
+        let mut state = 1u32;
+        samples
+            .iter()
+            .map(|s| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = (state >> 16) as f32 / 65536.0 - 0.5;
+                s * gain + noise * 0.02
+            })
+            .collect()
+    }
+
+    #[test]
+    fn survives_gain_noise_and_a_different_intro() {
+        let track = synth(&progression(7, 30), 1.5);
+        let original = from_samples(&track, RATE);
+
+        // Another upload: quieter, noisier and with a few seconds of something else first
+        let mut upload = synth(&progression(99, 3), 1.0);
+        upload.extend(noisy(&track, 0.6));
+        let upload = from_samples(&upload, RATE);
+
+        let similarity = Matcher::new(&original).similarity(&upload);
+        assert!(similarity > 0.7, "similarity {similarity}");
+    }
+
+    #[test]
+    fn different_tracks_do_not_match() {
+        let a = from_samples(&synth(&progression(1, 30), 1.5), RATE);
+        let b = from_samples(&synth(&progression(2, 30), 1.5), RATE);
+        let similarity = Matcher::new(&a).similarity(&b);
+        assert!(similarity < 0.5, "similarity {similarity}");
+    }
+
+    #[test]
+    fn silence_is_left_out() {
+        assert!(
+            from_samples(&vec![0.0; RATE as usize * 10], RATE)
+                .0
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn round_trips_through_hex() {
+        let fingerprint = Fingerprint(vec![0, 0xdeadbeef, u32::MAX]);
+        let json = serde_json::to_string(&fingerprint).unwrap();
+        assert_eq!(json, "\"00000000deadbeefffffffff\"");
+        assert_eq!(
+            serde_json::from_str::<Fingerprint>(&json).unwrap(),
+            fingerprint
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Musical key estimation from a chromagram.
+//!
+//! Magnitude spectra are folded into 12 pitch classes, summed over the whole
+//! track and correlated against the Krumhansl-Kessler major and minor key
+//! profiles in all 12 rotations. The best-correlating profile wins.
+
+use rustfft::{FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
+
+use crate::config::KeyNotation;
+
+/// FFT size; at 22.05 kHz this gives ~2.7 Hz bins, enough to separate low semitones
+const FFT_SIZE: usize = 8192;
+/// Hop between analysis frames
+const HOP_SIZE: usize = 4096;
+/// Lowest frequency folded into the chromagram (A1)
+const MIN_FREQ: f32 = 55.0;
+/// Highest frequency folded into the chromagram (~B6)
+const MAX_FREQ: f32 = 2000.0;
+
+/// Krumhansl-Kessler probe-tone profile for major keys, starting at the tonic
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+/// Krumhansl-Kessler probe-tone profile for minor keys, starting at the tonic
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Pitch class names, C = 0
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// A detected key: tonic pitch class (C = 0) plus mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MusicalKey {
+    pub tonic: u8,
+    pub mode: Mode,
+}
+
+impl MusicalKey {
+    /// Standard notation, e.g. "A minor"
+    pub fn standard(&self) -> String {
+        let mode = match self.mode {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+        };
+        format!("{} {}", NOTE_NAMES[self.tonic as usize], mode)
+    }
+
+    /// Short notation DJ software uses in its libraries, e.g. "Am" or "F#"
+    pub fn short(&self) -> String {
+        let suffix = match self.mode {
+            Mode::Major => "",
+            Mode::Minor => "m",
+        };
+        format!("{}{suffix}", NOTE_NAMES[self.tonic as usize])
+    }
+
+    /// Camelot wheel notation, e.g. "8A" for A minor and "8B" for C major
+    pub fn camelot(&self) -> String {
+        // Minor keys share a number with their relative major, three semitones up
+        let (major_tonic, letter) = match self.mode {
+            Mode::Major => (self.tonic as usize, 'B'),
+            Mode::Minor => ((self.tonic as usize + 3) % 12, 'A'),
+        };
+        // Each step round the wheel is a fifth (7 semitones); C major sits at 8
+        let number = (major_tonic * 7 + 7) % 12 + 1;
+        format!("{number}{letter}")
+    }
+
+    /// Format in whichever notation the user picked
+    pub fn format(&self, notation: KeyNotation) -> String {
+        match notation {
+            KeyNotation::Standard => self.standard(),
+            KeyNotation::Camelot => self.camelot(),
+        }
+    }
+
+    /// Read a key in Camelot ("8A"), short ("Am", "F#", "Dbm") or standard ("A minor")
+    /// notation, as other tools write it into tags
+    pub fn parse(text: &str) -> Option<Self> {
+        let text: String = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase();
+
+        let all = (0..12u8)
+            .flat_map(|tonic| [Mode::Major, Mode::Minor].map(|mode| MusicalKey { tonic, mode }));
+        if let Some(key) = all
+            .into_iter()
+            .find(|key| key.camelot().to_lowercase() == text)
+        {
+            return Some(key);
+        }
+
+        let mut chars = text.chars();
+        let natural: u8 = match chars.next()? {
+            'c' => 0,
+            'd' => 2,
+            'e' => 4,
+            'f' => 5,
+            'g' => 7,
+            'a' => 9,
+            'b' => 11,
+            _ => return None,
+        };
+        let rest = chars.as_str();
+        let (shift, rest) = if let Some(rest) = rest.strip_prefix(['#', '♯']) {
+            (1, rest)
+        } else if let Some(rest) = rest.strip_prefix(['b', '♭']) {
+            (11, rest)
+        } else {
+            (0, rest)
+        };
+        let mode = match rest {
+            "" | "maj" | "major" => Mode::Major,
+            "m" | "min" | "minor" => Mode::Minor,
+            _ => return None,
+        };
+        Some(MusicalKey {
+            tonic: (natural + shift) % 12,
+            mode,
+        })
+    }
+}
+
+/// Estimate the key of a mono signal
+pub fn estimate_key(samples: &[f32], sample_rate: u32) -> Option<MusicalKey> {
+    let chroma = chromagram(samples, sample_rate)?;
+
+    let mut best: Option<(MusicalKey, f32)> = None;
+    for tonic in 0..12u8 {
+        for (mode, profile) in [(Mode::Major, &MAJOR_PROFILE), (Mode::Minor, &MINOR_PROFILE)] {
+            // Rotate the profile so index 0 lines up with pitch class C
+            let rotated: Vec<f32> = (0..12)
+                .map(|pc| profile[(pc + 12 - tonic as usize) % 12])
+                .collect();
+            let score = correlation(&chroma, &rotated);
+            if best.is_none_or(|(_, s)| score > s) {
+                best = Some((MusicalKey { tonic, mode }, score));
+            }
+        }
+    }
+
+    best.map(|(key, _)| key)
+}
+
+/// Sum of per-frame normalised pitch-class energy over the whole signal
+fn chromagram(samples: &[f32], sample_rate: u32) -> Option<[f32; 12]> {
+    if samples.len() < FFT_SIZE {
+        return None;
+    }
+
+    // Map each FFT bin in range to its nearest pitch class once up front
+    let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+    let bin_classes: Vec<(usize, usize)> = (1..FFT_SIZE / 2)
+        .filter_map(|bin| {
+            let freq = bin as f32 * bin_hz;
+            if !(MIN_FREQ..=MAX_FREQ).contains(&freq) {
+                return None;
+            }
+            let midi = 69.0 + 12.0 * (freq / 440.0).log2();
+            Some((bin, midi.round() as usize % 12))
+        })
+        .collect();
+
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos())
+        .collect();
+
+    let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); FFT_SIZE];
+    let mut chroma = [0.0f32; 12];
+
+    for frame in samples.windows(FFT_SIZE).step_by(HOP_SIZE) {
+        for ((slot, sample), w) in buffer.iter_mut().zip(frame).zip(&window) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+
+        let mut frame_chroma = [0.0f32; 12];
+        for &(bin, class) in &bin_classes {
+            frame_chroma[class] += buffer[bin].norm();
+        }
+
+        // Normalise per frame so loud passages don't dominate the key decision
+        let total: f32 = frame_chroma.iter().sum();
+        if total > f32::EPSILON {
+            for (sum, value) in chroma.iter_mut().zip(frame_chroma) {
+                *sum += value / total;
+            }
+        }
+    }
+
+    chroma.iter().any(|&v| v > 0.0).then_some(chroma)
+}
+
+/// Pearson correlation of two equal-length vectors
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let mean_a = a.iter().sum::<f32>() / n;
+    let mean_b = b.iter().sum::<f32>() / n;
+
+    let mut cov = 0.0;
+    let mut var_a = 0.0;
+    let mut var_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        let (da, db) = (x - mean_a, y - mean_b);
+        cov += da * db;
+        var_a += da * da;
+        var_b += db * db;
+    }
+
+    if var_a <= f32::EPSILON || var_b <= f32::EPSILON {
+        return 0.0;
+    }
+    cov / (var_a.sqrt() * var_b.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 22050;
+
+    /// Frequency of a MIDI note number
+    fn midi_freq(note: u8) -> f32 {
+        440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+    }
+
+    /// Render a sequence of chords (MIDI notes), each held for `seconds`
+    fn synth_chords(chords: &[&[u8]], seconds: f32) -> Vec<f32> {
+        let len = (RATE as f32 * seconds) as usize;
+        let mut out = Vec::with_capacity(len * chords.len());
+        for chord in chords {
+            for i in 0..len {
+                let t = i as f32 / RATE as f32;
+                let sample: f32 = chord
+                    .iter()
+                    .map(|&n| (2.0 * std::f32::consts::PI * midi_freq(n) * t).sin())
+                    .sum();
+                out.push(sample / chord.len() as f32);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn parses_common_notations() {
+        let a_minor = MusicalKey {
+            tonic: 9,
+            mode: Mode::Minor,
+        };
+        for text in ["8A", "8a", "Am", "A minor", "Amin"] {
+            assert_eq!(MusicalKey::parse(text), Some(a_minor), "{text}");
+        }
+        let d_flat = MusicalKey {
+            tonic: 1,
+            mode: Mode::Major,
+        };
+        assert_eq!(MusicalKey::parse("C#"), Some(d_flat));
+        assert_eq!(MusicalKey::parse("Db major"), Some(d_flat));
+        assert_eq!(MusicalKey::parse("3B"), Some(d_flat));
+        assert_eq!(MusicalKey::parse("H"), None);
+        assert_eq!(MusicalKey::parse("13A"), None);
+    }
+
+    #[test]
+    fn detects_c_major_from_cadence() {
+        // I - IV - V - I in C major
+        let samples = synth_chords(
+            &[
+                &[48, 60, 64, 67],
+                &[53, 60, 65, 69],
+                &[55, 59, 62, 67],
+                &[48, 60, 64, 67],
+            ],
+            1.5,
+        );
+        let key = estimate_key(&samples, RATE).unwrap();
+        assert_eq!(
+            key,
+            MusicalKey {
+                tonic: 0,
+                mode: Mode::Major
+            }
+        );
+    }
+
+    #[test]
+    fn detects_a_minor_from_cadence() {
+        // i - iv - V - i in A minor (harmonic minor dominant with G#)
+        let samples = synth_chords(
+            &[
+                &[45, 57, 60, 64],
+                &[50, 57, 62, 65],
+                &[52, 56, 59, 64],
+                &[45, 57, 60, 64],
+            ],
+            1.5,
+        );
+        let key = estimate_key(&samples, RATE).unwrap();
+        assert_eq!(
+            key,
+            MusicalKey {
+                tonic: 9,
+                mode: Mode::Minor
+            }
+        );
+        assert_eq!(key.standard(), "A minor");
+        assert_eq!(key.camelot(), "8A");
+    }
+
+    #[test]
+    fn detects_transposed_key() {
+        // I - IV - V - I in G major
+        let samples = synth_chords(
+            &[
+                &[43, 55, 59, 62],
+                &[48, 60, 64, 67],
+                &[50, 57, 62, 66],
+                &[43, 55, 59, 62],
+            ],
+            1.5,
+        );
+        let key = estimate_key(&samples, RATE).unwrap();
+        assert_eq!(key.format(KeyNotation::Camelot), "9B");
+        assert_eq!(key.format(KeyNotation::Standard), "G major");
+    }
+
+    #[test]
+    fn silence_has_no_key() {
+        assert_eq!(estimate_key(&vec![0.0; RATE as usize * 2], RATE), None);
+    }
+
+    #[test]
+    fn camelot_wheel_matches_reference() {
+        let cases = [
+            (0, Mode::Major, "8B"),
+            (7, Mode::Major, "9B"),
+            (11, Mode::Major, "1B"),
+            (5, Mode::Major, "7B"),
+            (9, Mode::Minor, "8A"),
+            (4, Mode::Minor, "9A"),
+            (8, Mode::Minor, "1A"),
+            (0, Mode::Minor, "5A"),
+            (2, Mode::Minor, "7A"),
+        ];
+        for (tonic, mode, expected) in cases {
+            assert_eq!(MusicalKey { tonic, mode }.camelot(), expected);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use color_eyre::{Result, eyre::eyre};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tracing::info;
+
+use crate::config::AnalysisConfig;
+use crate::shutdown;
+use crate::tags;
+use key::MusicalKey;
+
+pub mod bpm;
+pub mod fingerprint;
+pub mod key;
+pub mod quality;
+
+/// Sample rate used for all analysis passes (plenty for tempo and pitch)
+pub const ANALYSIS_SAMPLE_RATE: u32 = 22050;
+
+/// Results of analysing a finished download
+#[derive(Debug, Clone, Default)]
+pub struct TrackAnalysis {
+    /// Estimated tempo in beats per minute
+    pub bpm: Option<f32>,
+    /// Estimated musical key
+    pub key: Option<MusicalKey>,
+    /// Offset of the first downbeat in seconds, for setting a cue point
+    pub first_downbeat: Option<f32>,
+}
+
+/// Decode any audio file ffmpeg understands into mono f32 samples
+pub async fn decode_mono(path: &Path, sample_rate: u32) -> Result<Vec<f32>> {
+    decode(path, sample_rate, None, None).await
+}
+
+/// Decode only the first `seconds` of a file
+pub async fn decode_mono_head(path: &Path, sample_rate: u32, seconds: u32) -> Result<Vec<f32>> {
+    decode(path, sample_rate, None, Some(seconds)).await
+}
+
+/// Decode `seconds` of a file starting `start` seconds in
+pub async fn decode_mono_excerpt(
+    path: &Path,
+    sample_rate: u32,
+    start: f64,
+    seconds: u32,
+) -> Result<Vec<f32>> {
+    decode(path, sample_rate, Some(start), Some(seconds)).await
+}
+
+async fn decode(
+    path: &Path,
+    sample_rate: u32,
+    start: Option<f64>,
+    seconds: Option<u32>,
+) -> Result<Vec<f32>> {
+    let mut cmd = shutdown::command("ffmpeg");
+    let rate_arg = sample_rate.to_string();
+    cmd.args(["-v", "error", "-nostdin"]);
+    // Seeking before the input skips straight there instead of decoding the way
+    if let Some(start) = start {
+        cmd.args(["-ss", &format!("{start:.3}")]);
+    }
+    cmd.arg("-i").arg(path);
+    if let Some(seconds) = seconds {
+        cmd.args(["-t", &seconds.to_string()]);
+    }
+    cmd.args(["-ac", "1", "-ar", &rate_arg, "-f", "f32le", "-"]);
+
+    // Only stdout carries the samples; keep stderr out of the TUI
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|_| eyre!("ffmpeg not found. Please install: brew install ffmpeg"))?;
+
+    if !output.status.success() {
+        return Err(eyre!("ffmpeg could not decode {}", path.display()));
+    }
+
+    let samples = output
+        .stdout
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    Ok(samples)
+}
+
+/// Decode a finished download, run every enabled analysis and write the results to its tags
+pub async fn analyze_track(path: PathBuf, config: AnalysisConfig) -> Result<TrackAnalysis> {
+    if !config.enabled() {
+        return Ok(TrackAnalysis::default());
+    }
+
+    let samples = decode_mono(&path, ANALYSIS_SAMPLE_RATE).await?;
+    info!("Decoded {} samples from {}", samples.len(), path.display());
+
+    // The DSP is CPU-bound, so keep it off the async workers
+    let analysis = tokio::task::spawn_blocking(move || {
+        let bpm = config
+            .bpm
+            .then(|| {
+                bpm::estimate_bpm(
+                    &samples,
+                    ANALYSIS_SAMPLE_RATE,
+                    config.bpm_min,
+                    config.bpm_max,
+                )
+            })
+            .flatten();
+        let first_downbeat =
+            bpm.and_then(|bpm| bpm::first_downbeat(&samples, ANALYSIS_SAMPLE_RATE, bpm));
+        let key = config
+            .key
+            .then(|| key::estimate_key(&samples, ANALYSIS_SAMPLE_RATE))
+            .flatten();
+
+        let analysis = TrackAnalysis {
+            bpm,
+            key,
+            first_downbeat,
+        };
+        tags::write_analysis(&path, &analysis, config.key_notation)?;
+
+        Ok::<_, color_eyre::Report>(analysis)
+    })
+    .await??;
+
+    Ok(analysis)
+}
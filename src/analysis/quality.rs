@@ -0,0 +1,283 @@
+//! Source quality check: what yt-dlp delivered, and how much bandwidth survived.
+//!
+//! Lossy encoders low-pass the signal before they throw bits away, so a
+//! transcode of a 96 kbps file stays band-limited however high the output
+//! bitrate. The averaged spectrum of the decoded file shows that as a cliff
+//! down to the noise floor; where the cliff sits is the effective bandwidth.
+
+use color_eyre::Result;
+use rustfft::{FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::config::QualityConfig;
+use crate::postprocess;
+use crate::tags;
+use crate::ytdlp::Format;
+
+/// Full-band rate, so the check can see everything up to 22 kHz
+pub const QUALITY_SAMPLE_RATE: u32 = 44100;
+/// FFT size; ~10.8 Hz bins at 44.1 kHz
+const FFT_SIZE: usize = 4096;
+/// Hop between analysed frames; the long-term average doesn't need every frame
+const HOP_SIZE: usize = FFT_SIZE * 4;
+/// Width of the smoothing applied to the averaged spectrum, in Hz
+const SMOOTHING_HZ: f32 = 200.0;
+/// How far the spectrum must fall past the cutoff, in dB...
+const CLIFF_DEPTH_DB: f32 = 25.0;
+/// ...within this distance above it, in Hz
+const CLIFF_WIDTH_HZ: f32 = 1000.0;
+/// Lowest cutoff reported; anything below is the music, not the encoder
+const MIN_CUTOFF_HZ: f32 = 5000.0;
+/// Stretches of the file measured, spread across it so a long mix isn't decoded whole...
+const EXCERPTS: usize = 4;
+/// ...each this long
+const EXCERPT_SECONDS: u32 = 30;
+
+/// The stream yt-dlp downloaded from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceAudio {
+    /// Audio codec, e.g. "opus" or "mp4a.40.2"
+    pub codec: String,
+    /// Average bitrate in kbps
+    pub kbps: f32,
+}
+
+impl SourceAudio {
+    /// The audio side of a yt-dlp format, if it reports a codec and bitrate
+    pub fn from_format(format: &Format) -> Option<Self> {
+        Some(Self {
+            codec: format.acodec.clone()?,
+            kbps: format.abr? as f32,
+        })
+    }
+
+    /// Codec and bitrate as yt-dlp prints them with `%(acodec)s %(abr)s`; "NA" or "none"
+    /// when it doesn't know
+    pub fn from_print(line: &str) -> Option<Self> {
+        let (codec, kbps) = line.trim().split_once(' ')?;
+        if codec == "NA" || codec == "none" {
+            return None;
+        }
+        Some(Self {
+            codec: codec.to_string(),
+            kbps: kbps.parse().ok()?,
+        })
+    }
+}
+
+/// What we know about the quality of a downloaded file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityInfo {
+    /// The stream it was encoded from
+    pub source: Option<SourceAudio>,
+    /// Highest frequency with real content, in Hz
+    pub cutoff_hz: Option<f32>,
+    /// Bandwidth is narrower than a decent source would give
+    pub low_quality: bool,
+}
+
+impl QualityInfo {
+    /// One-line summary for tags and logs, e.g. "opus 160kbps source, 19.8 kHz bandwidth"
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(source) = &self.source {
+            parts.push(format!("{} {:.0}kbps source", source.codec, source.kbps));
+        }
+        if let Some(cutoff) = self.cutoff_hz {
+            parts.push(format!("{:.1} kHz bandwidth", cutoff / 1000.0));
+        }
+        if self.low_quality {
+            parts.push("low-quality source".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Record the source stream, measure the bandwidth of `path` and note both in its tags
+pub async fn check(
+    path: &Path,
+    source: Option<SourceAudio>,
+    config: &QualityConfig,
+) -> Result<QualityInfo> {
+    let duration = match postprocess::probe_duration(path).await {
+        Ok(duration) => duration,
+        Err(e) => {
+            // Measure from the start instead
+            warn!("{}", e);
+            0.0
+        }
+    };
+    let mut excerpts = Vec::new();
+    for start in excerpt_starts(duration) {
+        excerpts.push(
+            super::decode_mono_excerpt(path, QUALITY_SAMPLE_RATE, start, EXCERPT_SECONDS).await?,
+        );
+    }
+    let cutoff_hz =
+        tokio::task::spawn_blocking(move || spectral_cutoff(&excerpts, QUALITY_SAMPLE_RATE))
+            .await?;
+
+    let quality = QualityInfo {
+        low_quality: cutoff_hz.is_some_and(|cutoff| cutoff < config.min_cutoff_hz),
+        source,
+        cutoff_hz,
+    };
+    info!("Quality of {}: {}", path.display(), quality.summary());
+
+    let (tag_path, tag_quality) = (path.to_path_buf(), quality.clone());
+    tokio::task::spawn_blocking(move || tags::write_quality(&tag_path, &tag_quality)).await??;
+
+    Ok(quality)
+}
+
+/// Where each excerpt starts: evenly spread through the file, or just the start when the
+/// file is short enough for that to cover it
+fn excerpt_starts(duration: f64) -> Vec<f64> {
+    let length = f64::from(EXCERPT_SECONDS);
+    if duration <= length * EXCERPTS as f64 {
+        return vec![0.0];
+    }
+    (1..=EXCERPTS)
+        .map(|i| (duration - length) * i as f64 / (EXCERPTS + 1) as f64)
+        .collect()
+}
+
+/// Effective bandwidth in Hz: where the long-term spectrum of the excerpts drops off a
+/// cliff, or Nyquist when it never does. `None` for silence or clips too short to measure.
+pub fn spectral_cutoff(excerpts: &[Vec<f32>], sample_rate: u32) -> Option<f32> {
+    if excerpts.iter().all(|samples| samples.len() < FFT_SIZE) {
+        return None;
+    }
+
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos())
+        .collect();
+
+    let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); FFT_SIZE];
+    let mut power = vec![0.0f64; FFT_SIZE / 2];
+    let mut frames = 0;
+
+    // Frames never straddle two excerpts, whose join would read as a broadband click
+    let windows = excerpts
+        .iter()
+        .flat_map(|samples| samples.windows(FFT_SIZE).step_by(HOP_SIZE));
+    for frame in windows {
+        for ((slot, sample), w) in buffer.iter_mut().zip(frame).zip(&window) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (sum, bin) in power.iter_mut().zip(&buffer) {
+            *sum += f64::from(bin.norm_sqr());
+        }
+        frames += 1;
+    }
+
+    if power.iter().all(|&p| p <= f64::from(f32::EPSILON)) {
+        return None;
+    }
+
+    // Smooth over neighbouring bins so single tonal peaks don't read as bandwidth
+    let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+    let radius = (SMOOTHING_HZ / bin_hz / 2.0).ceil() as usize;
+    let levels: Vec<f32> = (0..power.len())
+        .map(|bin| {
+            let range = bin.saturating_sub(radius)..(bin + radius + 1).min(power.len());
+            let mean = power[range.clone()].iter().sum::<f64>() / (range.len() * frames) as f64;
+            10.0 * (mean.max(1e-20)).log10() as f32
+        })
+        .collect();
+
+    // The cutoff is the highest point where everything a little further up sits far below it.
+    // Natural high-frequency roll-off is far too gradual to pass this.
+    let first = (MIN_CUTOFF_HZ / bin_hz) as usize;
+    let gap = (CLIFF_WIDTH_HZ / bin_hz).ceil() as usize;
+    let mut ceiling = f32::NEG_INFINITY;
+    for bin in (first..levels.len().saturating_sub(gap)).rev() {
+        ceiling = ceiling.max(levels[bin + gap]);
+        if levels[bin] > ceiling + CLIFF_DEPTH_DB {
+            return Some(bin as f32 * bin_hz);
+        }
+    }
+
+    // No cliff anywhere: the file is full-band
+    Some(sample_rate as f32 / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = QUALITY_SAMPLE_RATE;
+
+    /// White noise in -0.5..0.5
+    fn noise(len: usize, seed: u32) -> impl Iterator<Item = f32> {
+        let mut state = seed;
+        (0..len).map(move |_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+        })
+    }
+
+    /// White noise with everything above `cutoff_hz` removed, as a lossy encoder would,
+    /// over the faint full-band floor a real file always has
+    fn low_passed_noise(cutoff_hz: f32) -> Vec<f32> {
+        let len = 1 << 18;
+        let mut buffer: Vec<Complex<f32>> = noise(len, 7).map(|n| Complex::new(n, 0.0)).collect();
+
+        let mut planner = FftPlanner::new();
+        planner.plan_fft_forward(len).process(&mut buffer);
+        let last = (cutoff_hz / RATE as f32 * len as f32) as usize;
+        for (bin, value) in buffer.iter_mut().enumerate() {
+            // Both the positive and the mirrored negative frequencies
+            if bin > last && bin < len - last {
+                *value = Complex::new(0.0, 0.0);
+            }
+        }
+        planner.plan_fft_inverse(len).process(&mut buffer);
+        buffer
+            .iter()
+            .zip(noise(len, 11))
+            .map(|(c, floor)| c.re / len as f32 + floor * 3e-4)
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_encoder_cutoff() {
+        for cutoff in [11000.0, 16000.0] {
+            let measured = spectral_cutoff(&[low_passed_noise(cutoff)], RATE).unwrap();
+            assert!(
+                (measured - cutoff).abs() < 300.0,
+                "expected {cutoff} Hz, measured {measured}"
+            );
+        }
+        // Full-band noise has no cliff at all
+        assert_eq!(
+            spectral_cutoff(&[low_passed_noise(22050.0)], RATE),
+            Some(22050.0)
+        );
+        assert_eq!(spectral_cutoff(&[vec![0.0; 1 << 16]], RATE), None);
+    }
+
+    #[test]
+    fn long_files_are_only_sampled() {
+        assert_eq!(excerpt_starts(95.0), [0.0]);
+        let starts = excerpt_starts(2.0 * 3600.0);
+        assert_eq!(starts.len(), EXCERPTS);
+        assert!(starts.iter().all(|&s| s > 0.0 && s + 30.0 < 2.0 * 3600.0));
+    }
+
+    #[test]
+    fn reads_the_printed_source() {
+        assert_eq!(
+            SourceAudio::from_print("opus 129.478\n"),
+            Some(SourceAudio {
+                codec: "opus".to_string(),
+                kbps: 129.478
+            })
+        );
+        assert_eq!(SourceAudio::from_print("NA NA"), None);
+    }
+}
@@ -1,16 +1,49 @@
 use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use futures::StreamExt;
+use ratatui::layout::{Position, Rect};
 use ratatui::{DefaultTerminal, Frame};
 // Removed ratatui_input for simplicity
 use regex::Regex;
+use std::collections::VecDeque;
 use std::path::PathBuf;
-use std::time::{Duration, UNIX_EPOCH};
-use std::{fs, process::Stdio};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
 use tracing::{error, info, warn};
 
+use crate::analysis::quality::SourceAudio;
+use crate::clip::{self, Clip, ClipRequest};
+use crate::config::Config;
+use crate::download::{self, Downloaded};
+use crate::duplicates;
+use crate::export::{self, playlist};
+use crate::filter::{Candidate, Query};
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::keymap::{Action, Key, KeyMap};
+use crate::library::{self, LibraryFile, ScanReport, SortColumn};
+use crate::postprocess::split::{SplitPlan, SplitTrack};
+use crate::postprocess::{self, ProcessReport};
+use crate::routing;
+use crate::shutdown::{self, Signals};
+use crate::theme::Theme;
+use crate::tracklist::{self, Segment};
+use crate::ui::{self, Button, ScreenLayout};
+use crate::ytdlp::{self, SearchResult, VideoInfo};
+
 // Maximum input length to prevent memory issues and UI corruption
 const MAX_INPUT_LENGTH: usize = 500;
 const MAX_PASTE_LENGTH: usize = 10000;
+/// Status messages kept in the log pane
+const MAX_LOG_LINES: usize = 500;
+/// Rows PageUp/PageDown move in the history and details panes
+const PAGE: usize = 10;
+/// Time between spinner frames while something is in progress
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Application state
 #[derive(Debug)]
@@ -23,10 +56,368 @@ pub struct App {
     pub status_message: String,
     /// Download status
     pub download_status: DownloadStatus,
-    /// Focus state (Input or Convert button)
+    /// Pane that takes the keys; Tab and Shift+Tab cycle it
     pub focus: Focus,
-    /// Download history for display
-    pub download_history: Vec<String>,
+    /// Selection and scroll position of each pane
+    pub panes: Panes,
+    /// Every status message so far, oldest first, for the log pane
+    pub log: Vec<String>,
+    /// Bitrate Enter and the Download button use; the format button toggles it
+    pub bitrate: u32,
+    /// The last download that failed, for the Retry button
+    pub last_failed: Option<QueuedDownload>,
+    /// Size of the last frame drawn, for mapping mouse clicks to panes
+    screen: Rect,
+    /// Persistent download history
+    pub history: HistoryStore,
+    /// User configuration
+    pub config: Config,
+    /// Colours, from `ui.theme` fitted to the terminal
+    pub theme: Theme,
+    /// Global key bindings, from the defaults and `[keys]`
+    pub keymap: KeyMap,
+    /// Spinner frame, advanced while something is in progress
+    pub spinner: usize,
+    /// Set once quitting has been asked for while work was still running
+    pub quitting: Option<Quitting>,
+    /// Background tasks, stopped on quit
+    tasks: JoinSet<()>,
+    /// Lines scrolled down in the help overlay, while it's open
+    pub help: Option<u16>,
+    /// Trim leading/trailing silence from the next download (Ctrl+T)
+    pub trim_silence: bool,
+    /// Split the next download into tracks by chapters or tracklist (Ctrl+S)
+    pub split_mix: bool,
+    /// Timestamped tracklist captured from the last paste, used when splitting
+    pub pending_tracklist: Option<Vec<Segment>>,
+    /// YouTube search in progress or showing results
+    pub search: Option<SearchState>,
+    /// Downloads waiting their turn
+    pub queue: VecDeque<QueuedDownload>,
+    /// Details of the URL waiting for confirmation
+    pub preview: Option<PreviewState>,
+    /// This run of the app; downloads are stamped with it and it names the session playlist
+    pub session: u64,
+    /// Crate panel, while it's open (Ctrl+K)
+    pub crates: Option<CratePanel>,
+    /// Crate new downloads are added to
+    pub target_crate: Option<String>,
+    /// History search, while it's open (`/` with an empty input)
+    pub history_filter: Option<HistoryFilter>,
+    /// Library table, while it's open (Ctrl+L)
+    pub library: Option<LibraryView>,
+    /// A library scan is running
+    pub library_scanning: bool,
+    /// The library has been scanned since the app started
+    library_scanned: bool,
+    /// Sender handed to background tasks
+    jobs_tx: mpsc::UnboundedSender<JobEvent>,
+    /// Results coming back from background tasks
+    jobs_rx: mpsc::UnboundedReceiver<JobEvent>,
+}
+
+/// Messages sent from background tasks back to the UI loop
+#[derive(Debug)]
+pub enum JobEvent {
+    /// Post-download processing finished for history entry `id`
+    Processed {
+        id: u64,
+        result: Result<Box<ProcessReport>, String>,
+    },
+    /// A YouTube search finished
+    SearchResults {
+        query: String,
+        result: Result<Vec<SearchResult>, String>,
+    },
+    /// Metadata for a previewed URL arrived
+    Preview {
+        url: String,
+        result: Result<Box<VideoInfo>, String>,
+    },
+    /// A mix (history entry `parent`) was split into separate tracks
+    Split {
+        parent: u64,
+        url: String,
+        bitrate: u32,
+        result: Result<Vec<SplitTrack>, String>,
+    },
+    /// A session or crate playlist was written
+    Playlist { result: Result<PathBuf, String> },
+    /// A library scan finished
+    LibraryScanned { result: Result<ScanReport, String> },
+    /// The duplicate check for history entry `id` finished; holds the name of a file that sounds the same
+    Duplicate { id: u64, duplicate: Option<String> },
+    /// The running download moved on to its next step
+    DownloadProgress { message: String },
+    /// The running download finished; `download` is what was submitted, for a retry
+    Downloaded {
+        download: Box<QueuedDownload>,
+        result: Result<Box<Downloaded>, String>,
+    },
+}
+
+/// A download waiting in the queue
+#[derive(Debug, Clone)]
+pub struct QueuedDownload {
+    pub url: String,
+    /// Title shown while it waits
+    pub title: String,
+    pub bitrate: u32,
+    /// Clip range typed after the URL
+    pub clip_request: Option<ClipRequest>,
+    /// Metadata already fetched for the preview
+    pub info: Option<Box<VideoInfo>>,
+    /// Trim silence, as toggled when it was submitted
+    pub trim_silence: bool,
+    /// Split into tracks, as toggled when it was submitted
+    pub split: bool,
+    /// A pasted tracklist to split by
+    pub tracklist: Option<Vec<Segment>>,
+}
+
+/// A submitted URL waiting for the user to confirm the download
+#[derive(Debug, Clone)]
+pub struct PreviewState {
+    pub url: String,
+    pub clip_request: Option<ClipRequest>,
+    /// Output bitrate the download will use
+    pub bitrate: u32,
+    /// Metadata, once yt-dlp has answered
+    pub info: Option<VideoInfo>,
+    /// Why the metadata couldn't be fetched
+    pub error: Option<String>,
+}
+
+impl PreviewState {
+    /// Source audio bitrate when it's lower than the chosen output bitrate
+    pub fn upscaled_from(&self) -> Option<f64> {
+        let abr = self.info.as_ref()?.best_audio()?.abr?;
+        (abr < f64::from(self.bitrate)).then_some(abr)
+    }
+}
+
+/// Search query and the results being browsed
+#[derive(Debug, Clone)]
+pub struct SearchState {
+    pub query: String,
+    /// Still waiting for yt-dlp
+    pub loading: bool,
+    pub results: Vec<SearchResult>,
+    /// Highlighted row
+    pub selected: usize,
+    /// Rows marked for queueing with Space
+    pub marked: Vec<bool>,
+}
+
+impl SearchState {
+    fn loading(query: String) -> Self {
+        Self {
+            query,
+            loading: true,
+            results: Vec::new(),
+            selected: 0,
+            marked: Vec::new(),
+        }
+    }
+
+    fn set_results(&mut self, results: Vec<SearchResult>) {
+        self.loading = false;
+        self.marked = vec![false; results.len()];
+        self.results = results;
+        self.selected = 0;
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.results.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some(mark) = self.marked.get_mut(self.selected) {
+            *mark = !*mark;
+        }
+    }
+
+    /// Marked results, or just the highlighted one when nothing is marked
+    fn picked(&self) -> Vec<SearchResult> {
+        let marked: Vec<SearchResult> = self
+            .results
+            .iter()
+            .zip(&self.marked)
+            .filter(|(_, marked)| **marked)
+            .map(|(r, _)| r.clone())
+            .collect();
+
+        if marked.is_empty() {
+            self.results
+                .get(self.selected)
+                .cloned()
+                .into_iter()
+                .collect()
+        } else {
+            marked
+        }
+    }
+}
+
+/// Which list in the crate panel has the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrateColumn {
+    /// Every download, newest first
+    History,
+    /// The crates themselves
+    Crates,
+    /// Tracks in the highlighted crate
+    Tracks,
+}
+
+/// Cursor positions in the crate panel
+#[derive(Debug, Clone)]
+pub struct CratePanel {
+    pub column: CrateColumn,
+    /// Highlighted row in each column
+    pub history_selected: usize,
+    pub crate_selected: usize,
+    pub track_selected: usize,
+    /// Name being typed for a new crate
+    pub new_name: Option<String>,
+}
+
+impl CratePanel {
+    fn new() -> Self {
+        Self {
+            column: CrateColumn::History,
+            history_selected: 0,
+            crate_selected: 0,
+            track_selected: 0,
+            new_name: None,
+        }
+    }
+
+    /// The download highlighted in the history column
+    pub fn history_entry<'a>(&self, history: &'a HistoryStore) -> Option<&'a HistoryEntry> {
+        history.entries.iter().rev().nth(self.history_selected)
+    }
+
+    /// Move the cursor in the focused column, keeping it inside its list
+    fn select(&mut self, history: &HistoryStore, up: bool) {
+        let (selected, len) = match self.column {
+            CrateColumn::History => (&mut self.history_selected, history.entries.len()),
+            CrateColumn::Crates => (&mut self.crate_selected, history.crates.len()),
+            CrateColumn::Tracks => (
+                &mut self.track_selected,
+                history
+                    .crates
+                    .get(self.crate_selected)
+                    .map_or(0, |c| c.tracks.len()),
+            ),
+        };
+        *selected = if up {
+            selected.saturating_sub(1)
+        } else {
+            (*selected + 1).min(len.saturating_sub(1))
+        };
+        if self.column == CrateColumn::Crates {
+            self.track_selected = 0;
+        }
+    }
+
+    /// Focus the next (or previous) column
+    fn cycle(&mut self, back: bool) {
+        self.column = match (self.column, back) {
+            (CrateColumn::History, false) | (CrateColumn::Tracks, true) => CrateColumn::Crates,
+            (CrateColumn::Crates, false) | (CrateColumn::History, true) => CrateColumn::Tracks,
+            (CrateColumn::Tracks, false) | (CrateColumn::Crates, true) => CrateColumn::History,
+        };
+    }
+}
+
+/// Sorting, filtering and cursor of the library table
+#[derive(Debug, Clone, Default)]
+pub struct LibraryView {
+    /// Highlighted row among the visible files
+    pub selected: usize,
+    pub sort: SortColumn,
+    pub descending: bool,
+    /// Only files matching this are shown
+    pub filter: String,
+    /// Keys are going into the filter (after `/`)
+    pub filtering: bool,
+}
+
+impl LibraryView {
+    /// Files passing the filter, in sort order
+    pub fn visible<'a>(&self, files: &'a [LibraryFile]) -> Vec<&'a LibraryFile> {
+        let query = Query::parse(&self.filter);
+        let mut visible: Vec<&LibraryFile> = files
+            .iter()
+            .filter(|f| query.is_empty() || query.score(&Candidate::from_file(f)).is_some())
+            .collect();
+        visible.sort_by(|a, b| {
+            let order = self.sort.compare(a, b);
+            if self.descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        visible
+    }
+
+    /// Move the cursor by `delta` rows, staying inside `len` rows
+    fn select(&mut self, delta: isize, len: usize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+}
+
+/// Live search over the download history, opened with `/`
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub query: String,
+    /// Highlighted row among the matches
+    pub selected: usize,
+}
+
+impl HistoryFilter {
+    /// Downloads matching the query, best match first; newest first when the query is empty
+    pub fn matches<'a>(&self, history: &'a HistoryStore) -> Vec<&'a HistoryEntry> {
+        let query = Query::parse(&self.query);
+        let mut matches: Vec<(u32, &HistoryEntry)> = history
+            .entries
+            .iter()
+            .rev()
+            .filter_map(|entry| {
+                let score = query.score(&Candidate::from_entry(entry, &history.crates))?;
+                Some((score, entry))
+            })
+            .collect();
+        // Stable, so equal scores stay newest first
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        matches.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+/// Per-download choices captured when the download is started
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Output bitrate in kbps
+    pub bitrate: u32,
+    /// Trim leading/trailing silence after download
+    pub trim_silence: bool,
+    /// Only download this part of the video
+    pub clip: Option<Clip>,
+    /// Split the download into one file per track
+    pub split: bool,
+    /// The stream yt-dlp downloads from, when already known from metadata
+    pub source: Option<SourceAudio>,
 }
 
 #[derive(Debug, Clone)]
@@ -37,9 +428,64 @@ pub enum DownloadStatus {
     Error(String),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The panes of the main screen, in Tab order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Focus {
+    #[default]
     Input,
+    Queue,
+    History,
+    Details,
+    Log,
+}
+
+impl Focus {
+    pub const ALL: [Focus; 5] = [
+        Focus::Input,
+        Focus::Queue,
+        Focus::History,
+        Focus::Details,
+        Focus::Log,
+    ];
+
+    /// The pane after this one, wrapping round
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    /// The pane before this one, wrapping round
+    pub fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|f| *f == self)
+            .unwrap_or_default()
+    }
+}
+
+/// Where quitting has got to while work is still running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quitting {
+    /// Asking whether to stop the running work or let it finish
+    Confirming,
+    /// Letting the running download and processing finish, then quitting
+    WhenIdle,
+}
+
+/// Selection and scroll position of each pane
+#[derive(Debug, Clone, Default)]
+pub struct Panes {
+    /// Highlighted queued download
+    pub queue: usize,
+    /// Highlighted history row, newest first
+    pub history: usize,
+    /// Lines scrolled down in the details pane
+    pub details_scroll: u16,
+    /// Lines scrolled back from the newest log message
+    pub log_scroll: u16,
 }
 
 impl Default for App {
@@ -50,40 +496,463 @@ impl Default for App {
 
 impl App {
     pub fn new() -> Self {
+        let (jobs_tx, jobs_rx) = mpsc::unbounded_channel();
+        let config = Config::load();
+        let mut history = HistoryStore::load();
+        let session = history.start_session();
+        info!("Started session {}", session);
+        let status_message = match &history.backup {
+            Some(backup) => format!(
+                "⚠️ The history file couldn't be read; kept it as {} and started a new one",
+                backup.display()
+            ),
+            None => "Paste a YouTube URL and press Enter to download MP3".to_string(),
+        };
         Self {
             running: true,
             input: String::new(),
-            status_message: "Paste a YouTube URL and press Enter to download MP3".to_string(),
+            status_message,
             download_status: DownloadStatus::Idle,
             focus: Focus::Input,
-            download_history: Vec::new(),
+            panes: Panes::default(),
+            log: Vec::new(),
+            bitrate: 128,
+            last_failed: None,
+            screen: Rect::default(),
+            history,
+            trim_silence: config.trim.enabled,
+            split_mix: false,
+            pending_tracklist: None,
+            search: None,
+            queue: VecDeque::new(),
+            preview: None,
+            session,
+            crates: None,
+            target_crate: None,
+            history_filter: None,
+            library: None,
+            library_scanning: false,
+            library_scanned: false,
+            theme: Theme::load(&config.ui),
+            keymap: KeyMap::new(&config.keys),
+            spinner: 0,
+            quitting: None,
+            tasks: JoinSet::new(),
+            help: None,
+            config,
+            jobs_tx,
+            jobs_rx,
         }
     }
 
-    /// Main application loop
+    /// Main application loop, then a clean shutdown however the loop ended
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         info!("Starting main app loop");
+        let result = self.event_loop(&mut terminal).await;
+        info!("App loop finished");
+
+        if !self.tasks.is_empty() {
+            self.status_message = "👋 Stopping background work and cleaning up...".to_string();
+            let _ = terminal.draw(|frame| self.draw(frame));
+        }
+        self.shutdown().await;
+        result
+    }
+
+    /// Wait for terminal events, background job messages, the spinner and signals, and
+    /// redraw only after one of them changed something
+    async fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut events = EventStream::new();
+        let mut signals = Signals::new()?;
+        let mut spinner = tokio::time::interval(SPINNER_INTERVAL);
+        spinner.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut dirty = true;
 
         while self.running {
-            // Draw UI
-            terminal.draw(|frame| self.draw(frame))?;
-            // Handle events
-            if event::poll(Duration::from_millis(100))? {
-                if let Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press {
-                        self.handle_key_event(key).await?;
+            let downloading = matches!(self.download_status, DownloadStatus::Downloading);
+            if self.quitting == Some(Quitting::WhenIdle) {
+                // Nothing new starts; quit once the running work is done
+                if !downloading && self.tasks.is_empty() {
+                    info!("Background work finished, quitting");
+                    break;
+                }
+            } else if !downloading && !self.queue.is_empty() {
+                // Start the next queued download once the previous one is done
+                self.process_queue();
+                dirty = true;
+            }
+            if dirty {
+                self.record_status();
+                terminal.draw(|frame| self.draw(frame))?;
+                dirty = false;
+            }
+
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(Ok(event)) => dirty = self.handle_event(event)?,
+                    Some(Err(e)) => return Err(e.into()),
+                    None => self.running = false,
+                },
+                Some(job) = self.jobs_rx.recv() => {
+                    self.handle_job(job);
+                    // Take whatever else has arrived too, so a burst of results redraws once
+                    self.drain_jobs();
+                    dirty = true;
+                }
+                Some(finished) = self.tasks.join_next(), if !self.tasks.is_empty() => {
+                    if let Err(e) = finished {
+                        error!("Background task failed: {}", e);
                     }
                 }
+                _ = spinner.tick(), if self.is_busy() => {
+                    self.spinner = self.spinner.wrapping_add(1);
+                    dirty = true;
+                }
+                signal = signals.recv() => {
+                    // No questions asked: whatever sent it wants the app gone
+                    info!("Received {}, quitting", signal);
+                    self.running = false;
+                }
             }
         }
-
-        info!("App loop finished");
         Ok(())
     }
 
+    /// Stop background work, which kills its child processes and removes the files they
+    /// were half-way through, then make sure everything worth keeping is on disk
+    async fn shutdown(&mut self) {
+        if !self.tasks.is_empty() {
+            info!("Stopping {} background tasks", self.tasks.len());
+            self.tasks.shutdown().await;
+        }
+        if !self.queue.is_empty() {
+            info!("Dropping {} queued downloads", self.queue.len());
+        }
+        self.save_history();
+
+        let (name, entries) = self.session_playlist();
+        if self.config.playlist.on_exit && !entries.is_empty() {
+            match self.write_playlist(name, entries).await {
+                Ok(path) => info!("Session playlist saved to {}", path.display()),
+                Err(e) => warn!("Could not write the session playlist: {}", e),
+            }
+        }
+    }
+
+    /// Downloads running or queued, and downloads still being processed
+    pub fn unfinished_work(&self) -> (usize, usize) {
+        let downloading = usize::from(matches!(self.download_status, DownloadStatus::Downloading));
+        let processing = self
+            .history
+            .entries
+            .iter()
+            .filter(|entry| entry.analyzing)
+            .count();
+        (downloading + self.queue.len(), processing)
+    }
+
+    /// Quit, first asking what to do about downloads and processing that haven't finished.
+    /// Asking a second time quits straight away.
+    fn request_quit(&mut self) {
+        if self.quitting.is_some() || self.unfinished_work() == (0, 0) {
+            self.running = false;
+        } else {
+            self.quitting = Some(Quitting::Confirming);
+        }
+    }
+
+    /// Keys for the quit confirmation
+    fn handle_quit_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('y' | 'Y') | KeyCode::Enter => self.running = false,
+            KeyCode::Char('w' | 'W') => {
+                self.quitting = Some(Quitting::WhenIdle);
+                self.status_message =
+                    "⏳ Quitting once the running work finishes - quit again to stop it now"
+                        .to_string();
+            }
+            KeyCode::Char('n' | 'N') | KeyCode::Esc => self.quitting = None,
+            _ => {}
+        }
+    }
+
+    /// Append the status message to the log when it has changed since the last one
+    fn record_status(&mut self) {
+        if self.status_message.is_empty() || self.log.last() == Some(&self.status_message) {
+            return;
+        }
+        self.log.push(self.status_message.clone());
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    /// Downloads in the order the history pane lists them: newest first
+    pub fn history_rows(&self) -> Vec<&HistoryEntry> {
+        self.history.entries.iter().rev().collect()
+    }
+
+    /// The download highlighted in the history pane
+    pub fn selected_entry(&self) -> Option<&HistoryEntry> {
+        self.history.entries.iter().rev().nth(self.panes.history)
+    }
+
+    /// Apply a terminal event; returns whether the screen needs redrawing
+    fn handle_event(&mut self, event: Event) -> Result<bool> {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => self.handle_key_event(key)?,
+            // Bracketed paste delivers multi-line text (e.g. tracklists) in one piece
+            Event::Paste(text) => {
+                self.focus = Focus::Input;
+                self.handle_paste(&text);
+            }
+            Event::Mouse(mouse) if mouse.kind != MouseEventKind::Moved => {
+                self.handle_mouse(mouse)?
+            }
+            Event::Resize(..) => {}
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Whether anything is in progress that the spinner should show
+    pub fn is_busy(&self) -> bool {
+        matches!(self.download_status, DownloadStatus::Downloading)
+            || self.library_scanning
+            || self.search.as_ref().is_some_and(|search| search.loading)
+            || self
+                .preview
+                .as_ref()
+                .is_some_and(|preview| preview.info.is_none() && preview.error.is_none())
+            || self.history.entries.iter().any(|entry| entry.analyzing)
+    }
+
+    /// Apply every job message waiting in the channel
+    fn drain_jobs(&mut self) {
+        while let Ok(event) = self.jobs_rx.try_recv() {
+            self.handle_job(event);
+        }
+    }
+
+    /// Apply a message from a background task
+    fn handle_job(&mut self, event: JobEvent) {
+        match event {
+            JobEvent::Processed { id, result } => self.finish_processing(id, result),
+            JobEvent::Split {
+                parent,
+                url,
+                bitrate,
+                result,
+            } => self.finish_split(parent, url, bitrate, result),
+            JobEvent::SearchResults { query, result } => self.finish_search(query, result),
+            JobEvent::Preview { url, result } => self.finish_preview(url, result),
+            JobEvent::LibraryScanned { result } => self.finish_library_scan(result),
+            JobEvent::Duplicate { id, duplicate } => self.finish_duplicate_check(id, duplicate),
+            JobEvent::DownloadProgress { message } => self.status_message = message,
+            JobEvent::Downloaded { download, result } => self.finish_download(*download, result),
+            JobEvent::Playlist { result } => match result {
+                Ok(path) => {
+                    self.status_message = format!("📝 Playlist saved to {}", path.display());
+                }
+                Err(e) => {
+                    warn!("Could not write the session playlist: {}", e);
+                    self.status_message = format!("❌ Playlist not saved: {e}");
+                }
+            },
+        }
+    }
+
+    /// Record processing results against the matching history entry and persist them
+    fn finish_processing(&mut self, id: u64, result: Result<Box<ProcessReport>, String>) {
+        let Some(entry) = self.history.get_mut(id) else {
+            return;
+        };
+        entry.analyzing = false;
+
+        match result {
+            Ok(report) => {
+                entry.bpm = report.analysis.bpm;
+                entry.key = report.analysis.key;
+                entry.first_downbeat = report.analysis.first_downbeat;
+                entry.loudness = report.loudness;
+                entry.trim = report.trim;
+                entry.quality = report.quality;
+                entry.fingerprint = report.fingerprint;
+                entry.tags = report.tags;
+                if let Some(renamed) = report.renamed {
+                    entry.filename = renamed
+                        .file_name()
+                        .map(|f| f.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    entry.path = renamed;
+                }
+                info!(
+                    "Processed {}: bpm={:?} key={:?} downbeat={:?} loudness={:?} trim={:?} quality={:?}",
+                    entry.filename,
+                    entry.bpm,
+                    entry.key,
+                    entry.first_downbeat,
+                    entry.loudness,
+                    entry.trim,
+                    entry.quality
+                );
+                if entry.quality.as_ref().is_some_and(|q| q.low_quality) {
+                    self.status_message = format!(
+                        "⚠️ {} looks like a low-quality source - check before playing out",
+                        entry.filename
+                    );
+                }
+            }
+            Err(e) => {
+                warn!("Processing failed for {}: {}", entry.filename, e);
+                self.status_message = format!("⚠️ Analysis failed for {}: {e}", entry.filename);
+            }
+        }
+
+        self.save_history();
+        self.spawn_duplicate_check(id);
+    }
+
+    /// Compare a fingerprinted download against everything else on a blocking thread
+    fn spawn_duplicate_check(&mut self, id: u64) {
+        let candidates = duplicates::tracks(&self.history);
+        let Some(track) = candidates.iter().find(|t| t.download == Some(id)).cloned() else {
+            return;
+        };
+        let threshold = self.config.duplicates.threshold;
+        let tx = self.jobs_tx.clone();
+        self.tasks.spawn_blocking(move || {
+            let duplicate =
+                duplicates::find(&track, &candidates, threshold).map(|t| t.name.clone());
+            let _ = tx.send(JobEvent::Duplicate { id, duplicate });
+        });
+    }
+
+    /// Flag a download that's already in the history or library under another name
+    fn finish_duplicate_check(&mut self, id: u64, duplicate: Option<String>) {
+        let Some(entry) = self.history.get_mut(id) else {
+            return;
+        };
+        if let Some(existing) = &duplicate {
+            info!("{} sounds the same as {}", entry.filename, existing);
+            self.status_message = format!(
+                "🔁 {} - you already have this as {existing}",
+                entry.filename
+            );
+        }
+        entry.duplicate_of = duplicate;
+    }
+
+    /// Persist the history, reporting (but not failing on) write errors
+    fn save_history(&mut self) {
+        if let Err(e) = self.history.save() {
+            error!("Failed to save history: {}", e);
+            self.status_message = format!("⚠️ Could not save history: {e}");
+        }
+    }
+
+    /// Record the tracks split out of a mix as its children and analyse each of them
+    fn finish_split(
+        &mut self,
+        parent: u64,
+        url: String,
+        bitrate: u32,
+        result: Result<Vec<SplitTrack>, String>,
+    ) {
+        let tracks = match result {
+            Ok(tracks) => tracks,
+            Err(e) => {
+                warn!("Splitting entry {} failed: {}", parent, e);
+                self.status_message = format!("⚠️ Splitting failed: {e}");
+                return;
+            }
+        };
+
+        // Tracks come from the same stream as their mix
+        let source = self
+            .history
+            .entries
+            .iter()
+            .find(|e| e.id == parent)
+            .and_then(|e| e.quality.as_ref())
+            .and_then(|q| q.source.clone());
+        let children: Vec<(u64, PathBuf)> = tracks
+            .into_iter()
+            .map(|track| {
+                let id =
+                    self.history
+                        .add_child(parent, track.filename, track.path.clone(), url.clone());
+                if let Some(entry) = self.history.get_mut(id) {
+                    entry.analyzing = true;
+                }
+                (id, track.path)
+            })
+            .collect();
+
+        self.status_message = format!("✂️ Split into {} tracks", children.len());
+        self.save_history();
+
+        let options = DownloadOptions {
+            bitrate,
+            trim_silence: false,
+            clip: None,
+            split: false,
+            source,
+        };
+        self.spawn_processing(children, url, options, None);
+    }
+
+    /// Post-process, analyse and name finished downloads on a background task so the next
+    /// download isn't held up. Files are handled one at a time; a split plan runs after the first file.
+    fn spawn_processing(
+        &mut self,
+        files: Vec<(u64, PathBuf)>,
+        url: String,
+        options: DownloadOptions,
+        split: Option<SplitPlan>,
+    ) {
+        let tx = self.jobs_tx.clone();
+        let config = self.config.clone();
+        self.tasks.spawn(async move {
+            for (id, path) in files {
+                let work =
+                    postprocess::run(path.clone(), url.clone(), config.clone(), options.clone());
+                let result = shutdown::catch_panic(work)
+                    .await
+                    .map(Box::new)
+                    .map_err(|e| e.to_string());
+                // Processing may have renamed the file, and trimmed its start
+                let (path, trimmed) = match &result {
+                    Ok(report) => (
+                        report.renamed.clone().unwrap_or(path),
+                        report.trim.map_or(0.0, |trim| f64::from(trim.leading)),
+                    ),
+                    Err(_) => (path, 0.0),
+                };
+                // The receiver only goes away when the app is shutting down
+                let _ = tx.send(JobEvent::Processed { id, result });
+
+                // Split only once the mix itself is fully processed, so we cut the final audio,
+                // with the cut points moved back by whatever trimming took off the start
+                if let Some(plan) = &split {
+                    let plan = plan.shifted(trimmed);
+                    let work = postprocess::split::split(&path, &plan, config.naming.max_length);
+                    let result = shutdown::catch_panic(work).await.map_err(|e| e.to_string());
+                    let _ = tx.send(JobEvent::Split {
+                        parent: id,
+                        url: url.clone(),
+                        bitrate: options.bitrate,
+                        result,
+                    });
+                }
+            }
+        });
+    }
+
     /// Draw the application UI
     fn draw(&mut self, frame: &mut Frame) {
-        crate::ui::render(frame, self);
+        self.screen = frame.area();
+        ui::render(frame, self);
     }
 
     /// Sanitize and validate input text
@@ -100,9 +969,12 @@ impl App {
             input
         };
 
-        // Try to extract YouTube URL from the text
+        // Try to extract YouTube URL from the text, keeping any clip range typed after it
         if let Some(url) = self.extract_youtube_url(truncated) {
             info!("Extracted YouTube URL: {}", url);
+            if let Some(spec) = clip::find_spec(truncated) {
+                return format!("{url} {spec}");
+            }
             return url;
         }
 
@@ -135,14 +1007,17 @@ impl App {
         ];
 
         for pattern in &patterns {
-            if let Ok(regex) = Regex::new(pattern) {
-                if let Some(captures) = regex.captures(text) {
-                    if let Some(video_id) = captures.get(1) {
-                        let url = format!("https://www.youtube.com/watch?v={}", video_id.as_str());
-                        info!("Extracted YouTube URL from pattern '{}': {}", pattern, url);
-                        return Some(url);
-                    }
+            if let Ok(regex) = Regex::new(pattern)
+                && let Some(captures) = regex.captures(text)
+                && let Some(video_id) = captures.get(1)
+            {
+                let mut url = format!("https://www.youtube.com/watch?v={}", video_id.as_str());
+                // Keep the start offset - it can seed a clip range
+                if let Some(start) = captures.get(0).and_then(|m| clip::start_param(m.as_str())) {
+                    url.push_str(&format!("&t={}s", start as u64));
                 }
+                info!("Extracted YouTube URL from pattern '{}': {}", pattern, url);
+                return Some(url);
             }
         }
 
@@ -193,6 +1068,15 @@ impl App {
     /// Handle paste operation with sanitization
     fn handle_paste(&mut self, pasted_text: &str) {
         let original_len = pasted_text.len();
+
+        // Keep a pasted tracklist around for splitting the next download
+        let segments = tracklist::parse(pasted_text);
+        let tracklist_len = segments.len();
+        if tracklist_len > 0 {
+            info!("Captured tracklist with {} tracks", tracklist_len);
+            self.pending_tracklist = Some(segments);
+        }
+
         let sanitized = self.sanitize_input(pasted_text);
 
         if sanitized != pasted_text {
@@ -212,21 +1096,30 @@ impl App {
             );
         }
 
+        if tracklist_len > 0 {
+            self.status_message = match self.keymap.label(Action::ToggleSplit) {
+                Some(keys) => format!(
+                    "📋 Tracklist with {tracklist_len} tracks captured - {keys} to split the download"
+                ),
+                None => format!("📋 Tracklist with {tracklist_len} tracks captured"),
+            };
+        }
+
         // Replace the current input with sanitized content
         self.input = sanitized;
     }
 
     /// Handle keyboard events with improved error handling and input sanitization
-    async fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
-        // Global quit command
-        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
-            info!("User quit with Ctrl+C");
-            self.running = false;
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        // Global quit command; a printable quit key only counts where typing isn't expected
+        if self.keymap.action(&key) == Some(Action::Quit) && !Key::from(&key).is_printable() {
+            info!("User quit with {}", Key::from(&key));
+            self.request_quit();
             return Ok(());
         }
 
         // Use a separate method for handling that can't crash the UI
-        if let Err(e) = self.handle_key_event_safe(key).await {
+        if let Err(e) = self.handle_key_event_safe(key) {
             error!("Error handling key event: {}", e);
             self.status_message = format!("Error: {e}");
             // Don't crash the UI - just show the error message
@@ -236,245 +1129,1170 @@ impl App {
     }
 
     /// Safe key event handling that catches errors
-    async fn handle_key_event_safe(&mut self, key: KeyEvent) -> Result<()> {
-        match key.code {
-            KeyCode::Esc => {
-                self.running = false;
-            }
-            KeyCode::Enter => {
-                if !self.input.trim().is_empty() {
-                    self.start_download(128).await?;
-                }
-            }
-            KeyCode::Backspace => {
-                self.input.pop();
+    fn handle_key_event_safe(&mut self, key: KeyEvent) -> Result<()> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        // The quit question and help sit on top of everything else until they're answered
+        if self.quitting == Some(Quitting::Confirming) {
+            self.handle_quit_key(key);
+            return Ok(());
+        }
+        if self.help.is_some() {
+            self.handle_help_key(key);
+            return Ok(());
+        }
+
+        // The preview pane only answers confirm/cancel until it's dismissed
+        if self.preview.is_some() {
+            return self.handle_preview_key(key, ctrl);
+        }
+
+        // So do the history search, library table and crate panel while they're open
+        if self.history_filter.is_some() {
+            self.handle_history_filter_key(key, ctrl);
+            return Ok(());
+        }
+        if self.library.is_some() {
+            self.handle_library_key(key, ctrl);
+            return Ok(());
+        }
+        if self.crates.is_some() {
+            self.handle_crate_key(key, ctrl);
+            return Ok(());
+        }
+
+        // Printable keys like `/` and `?` are typed into the input once there's text in it
+        let typing =
+            self.focus == Focus::Input && !self.input.is_empty() && Key::from(&key).is_printable();
+        let action = self.keymap.action(&key).filter(|_| !typing);
+
+        match action {
+            Some(Action::NextPane) => {
+                self.focus = self.focus.next();
+                return Ok(());
             }
-            KeyCode::Delete => {
-                self.input.clear();
+            Some(Action::PreviousPane) => {
+                self.focus = self.focus.previous();
+                return Ok(());
             }
-            KeyCode::Tab => {
-                // Tab does nothing now since we only have input focus
-                // Keeping this for compatibility but it doesn't change focus
+            Some(Action::Help) => {
+                self.help = Some(0);
+                return Ok(());
             }
-            KeyCode::F(5) => {
-                // F5 to clear input and extract URL from current content
-                if !self.input.is_empty() {
-                    let original = self.input.clone();
-                    self.handle_paste(&original);
-                }
+            // Ctrl shortcuts work from every pane; everything else goes to the focused one
+            _ if self.focus != Focus::Input && !ctrl => {
+                self.handle_pane_key(key);
+                return Ok(());
             }
-            KeyCode::Char('1') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                if !self.input.trim().is_empty() {
-                    self.start_download(128).await?;
+            _ => {}
+        }
+
+        if let Some(action) = action {
+            return self.perform(action);
+        }
+
+        // Search results take Up/Down/Space while the input box is empty
+        let browsing_results = self.search.is_some() && self.input.is_empty();
+        match key.code {
+            KeyCode::Up if browsing_results => {
+                if let Some(search) = &mut self.search {
+                    search.select_previous();
                 }
             }
-            KeyCode::Char('2') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                if !self.input.trim().is_empty() {
-                    self.start_download(256).await?;
+            KeyCode::Down if browsing_results => {
+                if let Some(search) = &mut self.search {
+                    search.select_next();
                 }
             }
-            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                // Handle Ctrl+V paste - for now just inform user about F5
-                self.status_message =
-                    "💡 Paste detected! Press F5 to clean and extract URL from pasted content"
-                        .to_string();
-                info!("Ctrl+V detected - user should use F5 for URL extraction");
-            }
-            KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                // Handle Ctrl+A - select all (just clear input for simplicity)
-                info!("Ctrl+A detected - clearing input");
-            }
-            KeyCode::Char(c) => {
-                if key.modifiers.contains(KeyModifiers::CONTROL) {
-                    // Ignore other Ctrl+char combinations
-                } else {
-                    // Handle regular character input
-                    self.handle_char_input(c);
+            KeyCode::Char(' ') if browsing_results && !ctrl => {
+                if let Some(search) = &mut self.search {
+                    search.toggle_mark();
                 }
             }
+            // Other Ctrl combinations are ignored
+            KeyCode::Char(c) if !ctrl => self.handle_char_input(c),
             _ => {}
         }
 
         Ok(())
     }
 
-    /// Start downloading the YouTube video as MP3 with robust error handling
-    async fn start_download(&mut self, bitrate: u32) -> Result<()> {
-        let url = self.input.trim();
-
-        if url.is_empty() {
-            self.status_message = "Please enter a YouTube URL".to_string();
-            warn!("Empty URL provided");
-            return Ok(());
-        }
-
-        if !url.contains("youtube.com") && !url.contains("youtu.be") {
-            self.status_message = "Please enter a valid YouTube URL".to_string();
-            warn!("Invalid URL provided: {}", url);
-            return Ok(());
-        }
-
-        // Wrap download in error handling to prevent crashes
-        if let Err(e) = self.perform_download(url.to_string(), bitrate).await {
-            error!("Download failed: {}", e);
-            self.download_status = DownloadStatus::Error(e.to_string());
-            self.status_message = format!("❌ Download failed: {e}");
-        }
+    /// Carry out a bound action on the main screen
+    fn perform(&mut self, action: Action) -> Result<()> {
+        // Search results take Enter and the quick downloads while the input box is empty
+        let browsing_results = self.search.is_some() && self.input.is_empty();
+        let has_input = !self.input.trim().is_empty();
 
+        match action {
+            Action::Quit => self.request_quit(),
+            Action::Back if self.search.is_some() => {
+                // Esc closes search results first, then quits
+                self.search = None;
+            }
+            Action::Back => self.request_quit(),
+            Action::NextPane => self.focus = self.focus.next(),
+            Action::PreviousPane => self.focus = self.focus.previous(),
+            Action::Help => self.help = Some(0),
+            Action::Submit if browsing_results => self.queue_search_selection(self.bitrate),
+            Action::Submit if has_input => self.submit_input(self.bitrate)?,
+            Action::Download128 if browsing_results => self.queue_search_selection(128),
+            Action::Download256 if browsing_results => self.queue_search_selection(256),
+            Action::Download128 if has_input => self.submit_input(128)?,
+            Action::Download256 if has_input => self.submit_input(256)?,
+            Action::Submit | Action::Download128 | Action::Download256 => {}
+            Action::DeleteChar => {
+                self.input.pop();
+            }
+            Action::ClearInput => self.input.clear(),
+            Action::ExtractUrl if !self.input.is_empty() => {
+                // Clear the input and extract the URL from its current content
+                let original = self.input.clone();
+                self.handle_paste(&original);
+            }
+            Action::ExtractUrl => {}
+            Action::PasteHint => {
+                // Terminals that don't bracket pastes send Ctrl+V as a key; point at F5 instead
+                let extract = self
+                    .keymap
+                    .label(Action::ExtractUrl)
+                    .unwrap_or_else(|| "extract_url".to_string());
+                self.status_message = format!(
+                    "💡 Paste detected! Press {extract} to clean and extract URL from pasted content"
+                );
+                info!(
+                    "Paste key detected - user should use {} for URL extraction",
+                    extract
+                );
+            }
+            Action::ToggleTrim => {
+                // Silence trimming for the next download
+                self.trim_silence = !self.trim_silence;
+                let state = if self.trim_silence { "on" } else { "off" };
+                self.status_message = format!("✂️ Silence trimming {state}");
+                info!("Silence trimming toggled {}", state);
+            }
+            Action::ToggleSplit => {
+                // Splitting the next download into tracks
+                self.split_mix = !self.split_mix;
+                let state = if self.split_mix { "on" } else { "off" };
+                self.status_message = format!("✂️ Split into tracks {state}");
+                info!("Split mode toggled {}", state);
+            }
+            Action::ToggleBitrate => self.toggle_bitrate(),
+            Action::Retry => self.retry_failed(),
+            Action::SavePlaylist => {
+                // Write this session's playlist now
+                let (name, entries) = self.session_playlist();
+                self.spawn_playlist(name, entries);
+            }
+            Action::SearchHistory => {
+                self.history_filter = Some(HistoryFilter::default());
+            }
+            Action::OpenLibrary => {
+                // Scanning it the first time
+                self.library = Some(LibraryView::default());
+                if !self.library_scanned {
+                    self.start_library_scan();
+                }
+            }
+            Action::OpenCrates => {
+                self.crates = Some(CratePanel::new());
+                self.status_message =
+                    "📦 Crates - Tab to switch lists, n for a new crate, Esc to close".to_string();
+            }
+        }
         Ok(())
     }
 
-    /// Perform the actual download with proper error isolation
-    async fn perform_download(&mut self, url: String, bitrate: u32) -> Result<()> {
-        // Starting download silently
-        self.download_status = DownloadStatus::Downloading;
-        self.status_message = format!("🎵 Downloading MP3 at {bitrate}kbps... Please wait");
+    /// Keys for the help overlay: arrows scroll, anything else closes it
+    fn handle_help_key(&mut self, key: KeyEvent) {
+        let Some(scroll) = &mut self.help else {
+            return;
+        };
+        match key.code {
+            KeyCode::Up => *scroll = scroll.saturating_sub(1),
+            KeyCode::Down => *scroll = scroll.saturating_add(1),
+            KeyCode::PageUp => *scroll = scroll.saturating_sub(PAGE as u16),
+            KeyCode::PageDown => *scroll = scroll.saturating_add(PAGE as u16),
+            KeyCode::Home => *scroll = 0,
+            _ => self.help = None,
+        }
+    }
 
-        // Clear the input field when download starts
-        self.input.clear();
+    /// Keys for the queue, history, details and log panes
+    fn handle_pane_key(&mut self, key: KeyEvent) {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match (self.focus, key.code) {
+            // Esc hands the keys back to the input box
+            (_, KeyCode::Esc) => self.focus = Focus::Input,
+
+            (Focus::Queue, KeyCode::Up) if shift => self.move_queued(true),
+            (Focus::Queue, KeyCode::Down) if shift => self.move_queued(false),
+            (Focus::Queue, KeyCode::Char('K')) => self.move_queued(true),
+            (Focus::Queue, KeyCode::Char('J')) => self.move_queued(false),
+            (Focus::Queue, KeyCode::Up) => {
+                self.panes.queue = self.panes.queue.saturating_sub(1);
+            }
+            (Focus::Queue, KeyCode::Down) => {
+                self.panes.queue = (self.panes.queue + 1).min(self.queue.len().saturating_sub(1));
+            }
+            (Focus::Queue, KeyCode::Char('d') | KeyCode::Delete) => self.cancel_queued(),
+
+            (Focus::History, KeyCode::Up) => self.select_history(-1),
+            (Focus::History, KeyCode::Down) => self.select_history(1),
+            (Focus::History, KeyCode::PageUp) => self.select_history(-(PAGE as isize)),
+            (Focus::History, KeyCode::PageDown) => self.select_history(PAGE as isize),
+            (Focus::History, KeyCode::Home) => self.select_history(isize::MIN),
+            (Focus::History, KeyCode::End) => self.select_history(isize::MAX),
+            // Open the highlighted download in the crate panel, ready to add to a crate
+            (Focus::History, KeyCode::Enter) if !self.history.entries.is_empty() => {
+                let mut panel = CratePanel::new();
+                panel.history_selected = self.panes.history;
+                self.crates = Some(panel);
+            }
+            (Focus::History, KeyCode::Char('/')) => {
+                self.history_filter = Some(HistoryFilter::default());
+            }
+
+            (Focus::Details, KeyCode::Up) => {
+                self.panes.details_scroll = self.panes.details_scroll.saturating_sub(1);
+            }
+            (Focus::Details, KeyCode::Down) => {
+                self.panes.details_scroll = self.panes.details_scroll.saturating_add(1);
+            }
+            (Focus::Details, KeyCode::PageUp) => {
+                self.panes.details_scroll = self.panes.details_scroll.saturating_sub(PAGE as u16);
+            }
+            (Focus::Details, KeyCode::PageDown) => {
+                self.panes.details_scroll = self.panes.details_scroll.saturating_add(PAGE as u16);
+            }
+            (Focus::Details, KeyCode::Home) => self.panes.details_scroll = 0,
+
+            // The log scrolls back from the newest message
+            (Focus::Log, KeyCode::Up) => self.scroll_log(1),
+            (Focus::Log, KeyCode::Down) => self.scroll_log(-1),
+            (Focus::Log, KeyCode::PageUp) => self.scroll_log(PAGE as isize),
+            (Focus::Log, KeyCode::PageDown) => self.scroll_log(-(PAGE as isize)),
+            (Focus::Log, KeyCode::Home) => self.scroll_log(isize::MAX),
+            (Focus::Log, KeyCode::End) => self.panes.log_scroll = 0,
+            _ => {}
+        }
+    }
+
+    /// Click to focus panes, pick rows and press buttons; the wheel scrolls whatever is under it
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
+        let layout = ScreenLayout::new(self.screen, self);
+        let position = Position::new(mouse.column, mouse.row);
+        let overlay = self.quitting == Some(Quitting::Confirming)
+            || self.help.is_some()
+            || self.preview.is_some()
+            || self.history_filter.is_some()
+            || self.library.is_some()
+            || self.crates.is_some()
+            || self.search.is_some();
+
+        match mouse.kind {
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let up = mouse.kind == MouseEventKind::ScrollUp;
+                if overlay && layout.body.contains(position) {
+                    self.scroll_overlay(up)?;
+                } else if let Some(pane) = layout.pane_at(position) {
+                    self.scroll_pane(pane, up);
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some((button, _, _)) = ui::buttons(layout.buttons, self)
+                    .into_iter()
+                    .find(|(_, _, area)| area.contains(position))
+                {
+                    return self.press(button);
+                }
+                if overlay && layout.body.contains(position) {
+                    return Ok(());
+                }
+                let Some(pane) = layout.pane_at(position) else {
+                    return Ok(());
+                };
+                self.focus = pane;
+                match pane {
+                    Focus::Queue => {
+                        // The running download takes the first row
+                        let offset = usize::from(matches!(
+                            self.download_status,
+                            DownloadStatus::Downloading
+                        ));
+                        let selected = self.panes.queue + offset;
+                        if let Some(row) = ui::list_row(layout.queue, selected, mouse.row)
+                            && row >= offset
+                            && row - offset < self.queue.len()
+                        {
+                            self.panes.queue = row - offset;
+                        }
+                    }
+                    Focus::History => {
+                        if let Some(row) =
+                            ui::list_row(layout.history, self.panes.history, mouse.row)
+                            && row < self.history.entries.len()
+                        {
+                            let delta = row as isize - self.panes.history as isize;
+                            self.select_history(delta);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The wheel over an open overlay moves its highlight, as the arrow keys do
+    fn scroll_overlay(&mut self, up: bool) -> Result<()> {
+        if self.help.is_none()
+            && self.preview.is_none()
+            && self.history_filter.is_none()
+            && self.library.is_none()
+            && self.crates.is_none()
+            && let Some(search) = &mut self.search
+        {
+            // Search results live under the input, which may be busy with other text
+            if up {
+                search.select_previous();
+            } else {
+                search.select_next();
+            }
+            return Ok(());
+        }
+        let code = if up { KeyCode::Up } else { KeyCode::Down };
+        self.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    /// Scroll a pane without moving the focus
+    fn scroll_pane(&mut self, pane: Focus, up: bool) {
+        let delta = if up { -1 } else { 1 };
+        match pane {
+            Focus::Input => {}
+            Focus::Queue => {
+                let last = self.queue.len().saturating_sub(1);
+                self.panes.queue = self.panes.queue.saturating_add_signed(delta).min(last);
+            }
+            Focus::History => self.select_history(delta),
+            Focus::Details => {
+                self.panes.details_scroll = self
+                    .panes
+                    .details_scroll
+                    .saturating_add_signed(delta as i16);
+            }
+            Focus::Log => self.scroll_log(-delta),
+        }
+    }
+
+    /// Do what a clicked button says
+    fn press(&mut self, button: Button) -> Result<()> {
+        match button {
+            Button::Download if !self.input.trim().is_empty() => {
+                self.focus = Focus::Input;
+                self.submit_input(self.bitrate)?;
+            }
+            Button::Download => {}
+            Button::Format => self.toggle_bitrate(),
+            Button::Retry => self.retry_failed(),
+            Button::Cancel => self.cancel_queued(),
+        }
+        Ok(())
+    }
+
+    /// Switch Enter between 128 and 256 kbps downloads
+    fn toggle_bitrate(&mut self) {
+        self.bitrate = if self.bitrate == 128 { 256 } else { 128 };
+        self.status_message = format!("🎚️ Downloads now at {}kbps", self.bitrate);
+    }
+
+    /// Queue the last failed download again, ahead of everything else
+    fn retry_failed(&mut self) {
+        let Some(download) = self.last_failed.take() else {
+            self.status_message = "Nothing to retry".to_string();
+            return;
+        };
+        self.status_message = format!("🔁 Retrying {}", download.title);
+        self.queue.push_front(download);
+    }
+
+    /// Drop the highlighted download from the queue
+    fn cancel_queued(&mut self) {
+        if let Some(download) = self.queue.remove(self.panes.queue) {
+            self.status_message = format!("🗑️ Removed {} from the queue", download.title);
+        }
+        self.panes.queue = self.panes.queue.min(self.queue.len().saturating_sub(1));
+    }
 
-        // Download directly to Downloads folder (no subfolder)
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let output_dir = PathBuf::from(home).join("Downloads");
-
-        // Download using yt-dlp - clean and simple
-        let file_path = self
-            .download_mp3(url, output_dir, bitrate)
-            .await
-            .map_err(|e| color_eyre::eyre::eyre!("Download failed: {}", e))?;
-        // Download completed successfully
-        self.download_status = DownloadStatus::Success(file_path.clone());
-        self.status_message = format!("✅ Successfully downloaded: {file_path}");
-
-        // Add to download history for display - extract just the filename
-        if let Some(filename) = file_path.strip_prefix("✅ Downloaded: ") {
-            self.download_history.push(filename.to_string());
+    /// Move the history highlight by `delta` rows, clamped to the list
+    fn select_history(&mut self, delta: isize) {
+        let last = self.history.entries.len().saturating_sub(1);
+        let selected = self.panes.history.saturating_add_signed(delta).min(last);
+        if selected != self.panes.history {
+            self.panes.history = selected;
+            self.panes.details_scroll = 0;
+        }
+    }
+
+    /// Scroll the log back (positive) or forward (negative)
+    fn scroll_log(&mut self, delta: isize) {
+        let oldest = self.log.len().saturating_sub(1);
+        let scroll = (self.panes.log_scroll as usize)
+            .saturating_add_signed(delta)
+            .min(oldest);
+        self.panes.log_scroll = scroll as u16;
+    }
+
+    /// Move the highlighted queued download one place up or down
+    fn move_queued(&mut self, up: bool) {
+        let index = self.panes.queue;
+        let target = if up {
+            index.saturating_sub(1)
         } else {
-            // Fallback in case format changes
-            self.download_history.push(file_path.clone());
+            (index + 1).min(self.queue.len().saturating_sub(1))
+        };
+        if index < self.queue.len() {
+            self.queue.swap(index, target);
+            self.panes.queue = target;
         }
+    }
 
+    /// Keys while the history search is open; results follow every keystroke
+    fn handle_history_filter_key(&mut self, key: KeyEvent, ctrl: bool) {
+        let Some(filter) = self.history_filter.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.history_filter = None,
+            KeyCode::Char(c) if !ctrl => {
+                filter.query.push(c);
+                filter.selected = 0;
+            }
+            KeyCode::Backspace => {
+                filter.query.pop();
+                filter.selected = 0;
+            }
+            KeyCode::Up => filter.selected = filter.selected.saturating_sub(1),
+            KeyCode::Down => {
+                let len = filter.matches(&self.history).len();
+                filter.selected = (filter.selected + 1).min(len.saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                // Open the highlighted download in the crate panel, ready to add to a crate
+                let Some(id) = filter
+                    .matches(&self.history)
+                    .get(filter.selected)
+                    .map(|e| e.id)
+                else {
+                    return;
+                };
+                let mut panel = CratePanel::new();
+                panel.history_selected = self
+                    .history
+                    .entries
+                    .iter()
+                    .rev()
+                    .position(|e| e.id == id)
+                    .unwrap_or_default();
+                self.crates = Some(panel);
+                self.history_filter = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys while the library table is open
+    fn handle_library_key(&mut self, key: KeyEvent, ctrl: bool) {
+        let len = self
+            .library
+            .as_ref()
+            .map_or(0, |view| view.visible(&self.history.library).len());
+        let Some(view) = self.library.as_mut() else {
+            return;
+        };
+
+        // Typing a filter after `/`
+        if view.filtering {
+            match key.code {
+                KeyCode::Char(c) if !ctrl => view.filter.push(c),
+                KeyCode::Backspace => {
+                    view.filter.pop();
+                }
+                KeyCode::Enter => view.filtering = false,
+                KeyCode::Esc => {
+                    view.filter.clear();
+                    view.filtering = false;
+                }
+                _ => {}
+            }
+            view.selected = 0;
+            return;
+        }
+
+        match key.code {
+            KeyCode::Esc if !view.filter.is_empty() => {
+                view.filter.clear();
+                view.selected = 0;
+            }
+            KeyCode::Esc => self.library = None,
+            KeyCode::Char('l') if ctrl => self.library = None,
+            KeyCode::Char('r') if ctrl => self.start_library_scan(),
+            KeyCode::Up => view.select(-1, len),
+            KeyCode::Down => view.select(1, len),
+            KeyCode::PageUp => view.select(-20, len),
+            KeyCode::PageDown => view.select(20, len),
+            KeyCode::Home => view.selected = 0,
+            KeyCode::End => view.selected = len.saturating_sub(1),
+            KeyCode::Char('/') => view.filtering = true,
+            KeyCode::Char('s') if !ctrl => view.sort = view.sort.next(),
+            KeyCode::Char('r') if !ctrl => view.descending = !view.descending,
+            // 1-7 sort by that column; again to reverse
+            KeyCode::Char(c @ '1'..='7') if !ctrl => {
+                let column = SortColumn::ALL[c as usize - '1' as usize];
+                if view.sort == column {
+                    view.descending = !view.descending;
+                } else {
+                    view.sort = column;
+                    view.descending = false;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rescan the library folders in the background
+    fn start_library_scan(&mut self) {
+        if self.config.library.folders.is_empty() {
+            self.status_message =
+                "📚 No library folders - add them under [library] in the config".to_string();
+            return;
+        }
+        if self.library_scanning {
+            return;
+        }
+        self.library_scanning = true;
+        self.library_scanned = true;
+        self.status_message = "📚 Scanning library...".to_string();
+
+        let folders = self
+            .config
+            .library
+            .folders
+            .iter()
+            .map(|folder| routing::expand_home(folder))
+            .collect();
+        let cached = self.history.library.clone();
+        let fingerprints = self.config.duplicates.enabled;
+        let tx = self.jobs_tx.clone();
+        self.tasks.spawn(async move {
+            let result = shutdown::catch_panic(library::scan(folders, cached, fingerprints))
+                .await
+                .map_err(|e| e.to_string());
+            let _ = tx.send(JobEvent::LibraryScanned { result });
+        });
+    }
+
+    /// Keep the scan results and persist them for the next incremental scan
+    fn finish_library_scan(&mut self, result: Result<ScanReport, String>) {
+        self.library_scanning = false;
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                warn!("Library scan failed: {}", e);
+                self.status_message = format!("❌ Library scan failed: {e}");
+                return;
+            }
+        };
+
+        self.status_message = format!(
+            "📚 Library: {} files ({} new or changed, {} gone)",
+            report.files.len(),
+            report.probed,
+            report.removed
+        );
+        self.history.library = report.files;
+        if let Some(view) = &mut self.library {
+            let len = view.visible(&self.history.library).len();
+            view.select(0, len);
+        }
+        self.save_history();
+    }
+
+    /// Keys while the crate panel is open
+    fn handle_crate_key(&mut self, key: KeyEvent, ctrl: bool) {
+        let Some(panel) = self.crates.as_mut() else {
+            return;
+        };
+
+        // Typing the name of a new crate
+        if let Some(name) = &mut panel.new_name {
+            match key.code {
+                KeyCode::Char(c) if !ctrl => name.push(c),
+                KeyCode::Backspace => {
+                    name.pop();
+                }
+                KeyCode::Esc => panel.new_name = None,
+                KeyCode::Enter => {
+                    let name = name.trim().to_string();
+                    panel.new_name = None;
+                    if name.is_empty() {
+                        return;
+                    }
+                    panel.crate_selected = self.history.create_crate(&name);
+                    panel.track_selected = 0;
+                    panel.column = CrateColumn::Crates;
+                    info!("Created crate '{}'", name);
+                    self.status_message = format!("📦 Crate '{name}' ready");
+                    self.save_history();
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        let column = panel.column;
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Esc => self.crates = None,
+            KeyCode::Char('k') if ctrl => self.crates = None,
+            KeyCode::Tab | KeyCode::Right => panel.cycle(false),
+            KeyCode::BackTab | KeyCode::Left => panel.cycle(true),
+            // Shift+Up/Down (or K/J) reorders the crate
+            KeyCode::Up | KeyCode::Down if shift && column == CrateColumn::Tracks => {
+                self.move_crate_track(key.code == KeyCode::Up);
+            }
+            KeyCode::Char(c @ ('K' | 'J')) if column == CrateColumn::Tracks => {
+                self.move_crate_track(c == 'K');
+            }
+            KeyCode::Up => panel.select(&self.history, true),
+            KeyCode::Down => panel.select(&self.history, false),
+            KeyCode::Char('n') if !ctrl => {
+                panel.new_name = Some(String::new());
+                self.status_message = "📦 Name the new crate, then press Enter".to_string();
+            }
+            KeyCode::Enter | KeyCode::Char('a') if column == CrateColumn::History => {
+                self.add_to_crate();
+            }
+            KeyCode::Char('t') if column == CrateColumn::Crates => self.toggle_target_crate(),
+            KeyCode::Char('p') if column != CrateColumn::History => self.spawn_crate_playlist(),
+            KeyCode::Delete | KeyCode::Char('d') => match column {
+                CrateColumn::Crates => self.delete_crate(),
+                CrateColumn::Tracks => self.remove_crate_track(),
+                CrateColumn::History => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Add the download highlighted in the crate panel to the highlighted crate
+    fn add_to_crate(&mut self) {
+        let Some(panel) = &self.crates else {
+            return;
+        };
+        let Some(entry) = panel.history_entry(&self.history) else {
+            return;
+        };
+        let (id, filename) = (entry.id, entry.filename.clone());
+        let Some(krate) = self.history.crates.get_mut(panel.crate_selected) else {
+            self.status_message = "📦 Press n to create a crate first".to_string();
+            return;
+        };
+
+        self.status_message = if krate.add(id) {
+            format!("📦 Added {filename} to '{}'", krate.name)
+        } else {
+            format!("📦 {filename} is already in '{}'", krate.name)
+        };
+        self.save_history();
+    }
+
+    /// Move the highlighted track up or down its crate
+    fn move_crate_track(&mut self, up: bool) {
+        let Some(panel) = &mut self.crates else {
+            return;
+        };
+        let Some(krate) = self.history.crates.get_mut(panel.crate_selected) else {
+            return;
+        };
+        panel.track_selected = krate.move_track(panel.track_selected, up);
+        self.save_history();
+    }
+
+    /// Take the highlighted track out of its crate (the file stays put)
+    fn remove_crate_track(&mut self) {
+        let Some(panel) = &mut self.crates else {
+            return;
+        };
+        let Some(krate) = self.history.crates.get_mut(panel.crate_selected) else {
+            return;
+        };
+        let Some(next) = krate.remove(panel.track_selected) else {
+            return;
+        };
+        panel.track_selected = next;
+        self.status_message = format!("📦 Removed from '{}'", krate.name);
+        self.save_history();
+    }
+
+    /// Delete the highlighted crate; its tracks stay in the history
+    fn delete_crate(&mut self) {
+        let Some(panel) = &mut self.crates else {
+            return;
+        };
+        if panel.crate_selected >= self.history.crates.len() {
+            return;
+        }
+        let krate = self.history.crates.remove(panel.crate_selected);
+        panel.crate_selected = panel
+            .crate_selected
+            .min(self.history.crates.len().saturating_sub(1));
+        panel.track_selected = 0;
+        if self.target_crate.as_deref() == Some(krate.name.as_str()) {
+            self.target_crate = None;
+        }
+        info!("Deleted crate '{}'", krate.name);
+        self.status_message = format!("📦 Deleted crate '{}'", krate.name);
+        self.save_history();
+    }
+
+    /// Make the highlighted crate the one new downloads go into, or stop if it already is
+    fn toggle_target_crate(&mut self) {
+        let Some(name) = self
+            .crates
+            .as_ref()
+            .and_then(|panel| self.history.crates.get(panel.crate_selected))
+            .map(|c| c.name.clone())
+        else {
+            return;
+        };
+
+        if self.target_crate.as_deref() == Some(name.as_str()) {
+            self.target_crate = None;
+            self.status_message = format!("📦 New downloads no longer go into '{name}'");
+        } else {
+            self.status_message = format!("📦 New downloads go into '{name}'");
+            self.target_crate = Some(name);
+        }
+    }
+
+    /// Write the highlighted crate as a playlist next to the downloads
+    fn spawn_crate_playlist(&mut self) {
+        let Some(krate) = self
+            .crates
+            .as_ref()
+            .and_then(|panel| self.history.crates.get(panel.crate_selected))
+        else {
+            return;
+        };
+        let entries = krate
+            .tracks
+            .iter()
+            .filter_map(|id| self.history.get(*id))
+            .cloned()
+            .collect();
+        self.spawn_playlist(krate.name.clone(), entries);
+    }
+
+    /// Keys while a download preview is waiting for confirmation
+    fn handle_preview_key(&mut self, key: KeyEvent, ctrl: bool) -> Result<()> {
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('y') if !ctrl => self.confirm_preview()?,
+            KeyCode::Esc | KeyCode::Char('n') if !ctrl => {
+                if let Some(preview) = self.preview.take() {
+                    info!("Download of {} cancelled from preview", preview.url);
+                }
+                self.status_message = "Download cancelled".to_string();
+            }
+            // Switch bitrate from the preview; the upscaling warning follows
+            _ if self.keymap.action(&key) == Some(Action::Download128) => {
+                self.set_preview_bitrate(128)
+            }
+            _ if self.keymap.action(&key) == Some(Action::Download256) => {
+                self.set_preview_bitrate(256)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn set_preview_bitrate(&mut self, bitrate: u32) {
+        if let Some(preview) = &mut self.preview {
+            preview.bitrate = bitrate;
+            self.status_message = format!("Output bitrate set to {bitrate}kbps");
+        }
+    }
+
+    /// Start the download shown in the preview, reusing its metadata
+    fn confirm_preview(&mut self) -> Result<()> {
+        let Some(preview) = self.preview.take() else {
+            return Ok(());
+        };
+        let title = preview
+            .info
+            .as_ref()
+            .map_or_else(|| preview.url.clone(), |info| info.title.clone());
+        let download = QueuedDownload {
+            info: preview.info.map(Box::new),
+            ..self.queued(preview.url, title, preview.bitrate, preview.clip_request)
+        };
+        self.request_download(download);
         Ok(())
     }
 
-    /// Download MP3 using yt-dlp - clean and simple (2025 best practice)
-    async fn download_mp3(
+    /// Fetch metadata for a URL on a background task and show it for confirmation
+    fn start_preview(&mut self, url: String, clip_request: Option<ClipRequest>, bitrate: u32) {
+        self.status_message = "🔍 Fetching video details...".to_string();
+        self.search = None;
+        self.preview = Some(PreviewState {
+            url: url.clone(),
+            clip_request,
+            bitrate,
+            info: None,
+            error: None,
+        });
+
+        let tx = self.jobs_tx.clone();
+        self.tasks.spawn(async move {
+            let result = shutdown::catch_panic(ytdlp::fetch_info(&url))
+                .await
+                .map(Box::new)
+                .map_err(|e| e.to_string());
+            let _ = tx.send(JobEvent::Preview { url, result });
+        });
+    }
+
+    /// Fill in the preview, ignoring metadata for a URL the user has moved on from
+    fn finish_preview(&mut self, url: String, result: Result<Box<VideoInfo>, String>) {
+        let Some(preview) = self.preview.as_mut().filter(|p| p.url == url) else {
+            return;
+        };
+
+        match result {
+            Ok(info) => {
+                preview.info = Some(*info);
+                self.status_message = match preview.upscaled_from() {
+                    Some(abr) => format!(
+                        "⚠️ Source audio is only {abr:.0}kbps - {}kbps won't add quality. Enter to download anyway, Esc to cancel",
+                        preview.bitrate
+                    ),
+                    None => "Enter to download, Esc to cancel".to_string(),
+                };
+            }
+            Err(e) => {
+                warn!("Preview for {} failed: {}", url, e);
+                // Still allow the download - yt-dlp may well succeed where the preview didn't
+                preview.error = Some(e);
+                self.status_message =
+                    "⚠️ Couldn't fetch details - Enter to download anyway, Esc to cancel"
+                        .to_string();
+            }
+        }
+    }
+
+    /// Download the input if it's a URL, otherwise search YouTube for it
+    fn submit_input(&mut self, bitrate: u32) -> Result<()> {
+        let text = self.input.trim();
+        let is_url =
+            text.contains("youtube.com") || text.contains("youtu.be") || text.contains("://");
+
+        if is_url {
+            self.start_download(bitrate)
+        } else {
+            self.start_search();
+            Ok(())
+        }
+    }
+
+    /// Run a YouTube search for the input text on a background task
+    fn start_search(&mut self) {
+        let query = self.input.trim().to_string();
+        let count = self.config.search.results;
+        self.input.clear();
+        self.status_message = format!("🔎 Searching YouTube for '{query}'...");
+        self.search = Some(SearchState::loading(query.clone()));
+
+        let tx = self.jobs_tx.clone();
+        self.tasks.spawn(async move {
+            let result = shutdown::catch_panic(ytdlp::search(&query, count))
+                .await
+                .map_err(|e| e.to_string());
+            let _ = tx.send(JobEvent::SearchResults { query, result });
+        });
+    }
+
+    /// Everything downloaded since the app started, as a named playlist
+    fn session_playlist(&self) -> (String, Vec<HistoryEntry>) {
+        let entries = self
+            .history
+            .entries
+            .iter()
+            .filter(|e| e.session == Some(self.session))
+            .cloned()
+            .collect();
+        (export::session_name(self.session), entries)
+    }
+
+    /// Write a playlist next to the downloads
+    fn write_playlist(
         &self,
+        name: String,
+        entries: Vec<HistoryEntry>,
+    ) -> impl Future<Output = Result<PathBuf>> + use<> {
+        playlist::write(
+            name,
+            entries,
+            routing::expand_home(&self.config.output.dir),
+            self.config.playlist.clone(),
+        )
+    }
+
+    /// Write a playlist in the background
+    fn spawn_playlist(&mut self, name: String, entries: Vec<HistoryEntry>) {
+        if entries.is_empty() {
+            self.status_message = format!("📝 Nothing in '{name}' yet");
+            return;
+        }
+        self.status_message = format!("📝 Writing playlist '{name}'...");
+        let tx = self.jobs_tx.clone();
+        let write = self.write_playlist(name, entries);
+        self.tasks.spawn(async move {
+            let result = write.await.map_err(|e| e.to_string());
+            let _ = tx.send(JobEvent::Playlist { result });
+        });
+    }
+
+    /// Show search results, ignoring any that arrive for a query the user has moved on from
+    fn finish_search(&mut self, query: String, result: Result<Vec<SearchResult>, String>) {
+        let Some(search) = self.search.as_mut().filter(|s| s.query == query) else {
+            return;
+        };
+
+        match result {
+            Ok(results) if results.is_empty() => {
+                self.status_message = format!("🔎 No results for '{query}'");
+                self.search = None;
+            }
+            Ok(results) => {
+                self.status_message = format!(
+                    "🔎 {} results - ↑/↓ to move, Space to mark, Enter to queue, Esc to close",
+                    results.len()
+                );
+                search.set_results(results);
+            }
+            Err(e) => {
+                warn!("Search for '{}' failed: {}", query, e);
+                self.status_message = format!("❌ Search failed: {e}");
+                self.search = None;
+            }
+        }
+    }
+
+    /// Queue the marked search results (or the highlighted one) and close the results
+    fn queue_search_selection(&mut self, bitrate: u32) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+
+        let picked = search.picked();
+        for result in &picked {
+            info!("Queued '{}' ({})", result.title, result.url());
+            let download = self.queued(result.url(), result.title.clone(), bitrate, None);
+            self.queue.push_back(download);
+        }
+        self.status_message = format!("📥 Queued {} download(s)", picked.len());
+    }
+
+    /// A download with the per-download toggles as they are now, so changing them later
+    /// doesn't change what's already waiting
+    fn queued(
+        &mut self,
         url: String,
-        output_dir: PathBuf,
+        title: String,
         bitrate: u32,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        // Step 1: Get list of existing MP3 files BEFORE download
-        let existing_mp3s = self.get_mp3_files(&output_dir).await.unwrap_or_default();
-
-        // Step 2: Do the actual download (back to working logic)
-        let output_template = output_dir.join("%(title)s.%(ext)s");
-
-        let mut cmd = tokio::process::Command::new("yt-dlp");
-        let bitrate_arg = format!("{bitrate}K");
-        let output_arg = output_template.to_string_lossy().to_string();
-        cmd.args([
-            "--format",
-            "bestaudio",       // Download ONLY audio stream (no video)
-            "--extract-audio", // Extract to final format
-            "--audio-format",
-            "mp3", // Convert to MP3
-            "--audio-quality",
-            &bitrate_arg, // Bitrate (128K/256K)
-            "--output",
-            &output_arg,         // Save to Downloads/[title].mp3
-            "--no-playlist",     // Single video only
-            "--prefer-ffmpeg",   // Use ffmpeg for conversion
-            "--embed-thumbnail", // Add album art
-            "--add-metadata",    // Add metadata
-            "--no-warnings",     // Suppress warnings
-            "--quiet",           // Minimal output
-            url.as_str(),        // YouTube URL
-        ]);
-
-        // Completely suppress all output to keep TUI clean
-        cmd.stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .stdin(Stdio::null());
-
-        let output = cmd
-            .output()
-            .await
-            .map_err(|_| "yt-dlp not found. Please install: brew install yt-dlp".to_string())?;
-
-        if !output.status.success() {
-            return Err(
-                "Download failed. Check if the YouTube URL is valid and accessible.".into(),
+        clip_request: Option<ClipRequest>,
+    ) -> QueuedDownload {
+        // Splitting a clip makes no sense, so a clip range wins
+        let split = self.split_mix && clip_request.is_none();
+        QueuedDownload {
+            url,
+            title,
+            bitrate,
+            clip_request,
+            info: None,
+            trim_silence: self.trim_silence,
+            split,
+            tracklist: if split {
+                self.pending_tracklist.take()
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Start the next queued download, if any
+    fn process_queue(&mut self) {
+        let Some(next) = self.queue.pop_front() else {
+            return;
+        };
+        info!("Starting queued download '{}'", next.title);
+        self.begin_download(next);
+    }
+
+    /// Start a download now, or queue it behind the one already running: two at once would
+    /// share the download status and the output folder
+    fn request_download(&mut self, download: QueuedDownload) {
+        if matches!(self.download_status, DownloadStatus::Downloading) {
+            info!("Queued '{}' behind the running download", download.title);
+            self.status_message = format!(
+                "📥 Queued {} - it starts when the current download finishes",
+                download.title
             );
+            self.queue.push_back(download);
+        } else {
+            self.begin_download(download);
         }
+    }
 
-        // Give the file system a moment to update
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    /// Start downloading the YouTube video as MP3 with robust error handling
+    fn start_download(&mut self, bitrate: u32) -> Result<()> {
+        // Anything after the URL is an optional clip range
+        let (url, clip_request) = clip::parse_input(&self.input);
 
-        // Step 3: Get list of MP3 files AFTER download
-        let new_mp3s = self.get_mp3_files(&output_dir).await.unwrap_or_default();
+        if url.is_empty() {
+            self.status_message = "Please enter a YouTube URL".to_string();
+            warn!("Empty URL provided");
+            return Ok(());
+        }
 
-        // Step 4: Find the NEW file (difference between before and after)
-        let new_file = new_mp3s
-            .iter()
-            .find(|file| !existing_mp3s.contains(file))
-            .cloned()
-            .unwrap_or_else(|| {
-                // If no new file found, try to get the most recently modified MP3
-                fs::read_dir(&output_dir)
-                    .ok()
-                    .and_then(|entries| {
-                        entries
-                            .filter_map(|e| e.ok())
-                            .filter(|e| e.path().extension().is_some_and(|ext| ext == "mp3"))
-                            .max_by_key(|e| {
-                                e.metadata()
-                                    .and_then(|m| m.modified())
-                                    .unwrap_or(UNIX_EPOCH)
-                            })
-                    })
-                    .and_then(|e| e.file_name().into_string().ok())
-                    .unwrap_or_else(|| "unknown.mp3".to_string())
-            });
+        // yt-dlp resolves other sites (SoundCloud, Bandcamp, ...) as well
+        let is_web = url.starts_with("https://") || url.starts_with("http://");
+        if !is_web && !url.contains("youtube.com") && !url.contains("youtu.be") {
+            self.status_message = "Please enter a valid YouTube or SoundCloud URL".to_string();
+            warn!("Invalid URL provided: {}", url);
+            return Ok(());
+        }
 
-        Ok(format!("✅ Downloaded: {new_file}"))
+        // Clear the input field when download starts
+        self.input.clear();
+
+        if self.config.preview.enabled {
+            self.start_preview(url, clip_request, bitrate);
+            return Ok(());
+        }
+        let download = self.queued(url.clone(), url, bitrate, clip_request);
+        self.request_download(download);
+        Ok(())
     }
 
-    /// Helper function to get all MP3 filenames in a directory
-    async fn get_mp3_files(
-        &self,
-        dir: &PathBuf,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let mut mp3_files = Vec::new();
-
-        // Check if directory exists
-        if !dir.exists() {
-            return Ok(mp3_files);
-        }
-
-        let mut entries = tokio::fs::read_dir(dir).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-
-            // Only process files (not directories)
-            if path.is_file() {
-                if let Some(extension) = path.extension() {
-                    if extension == "mp3" {
-                        if let Some(filename) = path.file_name() {
-                            if let Some(filename_str) = filename.to_str() {
-                                // Only add non-empty filenames that actually contain text
-                                if !filename_str.is_empty() && filename_str.len() > 4 {
-                                    mp3_files.push(filename_str.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
+    /// Download a validated URL with the options it was submitted with, in the background
+    fn begin_download(&mut self, download: QueuedDownload) {
+        let submitted = Box::new(download.clone());
+        let QueuedDownload {
+            url,
+            bitrate,
+            clip_request,
+            info,
+            trim_silence,
+            split,
+            tracklist,
+            ..
+        } = download;
+        let options = DownloadOptions {
+            bitrate,
+            trim_silence,
+            clip: None,
+            split,
+            source: None,
+        };
+        let request = download::Request {
+            tracklist,
+            url,
+            options,
+            clip_request,
+            info: info.map(|info| *info),
+        };
+
+        self.download_status = DownloadStatus::Downloading;
+        self.status_message = format!("🎵 Starting download at {bitrate}kbps...");
+        let tx = self.jobs_tx.clone();
+        let output = self.config.output.clone();
+        self.tasks.spawn(async move {
+            let progress = |message| {
+                let _ = tx.send(JobEvent::DownloadProgress { message });
+            };
+            let result = shutdown::catch_panic(download::run(request, &output, progress))
+                .await
+                .map(Box::new)
+                .map_err(|e| e.to_string());
+            // The receiver only goes away when the app is shutting down
+            let _ = tx.send(JobEvent::Downloaded {
+                download: submitted,
+                result,
+            });
+        });
+    }
+
+    /// Record a finished download and hand it to processing, or offer a retry if it failed
+    fn finish_download(
+        &mut self,
+        submitted: QueuedDownload,
+        result: Result<Box<Downloaded>, String>,
+    ) {
+        let download = match result {
+            Ok(download) => download,
+            Err(e) => {
+                error!("Download failed: {}", e);
+                self.download_status = DownloadStatus::Error(e.clone());
+                // Name the retry key as the user has bound it
+                self.status_message = match self.keymap.label(Action::Retry) {
+                    Some(keys) => format!("❌ Download failed: {e} - {keys} to retry"),
+                    None => format!("❌ Download failed: {e} - use the Retry button to try again"),
+                };
+                self.last_failed = Some(submitted);
+                return;
             }
+        };
+        let url = submitted.url;
+        let Downloaded {
+            filename,
+            path,
+            options,
+            split,
+        } = *download;
+
+        self.download_status = DownloadStatus::Success(filename.clone());
+        self.status_message = format!("✅ Successfully downloaded: {filename}");
+
+        let id = self.history.add(filename, path.clone(), url.clone());
+        if let Some(index) = self
+            .target_crate
+            .as_deref()
+            .and_then(|name| self.history.find_crate(name))
+        {
+            self.history.crates[index].add(id);
+        }
+
+        // Every download goes through processing, if only to get its final name
+        if let Some(entry) = self.history.get_mut(id) {
+            entry.clip = options.clip.clone();
+            entry.analyzing = true;
+        }
+        self.save_history();
+
+        if let Some(plan) = &split {
+            self.status_message = format!(
+                "✅ Downloaded - splitting into {} tracks...",
+                plan.segments.len()
+            );
+        } else if options.split {
+            self.status_message =
+                "✅ Downloaded as one file - no chapters or tracklist found to split by"
+                    .to_string();
         }
 
-        Ok(mp3_files)
+        self.spawn_processing(vec![(id, path)], url, options, split);
     }
 
     /// Get the current input value
@@ -484,6 +2302,68 @@ impl App {
 
     /// Check if input field is focused
     pub fn is_input_focused(&self) -> bool {
-        true // Always focused now since it's the only element
+        self.focus == Focus::Input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> HistoryStore {
+        let mut store = HistoryStore::default();
+        for name in ["First.mp3", "Second.mp3", "Third.mp3"] {
+            store.add(name.to_string(), PathBuf::from(name), String::new());
+        }
+        let first = store.create_crate("Warmup");
+        store.crates[first].tracks = vec![0, 1];
+        store.create_crate("Peak Time");
+        store
+    }
+
+    #[test]
+    fn crate_panel_lists_newest_downloads_first() {
+        let store = store();
+        let mut panel = CratePanel::new();
+        assert_eq!(
+            panel.history_entry(&store).map(|e| e.filename.as_str()),
+            Some("Third.mp3")
+        );
+        for _ in 0..5 {
+            panel.select(&store, false);
+        }
+        assert_eq!(panel.history_selected, 2);
+        assert_eq!(
+            panel.history_entry(&store).map(|e| e.filename.as_str()),
+            Some("First.mp3")
+        );
+    }
+
+    #[test]
+    fn crate_panel_cursor_stays_in_its_list() {
+        let store = store();
+        let mut panel = CratePanel::new();
+
+        panel.cycle(true);
+        assert_eq!(panel.column, CrateColumn::Tracks);
+        panel.select(&store, false);
+        panel.select(&store, false);
+        assert_eq!(panel.track_selected, 1);
+
+        // Picking another crate starts at its first track
+        panel.cycle(true);
+        assert_eq!(panel.column, CrateColumn::Crates);
+        panel.select(&store, false);
+        panel.select(&store, false);
+        assert_eq!(panel.crate_selected, 1);
+        assert_eq!(panel.track_selected, 0);
+
+        // The second crate is empty
+        panel.cycle(false);
+        panel.select(&store, false);
+        assert_eq!(panel.track_selected, 0);
+
+        panel.cycle(false);
+        assert_eq!(panel.column, CrateColumn::History);
     }
 }
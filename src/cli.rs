@@ -0,0 +1,239 @@
+//! Subcommands that run without the TUI, e.g. before a gig.
+
+use clap::{Args, Parser, Subcommand};
+use color_eyre::{Result, eyre::eyre};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::duplicates;
+use crate::export::usb::{self, ExportOptions};
+use crate::export::{Library, rekordbox, serato, traktor, virtualdj};
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::routing;
+
+/// Download YouTube audio for DJing; run without a subcommand for the TUI
+#[derive(Debug, Parser)]
+#[command(name = "dj-cli", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List the download history with the ids other commands take
+    List,
+    /// Copy tracks to a USB stick for CDJs and controllers
+    Export(ExportArgs),
+    /// Write the history as Rekordbox XML (File > Import Collection)
+    Rekordbox {
+        /// Where to write the XML, e.g. ~/Music/dj-cli.xml
+        output: PathBuf,
+    },
+    /// Write one Serato crate per session
+    Serato {
+        /// The `_Serato_` folder to add crates to [default: ~/Music/_Serato_]
+        dir: Option<PathBuf>,
+    },
+    /// Write the history as a Traktor collection (File > Import Another Collection)
+    Traktor {
+        /// Where to write the NML, e.g. ~/Music/dj-cli.nml
+        output: PathBuf,
+    },
+    /// Write a VirtualDJ database and one virtual folder per session
+    #[command(name = "virtualdj")]
+    VirtualDj {
+        /// Folder to write database.xml and Folders/ into, e.g. /Volumes/USB/VirtualDJ
+        dir: PathBuf,
+    },
+    /// List downloads and library files that sound the same
+    Duplicates {
+        /// How alike tracks must be, from 0.0 to 1.0 [default: duplicates.threshold in the config]
+        #[arg(long)]
+        threshold: Option<f32>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Mount point of the stick, e.g. /Volumes/USB
+    pub target: PathBuf,
+    /// History ids to export (see `dj-cli list`)
+    pub ids: Vec<u64>,
+    /// Export the whole history
+    #[arg(long, conflicts_with = "ids")]
+    pub all: bool,
+    /// Export a crate, in its order
+    #[arg(long = "crate", value_name = "NAME", conflicts_with_all = ["ids", "all"])]
+    pub crate_name: Option<String>,
+    /// Convert everything to this format (mp3, m4a, aiff, wav)
+    #[arg(long)]
+    pub format: Option<String>,
+    /// Bitrate for lossy conversions, in kbps
+    #[arg(long)]
+    pub bitrate: Option<u32>,
+}
+
+/// Run a subcommand to completion
+pub async fn run(command: Command) -> Result<()> {
+    let config = Config::load();
+    let mut history = HistoryStore::load();
+
+    match command {
+        Command::List => {
+            list(&history, &config);
+            Ok(())
+        }
+        Command::Export(args) => export(args, &history, &config).await,
+        Command::Rekordbox { output } => {
+            let library = Library::gather(&history).await;
+            rekordbox::write(&output, &library).await?;
+            print_library(&library, &output);
+            Ok(())
+        }
+        Command::Serato { dir } => {
+            let dir = dir.unwrap_or_else(|| routing::expand_home("~/Music/_Serato_"));
+            let library = Library::gather(&history).await;
+            for path in serato::write(&dir, &library).await? {
+                println!("  wrote  {}", path.display());
+            }
+            Ok(())
+        }
+        Command::Traktor { output } => {
+            let library = Library::gather(&history).await;
+            traktor::write(&output, &library).await?;
+            print_library(&library, &output);
+            Ok(())
+        }
+        Command::VirtualDj { dir } => {
+            let library = Library::gather(&history).await;
+            virtualdj::write(&dir, &library).await?;
+            print_library(&library, &dir);
+            Ok(())
+        }
+        Command::Duplicates { threshold } => {
+            let threshold = threshold.unwrap_or(config.duplicates.threshold);
+            report_duplicates(&mut history, threshold).await
+        }
+    }
+}
+
+/// Fingerprint anything that isn't yet, then print each group of tracks that sound the same
+async fn report_duplicates(history: &mut HistoryStore, threshold: f32) -> Result<()> {
+    let added = duplicates::backfill(history).await;
+    if added > 0 {
+        println!("Fingerprinted {added} files");
+        history.save()?;
+    }
+
+    let tracks: Vec<_> = duplicates::tracks(history)
+        .into_iter()
+        .filter(|t| t.path.is_file())
+        .collect();
+    let clusters = duplicates::clusters(&tracks, threshold);
+    for cluster in &clusters {
+        println!();
+        for track in cluster {
+            let source = match track.download {
+                Some(id) => format!("download {id:>5}"),
+                None => "library       ".to_string(),
+            };
+            println!("  {source}  {}", track.path.display());
+        }
+    }
+    println!();
+    println!(
+        "{} groups of duplicates among {} tracks",
+        clusters.len(),
+        tracks.len()
+    );
+    Ok(())
+}
+
+/// Report what a library export wrote
+fn print_library(library: &Library, path: &std::path::Path) {
+    println!(
+        "Wrote {} tracks and {} playlists to {}",
+        library.tracks.len(),
+        library.playlists.len(),
+        path.display()
+    );
+}
+
+/// Print one line per history entry
+fn list(history: &HistoryStore, config: &Config) {
+    for entry in &history.entries {
+        let bpm = entry
+            .bpm
+            .map(|bpm| format!("{bpm:.1} BPM"))
+            .unwrap_or_default();
+        let key = entry
+            .key
+            .map(|key| key.format(config.analysis.key_notation))
+            .unwrap_or_default();
+        let indent = if entry.parent.is_some() { "  " } else { "" };
+        println!("{:>5}  {indent}{}  {bpm} {key}", entry.id, entry.filename);
+    }
+}
+
+async fn export(args: ExportArgs, history: &HistoryStore, config: &Config) -> Result<()> {
+    let entries: Vec<HistoryEntry> = if args.all {
+        history.entries.clone()
+    } else if let Some(name) = &args.crate_name {
+        let index = history
+            .find_crate(name)
+            .ok_or_else(|| eyre!("No crate named '{name}'"))?;
+        history.crates[index]
+            .tracks
+            .iter()
+            .filter_map(|id| history.get(*id).cloned())
+            .collect()
+    } else {
+        args.ids
+            .iter()
+            .map(|id| {
+                history
+                    .entries
+                    .iter()
+                    .find(|e| e.id == *id)
+                    .cloned()
+                    .ok_or_else(|| eyre!("No history entry with id {id} (see `dj-cli list`)"))
+            })
+            .collect::<Result<_>>()?
+    };
+    if entries.is_empty() {
+        return Err(eyre!(
+            "Nothing to export - pass history ids, --crate or --all"
+        ));
+    }
+
+    let options = ExportOptions {
+        format: args.format,
+        bitrate: args.bitrate.unwrap_or(config.export.bitrate),
+    };
+    println!(
+        "Exporting {} tracks to {}...",
+        entries.len(),
+        args.target.display()
+    );
+    let summary = usb::export(&args.target, &entries, &config.export, &options).await?;
+
+    for path in &summary.copied {
+        println!("  copied   {}", path.display());
+    }
+    for path in &summary.skipped {
+        println!("  present  {}", path.display());
+    }
+    for name in &summary.missing {
+        println!("  missing  {name}");
+    }
+    println!(
+        "Done: {} copied ({} transcoded, {}), {} already present, {} missing",
+        summary.copied.len(),
+        summary.transcoded,
+        usb::format_size(summary.bytes),
+        summary.skipped.len(),
+        summary.missing.len()
+    );
+    Ok(())
+}
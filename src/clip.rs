@@ -0,0 +1,273 @@
+//! Clip requests typed after the URL in the input box.
+//!
+//! Supported forms (after a space following the URL):
+//! - `42:10-47:30`  explicit start and end
+//! - `42:10-`       from a start time to the end of the video
+//! - `-47:30`       from the URL's `t=` (or the beginning) to an end time
+//! - `ch:3`         the third chapter
+//! - `ch`           the chapter containing the URL's `t=`
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use crate::ytdlp::Chapter;
+
+/// Time range in `H:MM:SS`, `MM:SS` or plain seconds, either side optional
+static RANGE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:^|\s)((?:\d+:)?(?:\d+:)?\d+(?:\.\d+)?)?-((?:\d+:)?(?:\d+:)?\d+(?:\.\d+)?)?(?:\s|$)",
+    )
+    .expect("valid clip range regex")
+});
+
+/// Chapter selector: `ch` or `ch:N`
+static CHAPTER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\s)ch(?::(\d+))?(?:\s|$)").expect("valid chapter regex"));
+
+/// YouTube's `t=` parameter: `90`, `90s` or `1h2m30s`
+static START_PARAM_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[?&#]t=(?:(\d+)h)?(?:(\d+)m)?(?:(\d+)s?)?").expect("valid t= regex")
+});
+
+/// What part of a video the user asked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipRequest {
+    /// Explicit times in seconds; a missing start falls back to the URL's `t=`
+    Range {
+        start: Option<f64>,
+        end: Option<f64>,
+    },
+    /// A chapter by 1-based index, or the chapter containing `t=` when `None`
+    Chapter(Option<usize>),
+}
+
+/// A resolved clip ready to hand to yt-dlp
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clip {
+    /// Start in seconds
+    pub start: f64,
+    /// End in seconds, or `None` for the end of the video
+    pub end: Option<f64>,
+    /// Chapter title, when the clip came from chapter data
+    pub title: Option<String>,
+}
+
+impl Clip {
+    /// Human-readable range, e.g. "42:10-47:30"
+    pub fn label(&self) -> String {
+        match self.end {
+            Some(end) => format!("{}-{}", format_time(self.start), format_time(end)),
+            None => format!("{}-end", format_time(self.start)),
+        }
+    }
+
+    /// Filesystem-safe range for file names (no colons), e.g. "42m10s-47m30s"
+    pub fn file_label(&self) -> String {
+        let part = |secs: f64| {
+            let secs = secs.round() as u64;
+            match (secs / 3600, secs % 3600 / 60, secs % 60) {
+                (0, m, s) => format!("{m}m{s:02}s"),
+                (h, m, s) => format!("{h}h{m:02}m{s:02}s"),
+            }
+        };
+        match self.end {
+            Some(end) => format!("{}-{}", part(self.start), part(end)),
+            None => format!("{}-end", part(self.start)),
+        }
+    }
+
+    /// Value for yt-dlp's `--download-sections`
+    pub fn section_arg(&self) -> String {
+        match self.end {
+            Some(end) => format!("*{:.3}-{:.3}", self.start, end),
+            None => format!("*{:.3}-inf", self.start),
+        }
+    }
+}
+
+impl ClipRequest {
+    /// Whether resolving this request needs the video's chapter list
+    pub fn needs_chapters(&self) -> bool {
+        matches!(self, ClipRequest::Chapter(_))
+    }
+
+    /// Turn the request into concrete times using the URL's `t=` and chapter data
+    pub fn resolve(&self, url_start: Option<f64>, chapters: &[Chapter]) -> Result<Clip, String> {
+        match self {
+            ClipRequest::Range { start, end } => {
+                let start = start.or(url_start).unwrap_or(0.0);
+                if let Some(end) = end
+                    && *end <= start
+                {
+                    return Err(format!(
+                        "Clip end {} is before its start {}",
+                        format_time(*end),
+                        format_time(start)
+                    ));
+                }
+                Ok(Clip {
+                    start,
+                    end: *end,
+                    title: None,
+                })
+            }
+            ClipRequest::Chapter(index) => {
+                if chapters.is_empty() {
+                    return Err("This video has no chapters".to_string());
+                }
+                let chapter = match index {
+                    Some(n) => chapters.get(n.saturating_sub(1)).ok_or_else(|| {
+                        format!("Chapter {n} not found ({} chapters)", chapters.len())
+                    })?,
+                    None => {
+                        let t = url_start.ok_or("Use ch:N, or a URL with t= inside a chapter")?;
+                        chapters
+                            .iter()
+                            .find(|c| c.start_time <= t && t < c.end_time)
+                            .ok_or_else(|| format!("No chapter at {}", format_time(t)))?
+                    }
+                };
+                Ok(Clip {
+                    start: chapter.start_time,
+                    end: Some(chapter.end_time),
+                    title: Some(chapter.title.clone()),
+                })
+            }
+        }
+    }
+}
+
+/// Split input into the URL part and an optional clip request following it
+pub fn parse_input(input: &str) -> (String, Option<ClipRequest>) {
+    let input = input.trim();
+    let Some((url, rest)) = input.split_once(char::is_whitespace) else {
+        return (input.to_string(), None);
+    };
+    (url.to_string(), parse_request(rest))
+}
+
+/// Parse a clip request from free text
+pub fn parse_request(text: &str) -> Option<ClipRequest> {
+    if let Some(captures) = CHAPTER_RE.captures(text) {
+        let index = captures.get(1).and_then(|m| m.as_str().parse().ok());
+        return Some(ClipRequest::Chapter(index));
+    }
+
+    // A lone dash in prose isn't a range, so keep looking past it
+    RANGE_RE.captures_iter(text).find_map(|captures| {
+        let start = captures.get(1).and_then(|m| parse_time(m.as_str()));
+        let end = captures.get(2).and_then(|m| parse_time(m.as_str()));
+        (start.is_some() || end.is_some()).then_some(ClipRequest::Range { start, end })
+    })
+}
+
+/// Find a clip request in messy pasted text and return it in canonical form
+pub fn find_spec(text: &str) -> Option<String> {
+    match parse_request(text)? {
+        ClipRequest::Chapter(Some(n)) => Some(format!("ch:{n}")),
+        ClipRequest::Chapter(None) => Some("ch".to_string()),
+        ClipRequest::Range { start, end } => Some(format!(
+            "{}-{}",
+            start.map(format_time).unwrap_or_default(),
+            end.map(format_time).unwrap_or_default()
+        )),
+    }
+}
+
+/// Read the `t=` start offset from a YouTube URL, in seconds
+pub fn start_param(url: &str) -> Option<f64> {
+    let captures = START_PARAM_RE.captures(url)?;
+    let part = |i: usize| -> f64 {
+        captures
+            .get(i)
+            .and_then(|m| m.as_str().parse::<f64>().ok())
+            .unwrap_or(0.0)
+    };
+    if (1..=3).all(|i| captures.get(i).is_none()) {
+        return None;
+    }
+    Some(part(1) * 3600.0 + part(2) * 60.0 + part(3))
+}
+
+/// Parse `H:MM:SS`, `MM:SS` or plain seconds
+pub fn parse_time(text: &str) -> Option<f64> {
+    if text.split(':').count() > 3 {
+        return None;
+    }
+    text.split(':').try_fold(0.0, |total, part| {
+        Some(total * 60.0 + part.parse::<f64>().ok()?)
+    })
+}
+
+/// Format seconds as `M:SS` or `H:MM:SS`
+pub fn format_time(seconds: f64) -> String {
+    let total = seconds.round() as u64;
+    let (h, m, s) = (total / 3600, total % 3600 / 60, total % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: Option<f64>, end: Option<f64>) -> Option<ClipRequest> {
+        Some(ClipRequest::Range { start, end })
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time("90"), Some(90.0));
+        assert_eq!(parse_time("1:30"), Some(90.0));
+        assert_eq!(parse_time("1:02:30"), Some(3750.0));
+        assert_eq!(parse_time("12.5"), Some(12.5));
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time("1::30"), None);
+        assert_eq!(parse_time("abc"), None);
+    }
+
+    #[test]
+    fn parses_ranges_and_chapters() {
+        assert_eq!(parse_request("1:30-2:45"), range(Some(90.0), Some(165.0)));
+        assert_eq!(parse_request("42:10-"), range(Some(2530.0), None));
+        assert_eq!(parse_request("-47:30"), range(None, Some(2850.0)));
+        assert_eq!(
+            parse_request("1:00:00-1:05:00"),
+            range(Some(3600.0), Some(3900.0))
+        );
+        assert_eq!(parse_request("ch:3"), Some(ClipRequest::Chapter(Some(3))));
+        assert_eq!(parse_request("ch"), Some(ClipRequest::Chapter(None)));
+        // A dash on its own in prose isn't a range
+        assert_eq!(parse_request("live - at the club"), None);
+
+        let (url, request) = parse_input("https://youtu.be/abc 1:30-2:45");
+        assert_eq!(url, "https://youtu.be/abc");
+        assert_eq!(request, range(Some(90.0), Some(165.0)));
+    }
+
+    #[test]
+    fn reads_the_start_param() {
+        let url = "https://www.youtube.com/watch?v=abc";
+        assert_eq!(start_param(&format!("{url}&t=90")), Some(90.0));
+        assert_eq!(start_param(&format!("{url}&t=90s")), Some(90.0));
+        assert_eq!(start_param(&format!("{url}&t=1h2m30s")), Some(3750.0));
+        assert_eq!(start_param("https://youtu.be/abc?t=2m"), Some(120.0));
+        assert_eq!(start_param(url), None);
+    }
+
+    #[test]
+    fn open_ranges_start_at_the_start_param() {
+        let request = parse_request("-2:00").unwrap();
+        let clip = request.resolve(Some(30.0), &[]).unwrap();
+        assert_eq!((clip.start, clip.end), (30.0, Some(120.0)));
+        assert!(request.resolve(Some(150.0), &[]).is_err());
+
+        let clip = parse_request("1:00-").unwrap().resolve(None, &[]).unwrap();
+        assert_eq!(clip.section_arg(), "*60.000-inf");
+        assert_eq!(clip.file_label(), "1m00s-end");
+    }
+}
@@ -0,0 +1,469 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// User configuration loaded from `~/.config/dj-cli/config.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Offline analysis run on every finished download
+    pub analysis: AnalysisConfig,
+    /// Loudness measurement and normalisation after download
+    pub loudness: LoudnessConfig,
+    /// Leading/trailing silence trimming
+    pub trim: TrimConfig,
+    /// Clip extraction
+    pub clip: ClipConfig,
+    /// In-app YouTube search
+    pub search: SearchConfig,
+    /// Metadata preview before each download
+    pub preview: PreviewConfig,
+    /// Source quality check
+    pub quality: QualityConfig,
+    /// Where downloads are saved
+    pub output: OutputConfig,
+    /// How downloaded files are named
+    pub naming: NamingConfig,
+    /// USB stick export
+    pub export: ExportConfig,
+    /// Session playlists
+    pub playlist: PlaylistConfig,
+    /// Local music library
+    pub library: LibraryConfig,
+    /// Duplicate detection
+    pub duplicates: DuplicatesConfig,
+    /// Terminal interface
+    pub ui: UiConfig,
+    /// Key bindings by action name, e.g. `download_128 = ["ctrl+1", "alt+1"]`
+    pub keys: HashMap<String, KeyList>,
+}
+
+/// One key or several for a binding in `[keys]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        match self {
+            KeyList::One(key) => std::slice::from_ref(key).iter(),
+            KeyList::Many(keys) => keys.iter(),
+        }
+    }
+}
+
+/// Settings for the terminal interface
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// Capture the mouse for clicking and scrolling; off leaves the terminal's own text selection alone
+    pub mouse: bool,
+    /// "dark", "light", "high-contrast", "monochrome" or the name of a theme under `[ui.themes]`
+    pub theme: String,
+    /// How many colours the terminal shows; `auto` goes by `COLORTERM` and `TERM`
+    pub colors: ColorMode,
+    /// User themes by name
+    pub themes: HashMap<String, ThemeConfig>,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            mouse: true,
+            theme: "dark".to_string(),
+            colors: ColorMode::default(),
+            themes: HashMap::new(),
+        }
+    }
+}
+
+/// Colour support of the terminal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    /// 24-bit colour
+    Truecolor,
+    /// The 256-colour palette
+    #[serde(rename = "256")]
+    Ansi256,
+    /// The 16 basic colours
+    #[serde(rename = "16")]
+    Ansi16,
+    /// No colour, as with `NO_COLOR`
+    None,
+}
+
+/// A user theme: colours as "#rrggbb", names like "lightcyan" or 256-colour indices.
+/// Anything left out comes from `base`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Theme to start from [default: dark]
+    pub base: Option<String>,
+    pub banner: Option<String>,
+    pub text: Option<String>,
+    pub muted: Option<String>,
+    pub accent: Option<String>,
+    pub focus: Option<String>,
+    pub success: Option<String>,
+    pub warning: Option<String>,
+    /// Background of the highlighted row
+    pub selection: Option<String>,
+    /// Background of the highlighted row in unfocused lists
+    pub selection_inactive: Option<String>,
+}
+
+/// Settings for acoustic duplicate detection
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DuplicatesConfig {
+    /// Fingerprint downloads and library files, and flag downloads you already have
+    pub enabled: bool,
+    /// How alike two fingerprints must be to count as the same track, from 0.0 to 1.0
+    pub threshold: f32,
+}
+
+impl Default for DuplicatesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.6,
+        }
+    }
+}
+
+/// Settings for the local library scanner
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LibraryConfig {
+    /// Folders scanned recursively for MP3, FLAC, AIFF and WAV files
+    pub folders: Vec<String>,
+}
+
+/// Settings for the playlist written for each session
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PlaylistConfig {
+    /// Write the session's playlist when the app exits
+    pub on_exit: bool,
+    pub format: PlaylistFormat,
+    /// Paths relative to the playlist, so it keeps working when the folder is copied to a stick
+    pub relative_paths: bool,
+}
+
+impl Default for PlaylistConfig {
+    fn default() -> Self {
+        Self {
+            on_exit: true,
+            format: PlaylistFormat::default(),
+            relative_paths: true,
+        }
+    }
+}
+
+/// Playlist file format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    /// Extended M3U in UTF-8
+    #[default]
+    M3u8,
+    Pls,
+}
+
+/// Settings for `dj-cli export`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Formats the player reads as they are
+    pub formats: Vec<String>,
+    /// Format everything else is converted to
+    pub transcode_to: String,
+    /// Bitrate for lossy conversions, in kbps
+    pub bitrate: u32,
+    /// Folder on the stick the tracks are copied into
+    pub folder: String,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            formats: ["mp3", "m4a", "aiff", "wav"].map(String::from).to_vec(),
+            transcode_to: "mp3".to_string(),
+            bitrate: 320,
+            folder: "DJ-CLI".to_string(),
+        }
+    }
+}
+
+/// Settings for file names
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NamingConfig {
+    /// e.g. "{artist} - {title} ({mix}) [{bpm} {key}]"; unset keeps the video title
+    pub template: Option<String>,
+    /// Longest file name allowed, in characters (without extension)
+    pub max_length: usize,
+}
+
+impl Default for NamingConfig {
+    fn default() -> Self {
+        Self {
+            template: None,
+            max_length: 120,
+        }
+    }
+}
+
+/// Settings for where downloads land
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Default folder; `~` expands to the home directory
+    pub dir: String,
+    /// Routing rules, tried in order; the first match picks the folder
+    pub routes: Vec<RouteRule>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            dir: "~/Downloads".to_string(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+/// Send downloads matching every condition set here to `dir`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteRule {
+    /// Site name ("youtube", "soundcloud") or part of the URL
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Genre from the metadata, case-insensitive
+    #[serde(default)]
+    pub genre: Option<String>,
+    /// Channel or uploader name, case-insensitive
+    #[serde(default)]
+    pub uploader: Option<String>,
+    /// Text anywhere in the title, case-insensitive
+    #[serde(default)]
+    pub title_contains: Option<String>,
+    /// Target folder; may use `{year}`, `{genre}`, `{artist}` etc. Relative folders sit under `output.dir`
+    pub dir: String,
+}
+
+/// Settings for the source quality check
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// Record the source format and measure the bandwidth of each download
+    pub enabled: bool,
+    /// Bandwidth below which a track is flagged as coming from a low-quality source, in Hz
+    pub min_cutoff_hz: f32,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_cutoff_hz: 16000.0,
+        }
+    }
+}
+
+/// Settings for the pre-download preview
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PreviewConfig {
+    /// Show the video's details and wait for confirmation before downloading
+    pub enabled: bool,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Settings for YouTube search
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// How many results to fetch per search
+    pub results: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self { results: 10 }
+    }
+}
+
+/// Settings for clips cut out of longer videos
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ClipConfig {
+    /// Fade-in length in seconds (0 = none)
+    pub fade_in: f32,
+    /// Fade-out length in seconds (0 = none)
+    pub fade_out: f32,
+}
+
+/// Settings for silence trimming
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TrimConfig {
+    /// Whether new downloads start with trimming switched on (toggle per download with Ctrl+T)
+    pub enabled: bool,
+    /// Level below which audio counts as silence, in dBFS
+    pub threshold_db: f32,
+}
+
+impl Default for TrimConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -50.0,
+        }
+    }
+}
+
+/// Settings for the optional loudness stage
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoudnessConfig {
+    /// What to do with the measurement
+    pub mode: LoudnessMode,
+    /// Integrated loudness to aim for, in LUFS (e.g. -9 for club, -14 for streaming)
+    pub target_lufs: f32,
+    /// Highest true peak allowed after gain, in dBTP
+    pub true_peak_ceiling: f32,
+}
+
+/// How the loudness stage applies its measurement
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoudnessMode {
+    /// Don't measure
+    #[default]
+    Off,
+    /// Measure and write ReplayGain tags, leaving the audio untouched
+    Tags,
+    /// Measure and re-encode with gain applied
+    Normalize,
+}
+
+impl LoudnessConfig {
+    /// Whether the loudness stage runs at all
+    pub fn enabled(&self) -> bool {
+        self.mode != LoudnessMode::Off
+    }
+}
+
+impl Default for LoudnessConfig {
+    fn default() -> Self {
+        Self {
+            mode: LoudnessMode::default(),
+            target_lufs: -14.0,
+            true_peak_ceiling: -1.0,
+        }
+    }
+}
+
+/// Settings for the post-download analysis pass
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AnalysisConfig {
+    /// Run BPM detection after each download
+    pub bpm: bool,
+    /// Lowest tempo considered (keeps half-time errors out)
+    pub bpm_min: f32,
+    /// Highest tempo considered (keeps double-time errors out)
+    pub bpm_max: f32,
+    /// Run key detection after each download
+    pub key: bool,
+    /// How keys are written to tags and shown in the history
+    pub key_notation: KeyNotation,
+}
+
+/// Notation used for detected keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyNotation {
+    /// e.g. "A minor"
+    Standard,
+    /// e.g. "8A"
+    #[default]
+    Camelot,
+}
+
+impl AnalysisConfig {
+    /// Whether any analysis pass is switched on
+    pub fn enabled(&self) -> bool {
+        self.bpm || self.key
+    }
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        Self {
+            bpm: true,
+            bpm_min: 70.0,
+            bpm_max: 180.0,
+            key: true,
+            key_notation: KeyNotation::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Location of the config file
+    pub fn path() -> PathBuf {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        PathBuf::from(home)
+            .join(".config")
+            .join("dj-cli")
+            .join("config.toml")
+    }
+
+    /// Load the config file, falling back to defaults if it is missing or invalid
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            info!("No config file at {}, using defaults", path.display());
+            return Self::default();
+        };
+
+        match toml::from_str::<Config>(&contents) {
+            Ok(config) => config.validated(),
+            Err(e) => {
+                warn!("Invalid config file {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Fix up values that would make the analysis misbehave
+    fn validated(mut self) -> Self {
+        let analysis = &mut self.analysis;
+        if !(analysis.bpm_min > 0.0 && analysis.bpm_min < analysis.bpm_max) {
+            warn!(
+                "Invalid BPM range {}-{}, using defaults",
+                analysis.bpm_min, analysis.bpm_max
+            );
+            let defaults = AnalysisConfig::default();
+            analysis.bpm_min = defaults.bpm_min;
+            analysis.bpm_max = defaults.bpm_max;
+        }
+        self.search.results = self.search.results.clamp(1, 50);
+        self.naming.max_length = self.naming.max_length.clamp(16, 200);
+        self
+    }
+}
@@ -0,0 +1,284 @@
+//! Downloading a URL as an MP3 with yt-dlp. Runs as a background task so the interface
+//! keeps drawing; progress goes back to the UI loop as status messages along the way.
+
+use color_eyre::{Result, eyre::eyre};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{info, warn};
+
+use crate::analysis::quality::SourceAudio;
+use crate::app::DownloadOptions;
+use crate::clip::{self, Clip, ClipRequest};
+use crate::config::OutputConfig;
+use crate::postprocess::split::SplitPlan;
+use crate::routing;
+use crate::shutdown;
+use crate::tracklist::{self, Segment};
+use crate::ytdlp::{self, VideoInfo};
+
+/// Numbers each download's work folder, so no two in this process share one
+static NEXT_WORK_DIR: AtomicU64 = AtomicU64::new(1);
+
+/// Everything a download needs, captured when it starts
+#[derive(Debug)]
+pub struct Request {
+    pub url: String,
+    pub options: DownloadOptions,
+    pub clip_request: Option<ClipRequest>,
+    /// Metadata already fetched for the preview, if any
+    pub info: Option<VideoInfo>,
+    /// A pasted tracklist to split by, ahead of chapters and the description
+    pub tracklist: Option<Vec<Segment>>,
+}
+
+/// A finished download, ready for the history and processing
+#[derive(Debug)]
+pub struct Downloaded {
+    pub filename: String,
+    pub path: PathBuf,
+    /// The options it was downloaded with, with the clip and source stream filled in
+    pub options: DownloadOptions,
+    /// How to split it, when splitting was asked for and there's something to split by
+    pub split: Option<SplitPlan>,
+}
+
+/// Download `request`, calling `progress` with a status message at each step
+pub async fn run(
+    request: Request,
+    output: &OutputConfig,
+    progress: impl Fn(String),
+) -> Result<Downloaded> {
+    let Request {
+        url,
+        mut options,
+        clip_request,
+        mut info,
+        tracklist,
+    } = request;
+    let bitrate = options.bitrate;
+
+    if let Some(request) = clip_request {
+        options.clip = Some(resolve_clip(&url, request, &mut info, &progress).await?);
+    }
+
+    let split = if options.split {
+        let plan = plan_split(&url, &mut info, tracklist, &progress).await?;
+        if plan.is_none() {
+            warn!("No chapters or tracklist for {}", url);
+        }
+        plan
+    } else {
+        None
+    };
+
+    let output_dir = route(&url, output, &mut info, &progress).await;
+    tokio::fs::create_dir_all(&output_dir).await?;
+
+    // Metadata fetched for the preview, clip, split or routing already tells us the source stream
+    if let Some(info) = &info {
+        options.source = info.best_audio().and_then(SourceAudio::from_format);
+    }
+
+    progress(match &options.clip {
+        Some(clip) => format!("🎵 Downloading clip {} at {bitrate}kbps...", clip.label()),
+        None => format!("🎵 Downloading MP3 at {bitrate}kbps... Please wait"),
+    });
+
+    let (path, source) = download_mp3(&url, &output_dir, bitrate, options.clip.as_ref()).await?;
+    // What yt-dlp says it downloaded beats what the metadata suggested it would
+    if source.is_some() {
+        options.source = source;
+    }
+    Ok(Downloaded {
+        filename: path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path,
+        options,
+        split,
+    })
+}
+
+/// Metadata for `url`, fetching it only if the preview didn't already
+async fn video_info<'a>(url: &str, info: &'a mut Option<VideoInfo>) -> Result<&'a VideoInfo> {
+    match info {
+        Some(info) => Ok(info),
+        None => Ok(info.insert(ytdlp::fetch_info(url).await?)),
+    }
+}
+
+/// Turn a clip request into concrete times, fetching chapters if it needs them
+async fn resolve_clip(
+    url: &str,
+    request: ClipRequest,
+    info: &mut Option<VideoInfo>,
+    progress: &impl Fn(String),
+) -> Result<Clip> {
+    let chapters = if request.needs_chapters() {
+        progress("📖 Reading chapters...".to_string());
+        video_info(url, info).await?.chapters()
+    } else {
+        &[]
+    };
+
+    request
+        .resolve(clip::start_param(url), chapters)
+        .map_err(|e| eyre!(e))
+}
+
+/// Work out where to split a mix: a pasted tracklist wins, then chapters, then a
+/// tracklist in the video description
+async fn plan_split(
+    url: &str,
+    info: &mut Option<VideoInfo>,
+    pasted: Option<Vec<Segment>>,
+    progress: &impl Fn(String),
+) -> Result<Option<SplitPlan>> {
+    progress("📖 Reading chapters and tracklist...".to_string());
+    let info = video_info(url, info).await?;
+
+    let segments = match pasted {
+        Some(pasted) => pasted,
+        None if !info.chapters().is_empty() => tracklist::from_chapters(info.chapters()),
+        None => tracklist::parse(info.description.as_deref().unwrap_or_default()),
+    };
+
+    if segments.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(SplitPlan {
+        segments,
+        album: info.title.clone(),
+    }))
+}
+
+/// Folder this download goes to, fetching metadata first if routing rules need it
+async fn route(
+    url: &str,
+    output: &OutputConfig,
+    info: &mut Option<VideoInfo>,
+    progress: &impl Fn(String),
+) -> PathBuf {
+    if !output.routes.is_empty() && info.is_none() {
+        progress("📖 Reading metadata for routing...".to_string());
+        if let Err(e) = video_info(url, info).await {
+            // Still download - just to the default folder
+            warn!("No metadata for routing {}: {}", url, e);
+        }
+    }
+
+    let dir = routing::output_dir(output, url, info.as_ref());
+    info!("Routing {} to {}", url, dir.display());
+    dir
+}
+
+/// Download MP3 using yt-dlp; returns the path of the file it wrote and the stream it
+/// was encoded from
+async fn download_mp3(
+    url: &str,
+    output_dir: &Path,
+    bitrate: u32,
+    clip: Option<&Clip>,
+) -> Result<(PathBuf, Option<SourceAudio>)> {
+    // Clips are saved as their own file next to any full download
+    let output_template = match clip {
+        Some(clip) => format!("%(title)s [{}].%(ext)s", clip.file_label()),
+        None => "%(title)s.%(ext)s".to_string(),
+    };
+    // yt-dlp works in a folder of its own and only moves the finished MP3 into the output
+    // folder, so whatever it leaves there is unfinished and goes when the download ends,
+    // however it ends. Each download gets its own, so one ending never clears out another.
+    let work_dir = output_dir.join(format!(
+        ".dj-cli-partial-{}-{}",
+        std::process::id(),
+        NEXT_WORK_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _partial = shutdown::Partial::new(&work_dir);
+    let home_arg = format!("home:{}", output_dir.display());
+    let temp_arg = format!("temp:{}", work_dir.display());
+
+    let mut cmd = shutdown::command("yt-dlp");
+    let bitrate_arg = format!("{bitrate}K");
+    cmd.args([
+        "--format",
+        "bestaudio",       // Download ONLY audio stream (no video)
+        "--extract-audio", // Extract to final format
+        "--audio-format",
+        "mp3", // Convert to MP3
+        "--audio-quality",
+        &bitrate_arg, // Bitrate (128K/256K)
+        "--paths",
+        &home_arg, // Save to [output dir]/[title].mp3
+        "--paths",
+        &temp_arg, // ...by way of the work folder
+        "--output",
+        &output_template,
+        "--no-playlist",     // Single video only
+        "--prefer-ffmpeg",   // Use ffmpeg for conversion
+        "--embed-thumbnail", // Add album art
+        "--add-metadata",    // Add metadata
+        "--no-warnings",     // Suppress warnings
+        "--quiet",           // Minimal output
+        "--print",
+        "after_move:%(acodec)s %(abr)s", // Then the source stream, for the quality check
+        "--print",
+        "after_move:filepath", // ...and where the MP3 ended up
+        url,                   // YouTube URL
+    ]);
+
+    // Let yt-dlp fetch only the requested section, cutting cleanly at the boundaries
+    if let Some(clip) = clip {
+        cmd.args([
+            "--download-sections",
+            &clip.section_arg(),
+            "--force-keyframes-at-cuts",
+        ]);
+    }
+
+    // Only the printed source and path come back; everything else stays out of the TUI
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|_| eyre!("yt-dlp not found. Please install: brew install yt-dlp"))?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "Download failed. Check if the YouTube URL is valid and accessible."
+        ));
+    }
+
+    printed(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| eyre!("yt-dlp didn't say where it saved the download"))
+}
+
+/// The path and source stream from yt-dlp's `--print` lines: the source, then the path.
+/// Renaming and processing add files to the output folder while a download runs, so
+/// yt-dlp's word is the only reliable way to know which file it wrote.
+fn printed(stdout: &str) -> Option<(PathBuf, Option<SourceAudio>)> {
+    let mut lines = stdout.lines().rev().filter(|line| !line.is_empty());
+    let path = PathBuf::from(lines.next()?);
+    let source = lines.next().and_then(SourceAudio::from_print);
+    Some((path, source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_what_yt_dlp_printed() {
+        let (path, source) = printed("opus 129.478\n/music/Artist - Track [live].mp3\n").unwrap();
+        assert_eq!(path, Path::new("/music/Artist - Track [live].mp3"));
+        assert_eq!(source.map(|s| s.codec), Some("opus".to_string()));
+
+        let (_, source) = printed("NA NA\n/music/Track.mp3\n").unwrap();
+        assert_eq!(source, None);
+        assert_eq!(printed(""), None);
+    }
+}
@@ -0,0 +1,368 @@
+//! Duplicate detection: downloads and library files that sound the same, however
+//! they're named - the same track ripped from two channels, or downloaded again
+//! when it was already in the library as an AIFF.
+
+use futures::{StreamExt, stream};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::analysis::fingerprint::{self, Fingerprint, Matcher};
+use crate::history::HistoryStore;
+
+/// Files fingerprinted at once by `backfill`
+const FINGERPRINT_CONCURRENCY: usize = 8;
+/// Distinct codes two fingerprints must share before they're compared in full
+const MIN_SHARED_CODES: usize = 4;
+/// Codes found in more tracks than this say nothing about which tracks match
+const COMMON_CODE_LIMIT: usize = 50;
+
+/// A fingerprinted download or library file
+#[derive(Debug, Clone)]
+pub struct Track {
+    /// File name
+    pub name: String,
+    pub path: PathBuf,
+    /// History id, for downloads
+    pub download: Option<u64>,
+    /// The mix a split track came from
+    pub parent: Option<u64>,
+    /// The video a download came from, see `source_id`
+    pub source: Option<String>,
+    /// Whether only part of the video was downloaded
+    pub clip: bool,
+    pub fingerprint: Fingerprint,
+}
+
+impl Track {
+    /// A mix and the tracks split out of it share audio without being duplicates, and so do
+    /// a clip and another download of the same video. Downloading a whole video twice still counts.
+    fn related(&self, other: &Track) -> bool {
+        let (Some(a), Some(b)) = (self.download, other.download) else {
+            return false;
+        };
+        let clipped =
+            (self.clip || other.clip) && self.source.is_some() && self.source == other.source;
+        self.parent == Some(b) || other.parent == Some(a) || clipped
+    }
+}
+
+/// What identifies the video behind a URL: YouTube's video id, or the URL without its
+/// query for other sites, so `&t=90` or a playlist parameter doesn't make it another video
+fn source_id(url: &str) -> Option<String> {
+    let url = url.trim();
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    if let Some(id) = query.split('&').find_map(|param| param.strip_prefix("v=")) {
+        return Some(id.to_string());
+    }
+    let base = base
+        .split('#')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/');
+    if let Some((_, id)) = base.split_once("youtu.be/") {
+        return Some(id.to_string());
+    }
+    (!base.is_empty()).then(|| base.to_lowercase())
+}
+
+/// Every fingerprinted download, then every fingerprinted library file that isn't also a download
+pub fn tracks(store: &HistoryStore) -> Vec<Track> {
+    let mut tracks: Vec<Track> = store
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            Some(Track {
+                name: entry.filename.clone(),
+                path: entry.path.clone(),
+                download: Some(entry.id),
+                parent: entry.parent,
+                source: source_id(&entry.url),
+                clip: entry.clip.is_some(),
+                fingerprint: entry.fingerprint.clone()?,
+            })
+        })
+        .collect();
+    for file in &store.library {
+        let Some(fingerprint) = &file.fingerprint else {
+            continue;
+        };
+        if tracks.iter().any(|t| t.path == file.path) {
+            continue;
+        }
+        tracks.push(Track {
+            name: file
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path: file.path.clone(),
+            download: None,
+            parent: None,
+            source: None,
+            clip: false,
+            fingerprint: fingerprint.clone(),
+        });
+    }
+    tracks
+}
+
+/// The existing file that sounds most like `track`, if any is at least `threshold` alike
+pub fn find<'a>(track: &Track, candidates: &'a [Track], threshold: f32) -> Option<&'a Track> {
+    let matcher = Matcher::new(&track.fingerprint);
+    candidates
+        .iter()
+        .filter(|c| c.path != track.path && !c.related(track))
+        .map(|c| (matcher.similarity(&c.fingerprint), c))
+        .filter(|(similarity, c)| *similarity >= threshold && c.path.is_file())
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, c)| c)
+}
+
+/// Groups of tracks that sound the same, each sorted by name
+pub fn clusters(tracks: &[Track], threshold: f32) -> Vec<Vec<&Track>> {
+    // Which tracks each code appears in, so only tracks with codes in common get compared
+    let distinct: Vec<Vec<u32>> = tracks
+        .iter()
+        .map(|track| {
+            let mut codes = track.fingerprint.0.clone();
+            codes.sort_unstable();
+            codes.dedup();
+            codes
+        })
+        .collect();
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, codes) in distinct.iter().enumerate() {
+        for code in codes {
+            index.entry(*code).or_default().push(i);
+        }
+    }
+
+    let mut groups = DisjointSet::new(tracks.len());
+    for (i, track) in tracks.iter().enumerate() {
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for code in &distinct[i] {
+            let holders = &index[code];
+            if holders.len() > COMMON_CODE_LIMIT {
+                continue;
+            }
+            for &j in holders.iter().filter(|&&j| j > i) {
+                *shared.entry(j).or_default() += 1;
+            }
+        }
+
+        let matcher = Matcher::new(&track.fingerprint);
+        for (j, count) in shared {
+            let other = &tracks[j];
+            if count >= MIN_SHARED_CODES
+                && !track.related(other)
+                && matcher.similarity(&other.fingerprint) >= threshold
+            {
+                groups.union(i, j);
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<&Track>> = HashMap::new();
+    for (i, track) in tracks.iter().enumerate() {
+        clusters.entry(groups.find(i)).or_default().push(track);
+    }
+    let mut clusters: Vec<Vec<&Track>> = clusters
+        .into_values()
+        .filter(|cluster| cluster.len() > 1)
+        .collect();
+    for cluster in &mut clusters {
+        cluster.sort_by_key(|t| t.name.to_lowercase());
+    }
+    clusters.sort_by_key(|cluster| cluster[0].name.to_lowercase());
+    clusters
+}
+
+/// Fingerprint downloads and library files that don't have one yet; returns how many were added
+pub async fn backfill(store: &mut HistoryStore) -> usize {
+    let downloads: Vec<(usize, PathBuf)> = store
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.fingerprint.is_none() && e.path.is_file())
+        .map(|(i, e)| (i, e.path.clone()))
+        .collect();
+    let library: Vec<(usize, PathBuf)> = store
+        .library
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.fingerprint.is_none() && f.path.is_file())
+        .map(|(i, f)| (i, f.path.clone()))
+        .collect();
+
+    let mut added = 0;
+    for (i, fingerprint) in fingerprint_all(downloads).await {
+        store.entries[i].fingerprint = Some(fingerprint);
+        added += 1;
+    }
+    for (i, fingerprint) in fingerprint_all(library).await {
+        store.library[i].fingerprint = Some(fingerprint);
+        added += 1;
+    }
+    added
+}
+
+/// Fingerprint files a few at a time, skipping (and logging) ones that fail
+async fn fingerprint_all(files: Vec<(usize, PathBuf)>) -> Vec<(usize, Fingerprint)> {
+    stream::iter(files)
+        .map(|(i, path)| async move {
+            match fingerprint::fingerprint(&path).await {
+                Ok(fingerprint) => Some((i, fingerprint)),
+                Err(e) => {
+                    warn!("Could not fingerprint {}: {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .buffer_unordered(FINGERPRINT_CONCURRENCY)
+        .filter_map(|result| async move { result })
+        .collect()
+        .await
+}
+
+/// Union-find over track indices
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Point everything on the way straight at the root
+        let mut i = i;
+        while self.parent[i] != root {
+            i = std::mem::replace(&mut self.parent[i], root);
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random codes from a seed
+    fn codes(seed: u64, len: usize) -> Vec<u32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 32) as u32
+            })
+            .collect()
+    }
+
+    fn track(name: &str, download: Option<u64>, codes: Vec<u32>) -> Track {
+        Track {
+            name: name.to_string(),
+            path: PathBuf::from(format!("/music/{name}")),
+            download,
+            parent: None,
+            source: None,
+            clip: false,
+            fingerprint: Fingerprint(codes),
+        }
+    }
+
+    #[test]
+    fn clusters_the_same_audio_under_different_names() {
+        let original = codes(1, 400);
+        // A re-upload: a different intro, then the same audio with a couple of bits flipped here and there
+        let mut reupload = codes(9, 30);
+        reupload.extend(original.iter().enumerate().map(
+            |(i, c)| {
+                if i % 5 == 0 { c ^ 0b101 } else { *c }
+            },
+        ));
+
+        let tracks = [
+            track("Artist - Track.mp3", Some(1), original),
+            track("Other Song.mp3", Some(2), codes(2, 400)),
+            track("Track (Official Video).aiff", None, reupload),
+        ];
+        let clusters = clusters(&tracks, 0.6);
+        assert_eq!(clusters.len(), 1);
+        let names: Vec<&str> = clusters[0].iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Artist - Track.mp3", "Track (Official Video).aiff"]);
+    }
+
+    #[test]
+    fn split_tracks_are_not_duplicates_of_their_mix() {
+        let audio = codes(3, 400);
+        let mix = track("Mix.mp3", Some(1), audio.clone());
+        let first = Track {
+            parent: Some(1),
+            ..track("01 Opener.mp3", Some(2), audio)
+        };
+        assert!(clusters(&[mix, first], 0.6).is_empty());
+    }
+
+    #[test]
+    fn clips_are_not_duplicates_of_their_video() {
+        let audio = codes(4, 400);
+        let from = |url: &str| Some(source_id(url).unwrap());
+        let full = Track {
+            source: from("https://www.youtube.com/watch?v=abc"),
+            ..track("Mix.mp3", Some(1), audio.clone())
+        };
+        let clip = Track {
+            source: from("https://youtu.be/abc?t=90"),
+            clip: true,
+            ..track("Mix (42-10-47-30).mp3", Some(2), audio[100..200].to_vec())
+        };
+        assert!(full.related(&clip) && clip.related(&full));
+
+        // The same video downloaded in full twice is still a duplicate
+        let again = Track {
+            source: from("https://www.youtube.com/watch?v=abc&list=PL1"),
+            ..track("Mix (1).mp3", Some(3), audio.clone())
+        };
+        assert!(!full.related(&again));
+        assert_eq!(clusters(&[full.clone(), again], 0.6).len(), 1);
+
+        // A clip of another video can still be a duplicate
+        let other = Track {
+            source: from("https://www.youtube.com/watch?v=xyz"),
+            ..clip
+        };
+        assert!(!full.related(&other));
+    }
+
+    #[test]
+    fn source_ids() {
+        assert_eq!(
+            source_id("https://www.youtube.com/watch?v=abc&t=10s").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            source_id("https://youtu.be/abc?t=90").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            source_id("https://SoundCloud.com/someone/edit/?in=x").as_deref(),
+            Some("https://soundcloud.com/someone/edit")
+        );
+        assert_eq!(source_id(""), None);
+    }
+}
@@ -0,0 +1,203 @@
+//! Getting tracks out of dj-cli and onto gig media or into DJ software.
+//!
+//! Library exporters share one snapshot of the history: `Library::gather`
+//! reads tags, sizes and durations once, and each format renders it.
+
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+
+use crate::analysis::key::MusicalKey;
+use crate::history::{self, HistoryEntry, HistoryStore};
+use crate::postprocess;
+use crate::tags;
+
+pub mod playlist;
+pub mod rekordbox;
+pub mod serato;
+pub mod traktor;
+pub mod usb;
+pub mod virtualdj;
+
+/// Everything an exporter needs to know about one track
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryTrack {
+    /// History id, used as the track id in exported libraries
+    pub id: u64,
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<i32>,
+    pub bpm: Option<f32>,
+    pub key: Option<MusicalKey>,
+    /// First downbeat in seconds, exported as a cue point
+    pub first_downbeat: Option<f32>,
+    /// Length in seconds
+    pub duration: Option<f64>,
+    /// File size in bytes
+    pub size: Option<u64>,
+    /// Unix seconds when it was downloaded
+    pub added: u64,
+    /// Where it came from
+    pub url: String,
+}
+
+/// A named, ordered list of tracks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playlist {
+    pub name: String,
+    /// History ids, in play order
+    pub tracks: Vec<u64>,
+}
+
+/// Tracks plus the playlists that group them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Library {
+    pub tracks: Vec<LibraryTrack>,
+    pub playlists: Vec<Playlist>,
+}
+
+impl Library {
+    /// Snapshot every history entry whose file still exists, with one playlist per session
+    /// and one per crate
+    pub async fn gather(store: &HistoryStore) -> Self {
+        let mut tracks = Vec::new();
+        for entry in &store.entries {
+            if entry.path.is_file() {
+                tracks.push(track(entry).await);
+            } else {
+                warn!(
+                    "Leaving missing file {} out of the library",
+                    entry.path.display()
+                );
+            }
+        }
+
+        let sessions = store
+            .sessions()
+            .into_iter()
+            .map(|(session, entries)| Playlist {
+                name: session_name(session),
+                tracks: entries.iter().map(|e| e.id).collect(),
+            });
+        let crates = store.crates.iter().map(|c| Playlist {
+            name: c.name.clone(),
+            tracks: c.tracks.clone(),
+        });
+        let playlists = sessions
+            .chain(crates)
+            .map(|mut playlist| {
+                playlist
+                    .tracks
+                    .retain(|id| tracks.iter().any(|t| t.id == *id));
+                playlist
+            })
+            .filter(|playlist| !playlist.tracks.is_empty())
+            .collect();
+
+        Self { tracks, playlists }
+    }
+}
+
+/// Playlist name for a session, e.g. "Session 2024-06-10 21:30"
+pub fn session_name(session: u64) -> String {
+    format!("Session {}", history::format_timestamp(session))
+}
+
+/// Read what the file itself knows and combine it with the history entry
+pub async fn track(entry: &HistoryEntry) -> LibraryTrack {
+    let tag_path = entry.path.clone();
+    let fields = tokio::task::spawn_blocking(move || tags::read_fields(&tag_path))
+        .await
+        .ok()
+        .and_then(|fields| fields.ok())
+        .unwrap_or_default();
+    let title = fields.title.unwrap_or_else(|| {
+        entry
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| entry.filename.clone())
+    });
+
+    LibraryTrack {
+        id: entry.id,
+        path: entry.path.clone(),
+        title,
+        artist: fields.artist,
+        album: fields.album,
+        genre: fields.genre,
+        year: fields.year,
+        bpm: entry.bpm,
+        key: entry.key,
+        first_downbeat: entry.first_downbeat,
+        duration: postprocess::probe_duration(&entry.path).await.ok(),
+        size: tokio::fs::metadata(&entry.path).await.ok().map(|m| m.len()),
+        added: entry.downloaded_at,
+        url: entry.url.clone(),
+    }
+}
+
+/// `file://` URL for a local path, percent-encoded the way DJ software expects
+pub fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://localhost");
+    if !path.starts_with('/') {
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    url
+}
+
+/// Split a path into the volume it lives on and the rest of it: "/Volumes/USB/a.mp3" gives
+/// `(Some("USB"), "a.mp3")`, "C:\Music\a.mp3" gives `(Some("C:"), "Music\a.mp3")`, and
+/// anything on the system disk of a Mac or Linux box gives `(None, "Users/dj/a.mp3")`
+pub fn split_volume(path: &Path) -> (Option<String>, PathBuf) {
+    if let Ok(rest) = path.strip_prefix("/Volumes") {
+        let mut components = rest.components();
+        if let Some(name) = components.next() {
+            let name = name.as_os_str().to_string_lossy().into_owned();
+            return (Some(name), components.as_path().to_path_buf());
+        }
+    }
+
+    let mut volume = None;
+    let mut components = path.components();
+    while let Some(component) = components.clone().next() {
+        match component {
+            Component::Prefix(prefix) => {
+                volume = Some(prefix.as_os_str().to_string_lossy().into_owned())
+            }
+            Component::RootDir => {}
+            _ => break,
+        }
+        components.next();
+    }
+    (volume, components.as_path().to_path_buf())
+}
+
+/// Escape text for an XML attribute value, dropping characters XML can't carry
+pub fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
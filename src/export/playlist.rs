@@ -0,0 +1,159 @@
+//! Playlist files: an extended M3U8 or PLS of a session (everything downloaded in
+//! one run of the app) or a crate, written next to the downloads.
+
+use color_eyre::{Result, eyre::eyre};
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+use super::{LibraryTrack, track};
+use crate::config::{PlaylistConfig, PlaylistFormat};
+use crate::history::HistoryEntry;
+use crate::naming;
+
+/// Extended M3U: `#EXTINF` lines carry each track's length and display title
+pub fn render_m3u8(tracks: &[LibraryTrack], dir: &Path, relative: bool) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for track in tracks {
+        let _ = writeln!(m3u, "#EXTINF:{},{}", seconds(track), display_title(track));
+        let _ = writeln!(m3u, "{}", entry_path(&track.path, dir, relative));
+    }
+    m3u
+}
+
+/// PLS version 2, numbered from 1
+pub fn render_pls(tracks: &[LibraryTrack], dir: &Path, relative: bool) -> String {
+    let mut pls = String::from("[playlist]\n");
+    for (i, track) in tracks.iter().enumerate() {
+        let n = i + 1;
+        let _ = writeln!(pls, "File{n}={}", entry_path(&track.path, dir, relative));
+        let _ = writeln!(pls, "Title{n}={}", display_title(track));
+        let _ = writeln!(pls, "Length{n}={}", seconds(track));
+    }
+    let _ = writeln!(pls, "NumberOfEntries={}", tracks.len());
+    pls.push_str("Version=2\n");
+    pls
+}
+
+/// Write the entries, in order, as the playlist `name` in `dir`; returns its path
+pub async fn write(
+    name: String,
+    entries: Vec<HistoryEntry>,
+    dir: PathBuf,
+    config: PlaylistConfig,
+) -> Result<PathBuf> {
+    let mut tracks = Vec::new();
+    for entry in &entries {
+        if entry.path.is_file() {
+            tracks.push(track(entry).await);
+        }
+    }
+    if tracks.is_empty() {
+        return Err(eyre!("No downloaded files in '{name}'"));
+    }
+
+    let (contents, ext) = match config.format {
+        PlaylistFormat::M3u8 => (render_m3u8(&tracks, &dir, config.relative_paths), "m3u8"),
+        PlaylistFormat::Pls => (render_pls(&tracks, &dir, config.relative_paths), "pls"),
+    };
+    tokio::fs::create_dir_all(&dir).await?;
+    let path = dir.join(format!("{}.{ext}", naming::sanitize(&name, 100)));
+    tokio::fs::write(&path, contents).await?;
+    info!(
+        "Wrote playlist '{}' with {} tracks to {}",
+        name,
+        tracks.len(),
+        path.display()
+    );
+    Ok(path)
+}
+
+/// Length in whole seconds, or -1 when unknown as both formats expect
+fn seconds(track: &LibraryTrack) -> i64 {
+    track.duration.map_or(-1, |d| d.round() as i64)
+}
+
+/// "Artist - Title", or just the title
+fn display_title(track: &LibraryTrack) -> String {
+    match &track.artist {
+        Some(artist) => format!("{artist} - {}", track.title),
+        None => track.title.clone(),
+    }
+}
+
+/// How the playlist refers to a track: relative to its own folder with forward
+/// slashes, or the full path
+fn entry_path(path: &Path, dir: &Path, relative: bool) -> String {
+    if !relative {
+        return path.to_string_lossy().into_owned();
+    }
+    relative_path(path, dir)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `path` relative to the directory `base`, climbing out with `..` where needed
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    // Different drives have nothing in common to climb back to
+    if common == 0 {
+        return path.iter().collect();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(path: &str, artist: Option<&str>, duration: Option<f64>) -> LibraryTrack {
+        LibraryTrack {
+            path: PathBuf::from(path),
+            title: "Title".to_string(),
+            artist: artist.map(String::from),
+            duration,
+            ..LibraryTrack::default()
+        }
+    }
+
+    #[test]
+    fn relative_paths_climb_out_of_the_playlist_folder() {
+        let base = Path::new("/Users/dj/Downloads");
+        assert_eq!(
+            relative_path(Path::new("/Users/dj/Downloads/House/a.mp3"), base),
+            PathBuf::from("House/a.mp3")
+        );
+        assert_eq!(
+            relative_path(Path::new("/Users/dj/Music/b.mp3"), base),
+            PathBuf::from("../Music/b.mp3")
+        );
+    }
+
+    #[test]
+    fn m3u8_and_pls_carry_lengths_and_titles() {
+        let tracks = [
+            track("/dl/House/a.mp3", Some("Artist"), Some(381.6)),
+            track("/dl/b.mp3", None, None),
+        ];
+        let dir = Path::new("/dl");
+        assert_eq!(
+            render_m3u8(&tracks, dir, true),
+            "#EXTM3U\n#EXTINF:382,Artist - Title\nHouse/a.mp3\n#EXTINF:-1,Title\nb.mp3\n"
+        );
+        assert_eq!(
+            render_pls(&tracks, dir, false),
+            "[playlist]\nFile1=/dl/House/a.mp3\nTitle1=Artist - Title\nLength1=382\n\
+             File2=/dl/b.mp3\nTitle2=Title\nLength2=-1\nNumberOfEntries=2\nVersion=2\n"
+        );
+    }
+}
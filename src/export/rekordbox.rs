@@ -0,0 +1,218 @@
+//! Rekordbox `DJ_PLAYLISTS` XML: File > Import Collection in Rekordbox picks up
+//! tracks with tags, BPM, key and a memory cue on the first downbeat, plus one
+//! playlist per dj-cli session under a "dj-cli" folder.
+
+use color_eyre::Result;
+use std::fmt::Write;
+use std::path::Path;
+use tracing::info;
+
+use super::{Library, LibraryTrack, file_url, xml_escape};
+use crate::history;
+
+/// Folder holding the exported playlists in Rekordbox's tree
+const FOLDER_NAME: &str = "dj-cli";
+
+/// Render the library as Rekordbox XML
+pub fn render(library: &Library) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<DJ_PLAYLISTS Version=\"1.0.0\">\n");
+    let _ = writeln!(
+        xml,
+        "  <PRODUCT Name=\"dj-cli\" Version=\"{}\" Company=\"dj-cli\"/>",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let _ = writeln!(xml, "  <COLLECTION Entries=\"{}\">", library.tracks.len());
+    for track in &library.tracks {
+        render_track(&mut xml, track);
+    }
+    xml.push_str("  </COLLECTION>\n");
+
+    xml.push_str("  <PLAYLISTS>\n");
+    xml.push_str("    <NODE Type=\"0\" Name=\"ROOT\" Count=\"1\">\n");
+    let _ = writeln!(
+        xml,
+        "      <NODE Type=\"0\" Name=\"{FOLDER_NAME}\" Count=\"{}\">",
+        library.playlists.len()
+    );
+    for playlist in &library.playlists {
+        let _ = writeln!(
+            xml,
+            "        <NODE Name=\"{}\" Type=\"1\" KeyType=\"0\" Entries=\"{}\">",
+            xml_escape(&playlist.name),
+            playlist.tracks.len()
+        );
+        for id in &playlist.tracks {
+            let _ = writeln!(xml, "          <TRACK Key=\"{id}\"/>");
+        }
+        xml.push_str("        </NODE>\n");
+    }
+    xml.push_str("      </NODE>\n");
+    xml.push_str("    </NODE>\n");
+    xml.push_str("  </PLAYLISTS>\n");
+    xml.push_str("</DJ_PLAYLISTS>\n");
+    xml
+}
+
+/// One `<TRACK>` in the collection, with its beat grid and cue
+fn render_track(xml: &mut String, track: &LibraryTrack) {
+    let text = |value: &Option<String>| xml_escape(value.as_deref().unwrap_or_default());
+    let _ = write!(
+        xml,
+        "    <TRACK TrackID=\"{}\" Name=\"{}\" Artist=\"{}\" Album=\"{}\" Genre=\"{}\" Kind=\"{}\" \
+         Size=\"{}\" TotalTime=\"{}\" Year=\"{}\" AverageBpm=\"{:.2}\" DateAdded=\"{}\" \
+         Comments=\"{}\" Location=\"{}\" Tonality=\"{}\"",
+        track.id,
+        xml_escape(&track.title),
+        text(&track.artist),
+        text(&track.album),
+        text(&track.genre),
+        kind(&track.path),
+        track.size.unwrap_or_default(),
+        track.duration.unwrap_or_default().round() as u64,
+        track.year.map(|y| y.to_string()).unwrap_or_default(),
+        track.bpm.unwrap_or_default(),
+        history::format_date(track.added),
+        xml_escape(&track.url),
+        xml_escape(&file_url(&track.path)),
+        track.key.map(|key| key.short()).unwrap_or_default(),
+    );
+
+    let Some(bpm) = track.bpm else {
+        xml.push_str("/>\n");
+        return;
+    };
+    xml.push_str(">\n");
+    // Anchor the grid on the first downbeat so bar 1 lines up
+    let start = track.first_downbeat.unwrap_or_default();
+    let _ = writeln!(
+        xml,
+        "      <TEMPO Inizio=\"{start:.3}\" Bpm=\"{bpm:.2}\" Metro=\"4/4\" Battito=\"1\"/>"
+    );
+    if let Some(cue) = track.first_downbeat {
+        let _ = writeln!(
+            xml,
+            "      <POSITION_MARK Name=\"First downbeat\" Type=\"0\" Start=\"{cue:.3}\" Num=\"-1\"/>"
+        );
+    }
+    xml.push_str("    </TRACK>\n");
+}
+
+/// Rekordbox's file kind label
+fn kind(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "m4a" | "aac" => "M4A File",
+        "wav" => "WAV File",
+        "aif" | "aiff" => "AIFF File",
+        "flac" => "FLAC File",
+        _ => "MP3 File",
+    }
+}
+
+/// Write the library to `path` as Rekordbox XML
+pub async fn write(path: &Path, library: &Library) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, render(library)).await?;
+    info!(
+        "Wrote Rekordbox XML with {} tracks to {}",
+        library.tracks.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::key::{Mode, MusicalKey};
+    use crate::export::Playlist;
+    use std::path::PathBuf;
+
+    fn sample_library() -> Library {
+        Library {
+            tracks: vec![
+                LibraryTrack {
+                    id: 3,
+                    path: PathBuf::from("/Users/dj/Music/House/Artist - Track (Extended Mix).mp3"),
+                    title: "Track (Extended Mix)".to_string(),
+                    artist: Some("Artist".to_string()),
+                    album: None,
+                    genre: Some("House".to_string()),
+                    year: Some(2024),
+                    bpm: Some(124.02),
+                    key: Some(MusicalKey {
+                        tonic: 9,
+                        mode: Mode::Minor,
+                    }),
+                    first_downbeat: Some(0.1234),
+                    duration: Some(381.6),
+                    size: Some(15_264_000),
+                    added: 1_718_000_000,
+                    url: "https://www.youtube.com/watch?v=abc&t=10s".to_string(),
+                },
+                LibraryTrack {
+                    id: 7,
+                    path: PathBuf::from("/Users/dj/Downloads/Café <Live>.m4a"),
+                    title: "Café \"Live\" & Loud".to_string(),
+                    added: 1_718_100_000,
+                    url: "https://youtu.be/xyz".to_string(),
+                    ..LibraryTrack::default()
+                },
+            ],
+            playlists: vec![
+                Playlist {
+                    name: "Session 2024-06-10 06:13".to_string(),
+                    tracks: vec![3],
+                },
+                Playlist {
+                    name: "Peak & Time".to_string(),
+                    tracks: vec![7, 3],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn matches_sample_xml() {
+        let expected =
+            include_str!("fixtures/rekordbox.xml").replace("{version}", env!("CARGO_PKG_VERSION"));
+        assert_eq!(render(&sample_library()), expected);
+    }
+
+    #[test]
+    fn empty_library_is_still_importable() {
+        let xml = render(&Library::default());
+        assert!(xml.contains("<COLLECTION Entries=\"0\">"));
+        assert!(xml.contains("Name=\"dj-cli\" Count=\"0\""));
+        assert!(xml.ends_with("</DJ_PLAYLISTS>\n"));
+    }
+
+    #[test]
+    fn locations_are_percent_encoded() {
+        assert_eq!(
+            file_url(Path::new("/Music/A & B/Ünïcode mix.mp3")),
+            "file://localhost/Music/A%20%26%20B/%C3%9Cn%C3%AFcode%20mix.mp3"
+        );
+        assert_eq!(
+            file_url(Path::new("C:\\Music\\track.mp3")),
+            "file://localhost/C:/Music/track.mp3"
+        );
+    }
+
+    #[test]
+    fn tonality_uses_short_notation() {
+        let key = |tonic, mode| MusicalKey { tonic, mode };
+        assert_eq!(key(9, Mode::Minor).short(), "Am");
+        assert_eq!(key(6, Mode::Major).short(), "F#");
+        assert_eq!(key(3, Mode::Minor).short(), "Ebm");
+    }
+}
@@ -0,0 +1,132 @@
+//! Serato crates: one `.crate` file per dj-cli session under `_Serato_/Subcrates`,
+//! nested in a "dj-cli" crate. Serato reads BPM and key from the files' own
+//! TBPM/TKEY tags, which the analysis stage already writes.
+//!
+//! A crate is a flat run of fields, each a four-letter tag, a big-endian length
+//! and a payload. Text is UTF-16BE; `o...` fields nest further fields.
+
+use color_eyre::{Result, eyre::eyre};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use super::{Library, split_volume};
+use crate::naming;
+
+/// Crate holding the session crates
+const PARENT_CRATE: &str = "dj-cli";
+/// Separator Serato uses between a parent crate and a subcrate in file names
+const SUBCRATE_SEPARATOR: &str = "%%";
+/// Columns shown when a crate is opened
+const COLUMNS: &[&str] = &["song", "artist", "bpm", "key", "length", "added"];
+
+/// Build the crate file for a list of track paths, relative to the volume root
+pub fn render(paths: &[String]) -> Vec<u8> {
+    let mut data = text_field(b"vrsn", "1.0/Serato ScratchLive Crate");
+    for column in COLUMNS {
+        let mut fields = text_field(b"tvcn", column);
+        fields.extend(text_field(b"tvcw", "0"));
+        data.extend(field(b"ovct", &fields));
+    }
+    for path in paths {
+        data.extend(field(b"otrk", &text_field(b"ptrk", path)));
+    }
+    data
+}
+
+/// Write one crate per playlist into `serato_dir` (the `_Serato_` folder). Only tracks on
+/// the same volume as that folder can go in its crates; returns the files written.
+pub async fn write(serato_dir: &Path, library: &Library) -> Result<Vec<PathBuf>> {
+    let subcrates = serato_dir.join("Subcrates");
+    tokio::fs::create_dir_all(&subcrates).await?;
+    let (volume, _) = split_volume(serato_dir);
+
+    let mut written = Vec::new();
+    let parent = subcrates.join(format!("{PARENT_CRATE}.crate"));
+    tokio::fs::write(&parent, render(&[])).await?;
+    written.push(parent);
+
+    for playlist in &library.playlists {
+        let paths: Vec<String> = playlist
+            .tracks
+            .iter()
+            .filter_map(|id| library.tracks.iter().find(|t| t.id == *id))
+            .filter_map(|track| {
+                let (track_volume, relative) = split_volume(&track.path);
+                if track_volume != volume {
+                    warn!(
+                        "{} is on another drive than {}, leaving it out of the crate",
+                        track.path.display(),
+                        serato_dir.display()
+                    );
+                    return None;
+                }
+                Some(
+                    relative
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                )
+            })
+            .collect();
+        if paths.is_empty() {
+            continue;
+        }
+
+        let name = naming::sanitize(&playlist.name, 100);
+        let path = subcrates.join(format!("{PARENT_CRATE}{SUBCRATE_SEPARATOR}{name}.crate"));
+        tokio::fs::write(&path, render(&paths)).await?;
+        written.push(path);
+    }
+
+    if written.len() == 1 {
+        return Err(eyre!(
+            "No tracks are on the same drive as {}",
+            serato_dir.display()
+        ));
+    }
+    info!(
+        "Wrote {} Serato crates to {}",
+        written.len(),
+        subcrates.display()
+    );
+    Ok(written)
+}
+
+/// A tagged field with a big-endian length prefix
+fn field(tag: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + payload.len());
+    data.extend_from_slice(tag);
+    data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    data.extend_from_slice(payload);
+    data
+}
+
+/// A field holding UTF-16BE text
+fn text_field(tag: &[u8; 4], text: &str) -> Vec<u8> {
+    let payload: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+    field(tag, &payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_entries_nest_utf16_paths() {
+        let data = render(&["Users/dj/Café.mp3".to_string()]);
+        let track = field(b"otrk", &text_field(b"ptrk", "Users/dj/Café.mp3"));
+        assert!(data.ends_with(&track));
+        // 8-byte header, then 17 UTF-16 code units
+        assert_eq!(&track[..8], b"otrk\0\0\0\x2a");
+        assert_eq!(&track[8..16], b"ptrk\0\0\0\x22");
+        assert_eq!(&track[16..20], &[0, b'U', 0, b's']);
+    }
+
+    #[test]
+    fn crates_start_with_the_version() {
+        let data = render(&[]);
+        assert_eq!(&data[..4], b"vrsn");
+        assert_eq!(u32::from_be_bytes(data[4..8].try_into().unwrap()), 56);
+    }
+}
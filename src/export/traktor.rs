@@ -0,0 +1,246 @@
+//! Traktor NML: a collection in the same format as Traktor's own `collection.nml`, with a
+//! playlist per dj-cli session. Traktor merges it in with File > Import Another Collection.
+
+use color_eyre::Result;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::path::Path;
+use tracing::info;
+
+use super::{Library, LibraryTrack, split_volume, xml_escape};
+use crate::analysis::key::{Mode, MusicalKey};
+use crate::history;
+
+/// Traktor names the boot volume after the disk; this is what nearly every Mac calls it
+const SYSTEM_VOLUME: &str = "Macintosh HD";
+
+/// Render the library as NML
+pub fn render(library: &Library) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\" ?>\n");
+    xml.push_str("<NML VERSION=\"19\">\n");
+    xml.push_str("  <HEAD COMPANY=\"www.native-instruments.com\" PROGRAM=\"Traktor\"/>\n");
+    xml.push_str("  <MUSICFOLDERS/>\n");
+
+    let _ = writeln!(xml, "  <COLLECTION ENTRIES=\"{}\">", library.tracks.len());
+    for track in &library.tracks {
+        render_entry(&mut xml, track);
+    }
+    xml.push_str("  </COLLECTION>\n");
+    xml.push_str("  <SETS ENTRIES=\"0\"/>\n");
+
+    xml.push_str("  <PLAYLISTS>\n");
+    xml.push_str("    <NODE TYPE=\"FOLDER\" NAME=\"$ROOT\">\n");
+    let _ = writeln!(
+        xml,
+        "      <SUBNODES COUNT=\"{}\">",
+        library.playlists.len()
+    );
+    for playlist in &library.playlists {
+        let tracks: Vec<&LibraryTrack> = playlist
+            .tracks
+            .iter()
+            .filter_map(|id| library.tracks.iter().find(|t| t.id == *id))
+            .collect();
+        let _ = writeln!(
+            xml,
+            "        <NODE TYPE=\"PLAYLIST\" NAME=\"{}\">",
+            xml_escape(&playlist.name)
+        );
+        let _ = writeln!(
+            xml,
+            "          <PLAYLIST ENTRIES=\"{}\" TYPE=\"LIST\" UUID=\"{}\">",
+            tracks.len(),
+            uuid(&playlist.name)
+        );
+        for track in tracks {
+            let _ = writeln!(
+                xml,
+                "            <ENTRY><PRIMARYKEY TYPE=\"TRACK\" KEY=\"{}\"/></ENTRY>",
+                xml_escape(&primary_key(&track.path))
+            );
+        }
+        xml.push_str("          </PLAYLIST>\n");
+        xml.push_str("        </NODE>\n");
+    }
+    xml.push_str("      </SUBNODES>\n");
+    xml.push_str("    </NODE>\n");
+    xml.push_str("  </PLAYLISTS>\n");
+    xml.push_str("</NML>\n");
+    xml
+}
+
+/// One collection `<ENTRY>` with its location, tags, tempo, key and cues
+fn render_entry(xml: &mut String, track: &LibraryTrack) {
+    let (volume, dir, file) = location(&track.path);
+    let _ = write!(xml, "    <ENTRY TITLE=\"{}\"", xml_escape(&track.title));
+    if let Some(artist) = &track.artist {
+        let _ = write!(xml, " ARTIST=\"{}\"", xml_escape(artist));
+    }
+    xml.push_str(">\n");
+    let _ = writeln!(
+        xml,
+        "      <LOCATION DIR=\"{}\" FILE=\"{}\" VOLUME=\"{}\" VOLUMEID=\"\"/>",
+        xml_escape(&dir),
+        xml_escape(&file),
+        xml_escape(&volume)
+    );
+    if let Some(album) = &track.album {
+        let _ = writeln!(xml, "      <ALBUM TITLE=\"{}\"/>", xml_escape(album));
+    }
+
+    let _ = write!(xml, "      <INFO");
+    if let Some(genre) = &track.genre {
+        let _ = write!(xml, " GENRE=\"{}\"", xml_escape(genre));
+    }
+    let _ = write!(xml, " COMMENT=\"{}\"", xml_escape(&track.url));
+    if let Some(key) = track.key {
+        let _ = write!(xml, " KEY=\"{}\"", key.short());
+    }
+    if let Some(duration) = track.duration {
+        let _ = write!(
+            xml,
+            " PLAYTIME=\"{}\" PLAYTIME_FLOAT=\"{duration:.6}\"",
+            duration.round() as u64
+        );
+    }
+    let _ = write!(xml, " IMPORT_DATE=\"{}\"", nml_date(track.added));
+    if let Some(year) = track.year {
+        let _ = write!(xml, " RELEASE_DATE=\"{year}/1/1\"");
+    }
+    if let Some(size) = track.size {
+        // Kilobytes
+        let _ = write!(xml, " FILESIZE=\"{}\"", size / 1024);
+    }
+    xml.push_str("/>\n");
+
+    if let Some(bpm) = track.bpm {
+        let _ = writeln!(
+            xml,
+            "      <TEMPO BPM=\"{bpm:.6}\" BPM_QUALITY=\"100.000000\"/>"
+        );
+    }
+    if let Some(key) = track.key {
+        let _ = writeln!(xml, "      <MUSICAL_KEY VALUE=\"{}\"/>", key_value(key));
+    }
+    if let Some(downbeat) = track.first_downbeat {
+        // Cue positions are in milliseconds; type 4 anchors the beat grid, type 0 is a plain cue
+        let start = f64::from(downbeat) * 1000.0;
+        let _ = writeln!(
+            xml,
+            "      <CUE_V2 NAME=\"AutoGrid\" DISPL_ORDER=\"0\" TYPE=\"4\" START=\"{start:.6}\" LEN=\"0.000000\" REPEATS=\"-1\" HOTCUE=\"-1\"/>"
+        );
+        let _ = writeln!(
+            xml,
+            "      <CUE_V2 NAME=\"First downbeat\" DISPL_ORDER=\"0\" TYPE=\"0\" START=\"{start:.6}\" LEN=\"0.000000\" REPEATS=\"-1\" HOTCUE=\"0\"/>"
+        );
+    }
+    xml.push_str("    </ENTRY>\n");
+}
+
+/// Traktor's split of a path into volume, `/:`-separated directory and file name
+fn location(path: &Path) -> (String, String, String) {
+    let (volume, relative) = split_volume(path);
+    let file = relative
+        .file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut dir = String::from("/:");
+    for component in relative.parent().into_iter().flat_map(Path::components) {
+        dir.push_str(&component.as_os_str().to_string_lossy());
+        dir.push_str("/:");
+    }
+    (
+        volume.unwrap_or_else(|| SYSTEM_VOLUME.to_string()),
+        dir,
+        file,
+    )
+}
+
+/// The key playlists use to refer to a collection entry
+fn primary_key(path: &Path) -> String {
+    let (volume, dir, file) = location(path);
+    format!("{volume}{dir}{file}")
+}
+
+/// Traktor's key number: 0-11 for C to B major, 12-23 for C to B minor
+fn key_value(key: MusicalKey) -> u8 {
+    match key.mode {
+        Mode::Major => key.tonic,
+        Mode::Minor => key.tonic + 12,
+    }
+}
+
+/// Date as Traktor writes it, e.g. "2024/6/10"
+fn nml_date(secs: u64) -> String {
+    let (year, month, day) = history::civil_date(secs);
+    format!("{year}/{month}/{day}")
+}
+
+/// A stable playlist id, so re-importing updates the playlist instead of duplicating it
+fn uuid(name: &str) -> String {
+    Sha256::digest(format!("dj-cli playlist {name}").as_bytes())[..16]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Write the library to `path` as NML
+pub async fn write(path: &Path, library: &Library) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, render(library)).await?;
+    info!(
+        "Wrote Traktor NML with {} tracks to {}",
+        library.tracks.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn splits_paths_the_way_traktor_does() {
+        let (volume, dir, file) = location(Path::new("/Volumes/USB/Music/House/Track.mp3"));
+        assert_eq!(volume, "USB");
+        assert_eq!(dir, "/:Music/:House/:");
+        assert_eq!(file, "Track.mp3");
+
+        // Files on the boot disk get the system volume name
+        let (volume, dir, _) = location(Path::new("/Users/dj/Downloads/Track.mp3"));
+        assert_eq!(volume, SYSTEM_VOLUME);
+        assert_eq!(dir, "/:Users/:dj/:Downloads/:");
+
+        // A file at the root of a volume has an empty directory
+        let (_, dir, file) = location(Path::new("/Volumes/USB/Track.mp3"));
+        assert_eq!(dir, "/:");
+        assert_eq!(file, "Track.mp3");
+    }
+
+    #[test]
+    fn primary_key_joins_volume_dir_and_file() {
+        assert_eq!(
+            primary_key(&PathBuf::from("/Volumes/USB/Music/Track.mp3")),
+            "USB/:Music/:Track.mp3"
+        );
+        assert_eq!(
+            primary_key(&PathBuf::from("/Users/dj/Track.mp3")),
+            "Macintosh HD/:Users/:dj/:Track.mp3"
+        );
+    }
+
+    #[test]
+    fn key_numbers() {
+        let key = |tonic, mode| key_value(MusicalKey { tonic, mode });
+        assert_eq!(key(0, Mode::Major), 0);
+        assert_eq!(key(11, Mode::Major), 11);
+        assert_eq!(key(0, Mode::Minor), 12);
+        // A minor
+        assert_eq!(key(9, Mode::Minor), 21);
+    }
+}
@@ -0,0 +1,429 @@
+//! Copy tracks to a FAT32/exFAT USB stick for CDJs and controllers.
+//!
+//! Everything that needs converting is transcoded to a temp folder first, so
+//! the free-space check knows the exact size of what's about to be written.
+//! Each copy is read back and compared by SHA-256 before it counts as done.
+
+use color_eyre::{Result, eyre::eyre};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tracing::{info, warn};
+
+use crate::config::ExportConfig;
+use crate::history::HistoryEntry;
+use crate::naming;
+use crate::postprocess;
+use crate::shutdown;
+
+/// Longest path below the mount point we'll create; older players choke on anything longer
+const MAX_PATH_CHARS: usize = 240;
+
+/// Room kept for the ` (n)` that tells apart tracks with the same name
+const SUFFIX_CHARS: usize = 7;
+
+/// Per-run choices on top of the config
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Convert everything to this format, whether the player could read it or not
+    pub format: Option<String>,
+    /// Bitrate for lossy transcodes, in kbps
+    pub bitrate: u32,
+}
+
+/// What an export did
+#[derive(Debug, Clone, Default)]
+pub struct ExportSummary {
+    /// Files written to the stick
+    pub copied: Vec<PathBuf>,
+    /// How many of those were transcoded first
+    pub transcoded: usize,
+    /// Files already on the stick
+    pub skipped: Vec<PathBuf>,
+    /// History entries whose file has gone missing
+    pub missing: Vec<String>,
+    /// Bytes written
+    pub bytes: u64,
+}
+
+/// One file queued for copying
+struct Planned {
+    source: PathBuf,
+    target: PathBuf,
+    /// `source` is a transcode in the temp folder, to be removed afterwards
+    temporary: bool,
+}
+
+/// Copy `entries` into the export folder on the stick mounted at `mount`
+pub async fn export(
+    mount: &Path,
+    entries: &[HistoryEntry],
+    config: &ExportConfig,
+    options: &ExportOptions,
+) -> Result<ExportSummary> {
+    if !mount.is_dir() {
+        return Err(eyre!("{} is not a mounted folder", mount.display()));
+    }
+
+    let folder = naming::sanitize(&config.folder, 64);
+    let dest = mount.join(&folder);
+    tokio::fs::create_dir_all(&dest).await?;
+
+    let mut summary = ExportSummary::default();
+    let mut plan = Vec::new();
+    let result = plan_copies(
+        entries,
+        &dest,
+        &folder,
+        config,
+        options,
+        &mut plan,
+        &mut summary,
+    )
+    .await;
+    let result = match result {
+        Ok(()) => copy_all(&plan, &dest, &mut summary).await,
+        Err(e) => Err(e),
+    };
+
+    // Transcodes are only stepping stones - never leave them behind
+    for item in plan.iter().filter(|item| item.temporary) {
+        let _ = tokio::fs::remove_file(&item.source).await;
+    }
+
+    result.map(|()| summary)
+}
+
+/// Work out the target for every entry, transcoding where needed and skipping what's already there.
+/// FAT and exFAT ignore case, so names are compared ignoring case against both the stick and this run.
+async fn plan_copies(
+    entries: &[HistoryEntry],
+    dest: &Path,
+    folder: &str,
+    config: &ExportConfig,
+    options: &ExportOptions,
+    plan: &mut Vec<Planned>,
+    summary: &mut ExportSummary,
+) -> Result<()> {
+    let on_stick: HashMap<String, PathBuf> = std::fs::read_dir(dest)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            (
+                entry.file_name().to_string_lossy().to_lowercase(),
+                entry.path(),
+            )
+        })
+        .collect();
+    let mut claimed = HashSet::new();
+
+    for entry in entries {
+        if !entry.path.is_file() {
+            warn!("Skipping missing file {}", entry.path.display());
+            summary.missing.push(entry.filename.clone());
+            continue;
+        }
+
+        let ext = extension(&entry.path);
+        let format = match &options.format {
+            Some(format) => format.to_ascii_lowercase(),
+            None if config.formats.iter().any(|f| f.eq_ignore_ascii_case(&ext)) => ext.clone(),
+            None => config.transcode_to.to_ascii_lowercase(),
+        };
+
+        // Keep the whole path under the player's limit, suffix included
+        let stem = entry
+            .path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(&entry.filename);
+        let room =
+            MAX_PATH_CHARS.saturating_sub(folder.chars().count() + format.len() + 2 + SUFFIX_CHARS);
+        let name = naming::sanitize(stem, room);
+
+        let (source, temporary) = if format == ext {
+            (entry.path.clone(), false)
+        } else {
+            let tmp = std::env::temp_dir().join(format!("dj-cli-export-{}.{format}", entry.id));
+            transcode(&entry.path, &tmp, options.bitrate).await?;
+            (tmp, true)
+        };
+
+        // The same track from an earlier export is left alone; a different one
+        // with the same name (re-tagged, say) gets its own name
+        let file_name = format!("{name}.{format}").to_lowercase();
+        if !claimed.contains(&file_name)
+            && let Some(existing) = on_stick.get(&file_name)
+            && same_file(&source, existing).await?
+        {
+            if temporary {
+                let _ = tokio::fs::remove_file(&source).await;
+            }
+            claimed.insert(file_name);
+            summary.skipped.push(existing.clone());
+            continue;
+        }
+
+        let target = (1..)
+            .map(|n| match n {
+                1 => format!("{name}.{format}"),
+                n => format!("{name} ({n}).{format}"),
+            })
+            .find(|candidate| {
+                let key = candidate.to_lowercase();
+                !claimed.contains(&key) && !on_stick.contains_key(&key)
+            })
+            .expect("an unbounded range always has a free name");
+        claimed.insert(target.to_lowercase());
+
+        if temporary {
+            summary.transcoded += 1;
+        }
+        plan.push(Planned {
+            source,
+            target: dest.join(target),
+            temporary,
+        });
+    }
+    Ok(())
+}
+
+/// Check there's room, then copy and verify every planned file
+async fn copy_all(plan: &[Planned], dest: &Path, summary: &mut ExportSummary) -> Result<()> {
+    let mut needed = 0;
+    for item in plan {
+        needed += tokio::fs::metadata(&item.source).await?.len();
+    }
+    let available = fs4::available_space(dest)?;
+    if needed > available {
+        return Err(eyre!(
+            "Not enough space on the stick: need {}, {} free",
+            format_size(needed),
+            format_size(available)
+        ));
+    }
+
+    for item in plan {
+        tokio::fs::copy(&item.source, &item.target).await?;
+        if !same_file(&item.source, &item.target).await? {
+            let _ = tokio::fs::remove_file(&item.target).await;
+            return Err(eyre!(
+                "Verification failed for {} - the stick may be faulty",
+                item.target.display()
+            ));
+        }
+        info!("Exported {}", item.target.display());
+        summary.bytes += tokio::fs::metadata(&item.target).await?.len();
+        summary.copied.push(item.target.clone());
+    }
+    Ok(())
+}
+
+/// Convert `source` to the format given by `out`'s extension
+async fn transcode(source: &Path, out: &Path, bitrate: u32) -> Result<()> {
+    let ext = extension(out);
+    let partial = shutdown::Partial::new(out);
+    let mut cmd = shutdown::command("ffmpeg");
+    cmd.args(["-v", "error", "-nostdin", "-y", "-i"])
+        .arg(source)
+        .args(["-map", "0:a:0", "-map_metadata", "0"]);
+    // Only these containers carry cover art; WAV has nowhere to put it
+    if matches!(ext.as_str(), "mp3" | "m4a") {
+        cmd.args(["-map", "0:v?", "-c:v", "copy"]);
+    }
+    // Older CDJs only read ID3v2.3
+    if ext == "mp3" {
+        cmd.args(["-id3v2_version", "3"]);
+    }
+    cmd.args(postprocess::encoder_args(out, bitrate)).arg(out);
+
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let status = cmd
+        .status()
+        .await
+        .map_err(|_| eyre!("ffmpeg not found. Please install: brew install ffmpeg"))?;
+
+    if !status.success() {
+        return Err(eyre!(
+            "ffmpeg could not convert {} to {}",
+            source.display(),
+            ext
+        ));
+    }
+    partial.finish();
+    info!("Transcoded {} to {}", source.display(), out.display());
+    Ok(())
+}
+
+/// Whether two files have identical contents (sizes first, then SHA-256)
+async fn same_file(a: &Path, b: &Path) -> Result<bool> {
+    if tokio::fs::metadata(a).await?.len() != tokio::fs::metadata(b).await?.len() {
+        return Ok(false);
+    }
+    let (a, b) = (a.to_path_buf(), b.to_path_buf());
+    tokio::task::spawn_blocking(move || Ok(sha256(&a)? == sha256(&b)?)).await?
+}
+
+/// SHA-256 of a file's contents
+fn sha256(path: &Path) -> Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 16];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Lower-case file extension
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Human-readable byte count, e.g. "1.4 GB"
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MB", b as f64 / (1u64 << 20) as f64),
+        b => format!("{:.0} KB", b as f64 / 1024.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: u64, path: PathBuf) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            filename: path.file_name().unwrap().to_string_lossy().into_owned(),
+            path,
+            url: String::new(),
+            downloaded_at: 0,
+            bpm: None,
+            key: None,
+            first_downbeat: None,
+            loudness: None,
+            trim: None,
+            quality: None,
+            parent: None,
+            clip: None,
+            session: None,
+            fingerprint: None,
+            tags: None,
+            duplicate_of: None,
+            analyzing: false,
+        }
+    }
+
+    /// Write `contents` to `dir/name`, returning the path
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    async fn plan(entries: &[HistoryEntry], dest: &Path) -> (Vec<Planned>, ExportSummary) {
+        let options = ExportOptions {
+            format: None,
+            bitrate: 320,
+        };
+        let mut plan = Vec::new();
+        let mut summary = ExportSummary::default();
+        plan_copies(
+            entries,
+            dest,
+            "DJ-CLI",
+            &ExportConfig::default(),
+            &options,
+            &mut plan,
+            &mut summary,
+        )
+        .await
+        .unwrap();
+        (plan, summary)
+    }
+
+    fn target_names(plan: &[Planned]) -> Vec<String> {
+        plan.iter()
+            .map(|item| {
+                item.target
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn same_names_in_one_export_get_their_own_files() {
+        let root = std::env::temp_dir().join(format!("dj-cli-usb-run-{}", std::process::id()));
+        let dest = root.join("stick");
+        std::fs::create_dir_all(&dest).unwrap();
+        let entries = [
+            entry(1, write(&root.join("a"), "Track.mp3", "first")),
+            entry(2, write(&root.join("b"), "track.mp3", "second")),
+            entry(3, write(&root.join("c"), "TRACK.mp3", "third")),
+        ];
+
+        let (plan, summary) = plan(&entries, &dest).await;
+        assert_eq!(
+            target_names(&plan),
+            ["Track.mp3", "track (2).mp3", "TRACK (3).mp3"]
+        );
+        assert!(summary.skipped.is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn names_on_the_stick_are_matched_ignoring_case() {
+        let root = std::env::temp_dir().join(format!("dj-cli-usb-stick-{}", std::process::id()));
+        let dest = root.join("stick");
+        write(&dest, "TRACK.MP3", "already there");
+        write(&dest, "Other.mp3", "old version");
+        let entries = [
+            entry(1, write(&root.join("a"), "Track.mp3", "already there")),
+            entry(2, write(&root.join("b"), "Track.mp3", "something else")),
+            entry(3, write(&root.join("a"), "Other.mp3", "re-tagged")),
+            entry(4, root.join("a").join("Gone.mp3")),
+        ];
+
+        let (plan, summary) = plan(&entries, &dest).await;
+        assert_eq!(summary.skipped, [dest.join("TRACK.MP3")]);
+        assert_eq!(target_names(&plan), ["Track (2).mp3", "Other (2).mp3"]);
+        assert_eq!(summary.missing, ["Gone.mp3"]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn long_names_leave_room_for_a_suffix() {
+        let root = std::env::temp_dir().join(format!("dj-cli-usb-long-{}", std::process::id()));
+        let dest = root.join("stick");
+        std::fs::create_dir_all(&dest).unwrap();
+        let stem = "x".repeat(240);
+        let entries = [
+            entry(1, write(&root.join("a"), &format!("{stem}.mp3"), "first")),
+            entry(2, write(&root.join("b"), &format!("{stem}.mp3"), "second")),
+        ];
+
+        let (plan, _) = plan(&entries, &dest).await;
+        for name in target_names(&plan) {
+            assert!("DJ-CLI/".len() + name.chars().count() <= MAX_PATH_CHARS);
+        }
+        assert!(target_names(&plan)[1].ends_with(" (2).mp3"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! VirtualDJ: a `database.xml` with tags, BPM, key and cues, plus a `.vdjfolder` per dj-cli
+//! session under `Folders`. Point it at a VirtualDJ folder of its own (e.g. the one on a USB
+//! drive, which VirtualDJ reads on its own) - we never overwrite a database we didn't write.
+
+use color_eyre::{Result, eyre::eyre};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use super::{Library, LibraryTrack, xml_escape};
+use crate::naming;
+
+/// Comment marking databases we wrote, and so may replace
+const MARKER: &str = "<!-- Written by dj-cli -->";
+
+/// Render the library as a VirtualDJ 8 database
+pub fn render_database(library: &Library) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(MARKER);
+    xml.push('\n');
+    xml.push_str("<VirtualDJ_Database Version=\"8.2\">\n");
+    for track in &library.tracks {
+        render_song(&mut xml, track);
+    }
+    xml.push_str("</VirtualDJ_Database>\n");
+    xml
+}
+
+/// One `<Song>` with its tags, scan results and cues
+fn render_song(xml: &mut String, track: &LibraryTrack) {
+    let _ = write!(
+        xml,
+        " <Song FilePath=\"{}\"",
+        xml_escape(&track.path.to_string_lossy())
+    );
+    if let Some(size) = track.size {
+        let _ = write!(xml, " FileSize=\"{size}\"");
+    }
+    xml.push_str(">\n");
+
+    let _ = write!(xml, "  <Tags Title=\"{}\"", xml_escape(&track.title));
+    let optional = [
+        ("Author", &track.artist),
+        ("Album", &track.album),
+        ("Genre", &track.genre),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            let _ = write!(xml, " {name}=\"{}\"", xml_escape(value));
+        }
+    }
+    if let Some(year) = track.year {
+        let _ = write!(xml, " Year=\"{year}\"");
+    }
+    xml.push_str(" />\n");
+
+    let _ = write!(xml, "  <Infos FirstSeen=\"{}\"", track.added);
+    if let Some(duration) = track.duration {
+        let _ = write!(xml, " SongLength=\"{duration:.6}\"");
+    }
+    xml.push_str(" />\n");
+    let _ = writeln!(xml, "  <Comment>{}</Comment>", xml_escape(&track.url));
+
+    if track.bpm.is_some() || track.key.is_some() {
+        xml.push_str("  <Scan Version=\"801\"");
+        if let Some(bpm) = track.bpm.filter(|bpm| *bpm > 0.0) {
+            // VirtualDJ stores the length of a beat in seconds, not beats per minute
+            let _ = write!(xml, " Bpm=\"{:.6}\"", 60.0 / f64::from(bpm));
+        }
+        if let Some(key) = track.key {
+            let _ = write!(xml, " Key=\"{}\"", key.short());
+        }
+        xml.push_str(" />\n");
+    }
+    if let Some(downbeat) = track.first_downbeat {
+        let _ = writeln!(xml, "  <Poi Pos=\"{downbeat:.6}\" Type=\"beatgrid\" />");
+        let _ = writeln!(
+            xml,
+            "  <Poi Name=\"First downbeat\" Pos=\"{downbeat:.6}\" Num=\"1\" />"
+        );
+    }
+    xml.push_str(" </Song>\n");
+}
+
+/// Render a virtual folder listing `tracks` in order
+pub fn render_folder(tracks: &[&LibraryTrack]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<VirtualFolder>\n");
+    for (idx, track) in tracks.iter().enumerate() {
+        let _ = write!(
+            xml,
+            " <song path=\"{}\" title=\"{}\"",
+            xml_escape(&track.path.to_string_lossy()),
+            xml_escape(&track.title)
+        );
+        if let Some(artist) = &track.artist {
+            let _ = write!(xml, " artist=\"{}\"", xml_escape(artist));
+        }
+        if let Some(duration) = track.duration {
+            let _ = write!(xml, " songlength=\"{duration:.6}\"");
+        }
+        if let Some(bpm) = track.bpm {
+            let _ = write!(xml, " bpm=\"{bpm:.2}\"");
+        }
+        if let Some(key) = track.key {
+            let _ = write!(xml, " key=\"{}\"", key.short());
+        }
+        let _ = writeln!(xml, " idx=\"{idx}\" />");
+    }
+    xml.push_str("</VirtualFolder>\n");
+    xml
+}
+
+/// Write `database.xml` and one `.vdjfolder` per playlist into `dir`; returns the files written
+pub async fn write(dir: &Path, library: &Library) -> Result<Vec<PathBuf>> {
+    let database = dir.join("database.xml");
+    if let Ok(existing) = tokio::fs::read_to_string(&database).await
+        && !existing.contains(MARKER)
+    {
+        return Err(eyre!(
+            "{} belongs to VirtualDJ - export to an empty folder instead",
+            database.display()
+        ));
+    }
+
+    let folders = dir.join("Folders");
+    tokio::fs::create_dir_all(&folders).await?;
+    tokio::fs::write(&database, render_database(library)).await?;
+    let mut written = vec![database];
+
+    for playlist in &library.playlists {
+        let tracks: Vec<&LibraryTrack> = playlist
+            .tracks
+            .iter()
+            .filter_map(|id| library.tracks.iter().find(|t| t.id == *id))
+            .collect();
+        let name = naming::sanitize(&playlist.name, 100);
+        let path = folders.join(format!("{name}.vdjfolder"));
+        tokio::fs::write(&path, render_folder(&tracks)).await?;
+        written.push(path);
+    }
+
+    info!(
+        "Wrote VirtualDJ database with {} tracks to {}",
+        library.tracks.len(),
+        dir.display()
+    );
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn stores_bpm_as_beat_length() {
+        let track = LibraryTrack {
+            path: PathBuf::from("/Music/Track.mp3"),
+            title: "Track".to_string(),
+            bpm: Some(120.0),
+            ..LibraryTrack::default()
+        };
+        let mut xml = String::new();
+        render_song(&mut xml, &track);
+        assert!(xml.contains("Bpm=\"0.500000\""), "{xml}");
+
+        // A failed detection shouldn't turn into an infinite beat
+        let unknown = LibraryTrack {
+            bpm: Some(0.0),
+            ..track
+        };
+        let mut xml = String::new();
+        render_song(&mut xml, &unknown);
+        assert!(xml.contains("<Scan Version=\"801\" />"), "{xml}");
+    }
+
+    #[tokio::test]
+    async fn never_overwrites_a_database_it_did_not_write() {
+        let dir = std::env::temp_dir().join(format!("dj-cli-virtualdj-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let database = dir.join("database.xml");
+        let library = Library::default();
+
+        // Ours: replaced freely
+        write(&dir, &library).await.unwrap();
+        write(&dir, &library).await.unwrap();
+
+        let theirs = "<?xml version=\"1.0\"?>\n<VirtualDJ_Database Version=\"8.2\">\n";
+        std::fs::write(&database, theirs).unwrap();
+        assert!(write(&dir, &library).await.is_err());
+        assert_eq!(std::fs::read_to_string(&database).unwrap(), theirs);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
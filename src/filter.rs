@@ -0,0 +1,373 @@
+//! Search queries over downloads and library files.
+//!
+//! A query is a mix of fuzzy terms and structured filters:
+//! `daft punk bpm:120-128 key:8A format:aiff crate:"peak time"`. Every part has
+//! to match; fuzzy terms may match any text field and rank the results.
+
+use crate::analysis::key::MusicalKey;
+use crate::history::{Crate, HistoryEntry};
+use crate::library::LibraryFile;
+use crate::tracklist;
+
+/// Everything a query can look at for one track
+#[derive(Debug, Clone, Default)]
+pub struct Candidate {
+    pub artist: Option<String>,
+    pub title: String,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    /// Comment tags; only searched with `comment:`, as they're often whole video descriptions
+    pub comment: Option<String>,
+    pub url: Option<String>,
+    /// Names of the crates it's in
+    pub crates: Vec<String>,
+    pub bpm: Option<f32>,
+    pub key: Option<MusicalKey>,
+    /// Key as tagged, for keys that don't parse
+    pub key_text: Option<String>,
+    /// File extension or container, e.g. "mp3"
+    pub format: Option<String>,
+}
+
+impl Candidate {
+    /// A download, with artist and title taken from its "Artist - Title" file name
+    pub fn from_entry(entry: &HistoryEntry, crates: &[Crate]) -> Self {
+        let stem = entry
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| entry.filename.clone());
+        let (artist, title) = tracklist::parse_artist_title(&stem);
+        let tags = entry.tags.clone().unwrap_or_default();
+        Self {
+            artist,
+            title,
+            album: tags.album,
+            genre: tags.genre,
+            comment: tags.comment,
+            url: Some(entry.url.clone()),
+            crates: crates
+                .iter()
+                .filter(|c| c.tracks.contains(&entry.id))
+                .map(|c| c.name.clone())
+                .collect(),
+            bpm: entry.bpm,
+            key: entry.key,
+            format: extension(&entry.path),
+            ..Self::default()
+        }
+    }
+
+    /// A file found by the library scanner
+    pub fn from_file(file: &LibraryFile) -> Self {
+        Self {
+            artist: file.artist.clone(),
+            title: file.display_title(),
+            album: file.album.clone(),
+            genre: file.genre.clone(),
+            bpm: file.bpm,
+            key: file.key.as_deref().and_then(MusicalKey::parse),
+            key_text: file.key.clone(),
+            format: extension(&file.path),
+            ..Self::default()
+        }
+    }
+}
+
+/// Lower-case file extension
+fn extension(path: &std::path::Path) -> Option<String> {
+    path.extension().map(|e| e.to_string_lossy().to_lowercase())
+}
+
+/// A text field a `name:value` filter can be limited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Artist,
+    Title,
+    Album,
+    Genre,
+    Comment,
+    Url,
+    Crate,
+}
+
+/// A parsed search query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    /// Free terms, each matched fuzzily against every text field
+    terms: Vec<String>,
+    /// Fuzzy terms limited to one field
+    fields: Vec<(Field, String)>,
+    /// Inclusive tempo range
+    bpm: Option<(f32, f32)>,
+    key: Option<String>,
+    format: Option<String>,
+}
+
+impl Query {
+    /// Parse a query; anything that isn't a known `name:value` filter is a free term
+    pub fn parse(input: &str) -> Self {
+        let mut query = Self::default();
+        for token in tokenize(input) {
+            let Some((name, value)) = token.split_once(':').filter(|(_, v)| !v.is_empty()) else {
+                query.terms.push(token.to_lowercase());
+                continue;
+            };
+            let value = value.to_lowercase();
+            match name.to_lowercase().as_str() {
+                "bpm" => match parse_bpm(&value) {
+                    Some(range) => query.bpm = Some(range),
+                    None => query.terms.push(token.to_lowercase()),
+                },
+                "key" => query.key = Some(value),
+                "format" | "ext" => query.format = Some(value),
+                "artist" => query.fields.push((Field::Artist, value)),
+                "title" => query.fields.push((Field::Title, value)),
+                "album" => query.fields.push((Field::Album, value)),
+                "genre" => query.fields.push((Field::Genre, value)),
+                "comment" => query.fields.push((Field::Comment, value)),
+                "url" | "source" => query.fields.push((Field::Url, value)),
+                "crate" => query.fields.push((Field::Crate, value)),
+                // "https://..." and the like are just text
+                _ => query.terms.push(token.to_lowercase()),
+            }
+        }
+        query
+    }
+
+    /// Whether the query lets everything through
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// How well `candidate` matches, higher being better; `None` when it doesn't
+    pub fn score(&self, candidate: &Candidate) -> Option<u32> {
+        if let Some((low, high)) = self.bpm {
+            let bpm = candidate.bpm?;
+            if bpm < low || bpm > high {
+                return None;
+            }
+        }
+        if let Some(key) = &self.key
+            && !key_matches(key, candidate)
+        {
+            return None;
+        }
+        if let Some(format) = &self.format {
+            let actual = candidate.format.as_deref()?.to_lowercase();
+            if normalize_format(&actual) != normalize_format(format) {
+                return None;
+            }
+        }
+
+        let mut score = 0;
+        for (field, term) in &self.fields {
+            score += field_values(candidate, *field)
+                .filter_map(|value| fuzzy_score(term, value))
+                .max()?;
+        }
+        for term in &self.terms {
+            score += Field::ALL
+                .iter()
+                .flat_map(|field| field_values(candidate, *field))
+                .filter_map(|value| fuzzy_score(term, value))
+                .max()?;
+        }
+        Some(score)
+    }
+}
+
+impl Field {
+    /// Fields free terms are matched against
+    const ALL: [Field; 6] = [
+        Field::Artist,
+        Field::Title,
+        Field::Album,
+        Field::Genre,
+        Field::Url,
+        Field::Crate,
+    ];
+}
+
+/// The text a field holds; crates can give several values
+fn field_values(candidate: &Candidate, field: Field) -> Box<dyn Iterator<Item = &str> + '_> {
+    match field {
+        Field::Artist => Box::new(candidate.artist.as_deref().into_iter()),
+        Field::Title => Box::new(std::iter::once(candidate.title.as_str())),
+        Field::Album => Box::new(candidate.album.as_deref().into_iter()),
+        Field::Genre => Box::new(candidate.genre.as_deref().into_iter()),
+        Field::Comment => Box::new(candidate.comment.as_deref().into_iter()),
+        Field::Url => Box::new(candidate.url.as_deref().into_iter()),
+        Field::Crate => Box::new(candidate.crates.iter().map(String::as_str)),
+    }
+}
+
+/// Split on whitespace, keeping `"quoted phrases"` (and `name:"quoted values"`) together
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// "120-128", "124" (within half a BPM) or "120+"
+fn parse_bpm(value: &str) -> Option<(f32, f32)> {
+    if let Some(low) = value.strip_suffix('+') {
+        return Some((low.parse().ok()?, f32::INFINITY));
+    }
+    if let Some((low, high)) = value.split_once('-') {
+        let (low, high): (f32, f32) = (low.parse().ok()?, high.parse().ok()?);
+        return Some((low.min(high), low.max(high)));
+    }
+    let bpm: f32 = value.parse().ok()?;
+    Some((bpm - 0.5, bpm + 0.5))
+}
+
+/// Compare keys in any notation; fall back to the tag text when either side doesn't parse
+fn key_matches(query: &str, candidate: &Candidate) -> bool {
+    match (MusicalKey::parse(query), candidate.key) {
+        (Some(wanted), Some(actual)) => wanted == actual,
+        _ => candidate
+            .key_text
+            .as_deref()
+            .is_some_and(|text| text.eq_ignore_ascii_case(query)),
+    }
+}
+
+/// Treat the AIFF spellings as one format
+fn normalize_format(format: &str) -> &str {
+    match format {
+        "aif" => "aiff",
+        other => other,
+    }
+}
+
+/// Subsequence match of `needle` in `haystack`, scored so that consecutive runs,
+/// word starts and whole substrings rank higher. `needle` must be lower case.
+pub fn fuzzy_score(needle: &str, haystack: &str) -> Option<u32> {
+    let haystack = haystack.to_lowercase();
+    let chars: Vec<char> = haystack.chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for wanted in needle.chars() {
+        let found = position + chars[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 4;
+        }
+        if found == 0 || !chars[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+
+    if haystack.contains(needle) {
+        score += 8;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::key::Mode;
+    use crate::history::HistoryStore;
+    use crate::tags::TagFields;
+    use std::path::PathBuf;
+
+    fn candidate() -> Candidate {
+        Candidate {
+            artist: Some("Daft Punk".to_string()),
+            title: "One More Time".to_string(),
+            url: Some("https://youtu.be/abc".to_string()),
+            crates: vec!["Peak Time".to_string()],
+            bpm: Some(122.8),
+            key: Some(MusicalKey {
+                tonic: 9,
+                mode: Mode::Minor,
+            }),
+            format: Some("mp3".to_string()),
+            ..Candidate::default()
+        }
+    }
+
+    #[test]
+    fn fuzzy_terms_match_any_field() {
+        assert!(Query::parse("dft pnk").score(&candidate()).is_some());
+        assert!(Query::parse("youtu.be").score(&candidate()).is_some());
+        assert!(Query::parse("peak").score(&candidate()).is_some());
+        assert!(Query::parse("zedd").score(&candidate()).is_none());
+    }
+
+    #[test]
+    fn structured_filters_narrow_results() {
+        let track = candidate();
+        assert!(
+            Query::parse("bpm:120-125 key:8A format:mp3")
+                .score(&track)
+                .is_some()
+        );
+        assert!(Query::parse("bpm:123").score(&track).is_some());
+        assert!(Query::parse("bpm:124+").score(&track).is_none());
+        assert!(Query::parse("key:Am").score(&track).is_some());
+        assert!(Query::parse("key:9A").score(&track).is_none());
+        assert!(Query::parse("format:aiff").score(&track).is_none());
+        assert!(Query::parse("crate:\"peak time\"").score(&track).is_some());
+        assert!(Query::parse("artist:time").score(&track).is_none());
+    }
+
+    #[test]
+    fn downloads_are_searchable_by_their_tags() {
+        let mut store = HistoryStore::default();
+        let id = store.add(
+            "Daft Punk - One More Time.mp3".to_string(),
+            PathBuf::from("/music/Daft Punk - One More Time.mp3"),
+            "https://youtu.be/abc".to_string(),
+        );
+        let entry = store.get_mut(id).unwrap();
+        assert!(
+            Query::parse("genre:house")
+                .score(&Candidate::from_entry(entry, &[]))
+                .is_none()
+        );
+
+        entry.tags = Some(TagFields {
+            album: Some("Discovery".to_string()),
+            genre: Some("French House".to_string()),
+            comment: Some("Recorded live at Wireless, 2007".to_string()),
+            ..TagFields::default()
+        });
+        let track = Candidate::from_entry(entry, &[]);
+        assert_eq!(track.artist.as_deref(), Some("Daft Punk"));
+        assert!(Query::parse("genre:house").score(&track).is_some());
+        assert!(Query::parse("album:discovery").score(&track).is_some());
+        assert!(Query::parse("discovery").score(&track).is_some());
+        assert!(Query::parse("comment:wireless").score(&track).is_some());
+        // Comments are only searched when asked for
+        assert!(Query::parse("wireless").score(&track).is_none());
+    }
+
+    #[test]
+    fn closer_matches_score_higher() {
+        let exact = fuzzy_score("time", "One More Time").unwrap();
+        let scattered = fuzzy_score("time", "This Is My Everything").unwrap();
+        assert!(exact > scattered);
+    }
+}
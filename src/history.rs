@@ -0,0 +1,468 @@
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::analysis::fingerprint::Fingerprint;
+use crate::analysis::key::MusicalKey;
+use crate::analysis::quality::QualityInfo;
+use crate::clip::Clip;
+use crate::library::LibraryFile;
+use crate::postprocess::loudness::LoudnessInfo;
+use crate::postprocess::trim::TrimInfo;
+use crate::tags::TagFields;
+
+/// A finished download and what we know about it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Stable identifier within the store
+    pub id: u64,
+    /// File name shown in the downloads list
+    pub filename: String,
+    /// Full path of the downloaded file
+    pub path: PathBuf,
+    /// Source URL the file was downloaded from
+    pub url: String,
+    /// Unix timestamp of when the download finished
+    pub downloaded_at: u64,
+    /// Detected tempo, once analysis has finished
+    #[serde(default)]
+    pub bpm: Option<f32>,
+    /// Detected key, once analysis has finished
+    #[serde(default)]
+    pub key: Option<MusicalKey>,
+    /// Detected first downbeat in seconds, for a quick cue point
+    #[serde(default)]
+    pub first_downbeat: Option<f32>,
+    /// Measured loudness, if the loudness stage ran
+    #[serde(default)]
+    pub loudness: Option<LoudnessInfo>,
+    /// Silence trimmed from either end, if trimming ran
+    #[serde(default)]
+    pub trim: Option<TrimInfo>,
+    /// Source format and measured bandwidth, if the quality check ran
+    #[serde(default)]
+    pub quality: Option<QualityInfo>,
+    /// The mix this track was split out of
+    #[serde(default)]
+    pub parent: Option<u64>,
+    /// The part of the video this is, when only a clip was downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clip: Option<Clip>,
+    /// When the app run that downloaded this started (Unix seconds); groups entries into sessions
+    #[serde(default)]
+    pub session: Option<u64>,
+    /// Acoustic fingerprint, for finding the same track under another name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+    /// Tags read back once processing finished, for searching by album, genre or comment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<TagFields>,
+    /// Name of a file that sounds the same, found when the download finished (never persisted)
+    #[serde(skip)]
+    pub duplicate_of: Option<String>,
+    /// Whether background processing is still running (never persisted)
+    #[serde(skip)]
+    pub analyzing: bool,
+}
+
+/// A named, ordered selection of downloads, e.g. "Friday Warmup"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Crate {
+    pub name: String,
+    /// History ids, in play order
+    pub tracks: Vec<u64>,
+}
+
+impl Crate {
+    /// Append a download unless it's already in the crate; returns whether it was added
+    pub fn add(&mut self, id: u64) -> bool {
+        if self.tracks.contains(&id) {
+            return false;
+        }
+        self.tracks.push(id);
+        true
+    }
+
+    /// Move the track at `index` one place up or down; returns where it ended up
+    pub fn move_track(&mut self, index: usize, up: bool) -> usize {
+        let target = if up {
+            index.saturating_sub(1)
+        } else {
+            (index + 1).min(self.tracks.len().saturating_sub(1))
+        };
+        if index < self.tracks.len() {
+            self.tracks.swap(index, target);
+        }
+        target
+    }
+
+    /// Take the track at `index` out of the crate; returns where the cursor should go next
+    pub fn remove(&mut self, index: usize) -> Option<usize> {
+        if index >= self.tracks.len() {
+            return None;
+        }
+        self.tracks.remove(index);
+        Some(index.min(self.tracks.len().saturating_sub(1)))
+    }
+}
+
+/// Download history persisted to `~/.local/share/dj-cli/history.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HistoryStore {
+    /// All downloads, oldest first
+    pub entries: Vec<HistoryEntry>,
+    /// Crates, in the order they were created
+    #[serde(default)]
+    pub crates: Vec<Crate>,
+    /// Files found by the last library scan, kept so rescans only probe what changed
+    #[serde(default)]
+    pub library: Vec<LibraryFile>,
+    /// Next id handed out by `add`
+    next_id: u64,
+    /// Session new entries are stamped with, once the app has started one
+    #[serde(skip)]
+    session: Option<u64>,
+    /// Where an unreadable history file was moved to when this store started empty instead
+    #[serde(skip)]
+    pub backup: Option<PathBuf>,
+    /// Set when an unreadable history file couldn't be moved aside, so `save` leaves it alone
+    #[serde(skip)]
+    locked: bool,
+}
+
+impl HistoryStore {
+    /// Location of the history file
+    pub fn path() -> PathBuf {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        PathBuf::from(home)
+            .join(".local")
+            .join("share")
+            .join("dj-cli")
+            .join("history.json")
+    }
+
+    /// Load the history file, starting empty if it is missing. One that can't be read is
+    /// kept as a backup rather than overwritten by the next save.
+    pub fn load() -> Self {
+        Self::load_from(&Self::path())
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("No history file at {}, starting fresh", path.display());
+                return Self::default();
+            }
+            Err(e) => {
+                warn!("Could not read history file {}: {}", path.display(), e);
+                return Self::unreadable(path);
+            }
+        };
+
+        match serde_json::from_str::<HistoryStore>(&contents) {
+            Ok(store) => store,
+            Err(e) => {
+                warn!("Invalid history file {}: {}", path.display(), e);
+                Self::unreadable(path)
+            }
+        }
+    }
+
+    /// An empty store in place of a history file that couldn't be loaded, which is moved
+    /// aside - or, failing that, never saved over
+    fn unreadable(path: &Path) -> Self {
+        let backup = path.with_extension(format!("json.unreadable-{}", unix_now()));
+        match std::fs::rename(path, &backup) {
+            Ok(()) => {
+                warn!("Kept the unreadable history file as {}", backup.display());
+                Self {
+                    backup: Some(backup),
+                    ..Self::default()
+                }
+            }
+            Err(e) => {
+                warn!("Could not move {} aside: {}", path.display(), e);
+                Self {
+                    locked: true,
+                    ..Self::default()
+                }
+            }
+        }
+    }
+
+    /// Write the history file, replacing it atomically
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path())
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if self.locked {
+            return Err(eyre!(
+                "{} couldn't be loaded, so it isn't being overwritten",
+                path.display()
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temp file first so a crash never leaves a half-written history
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Start a new session; entries added from now on belong to it
+    pub fn start_session(&mut self) -> u64 {
+        let session = unix_now();
+        self.session = Some(session);
+        session
+    }
+
+    /// Distinct sessions in the order they happened, with their top-level and split entries
+    pub fn sessions(&self) -> Vec<(u64, Vec<&HistoryEntry>)> {
+        let mut sessions: Vec<(u64, Vec<&HistoryEntry>)> = Vec::new();
+        for entry in &self.entries {
+            let Some(session) = entry.session else {
+                continue;
+            };
+            match sessions.iter_mut().find(|(id, _)| *id == session) {
+                Some((_, entries)) => entries.push(entry),
+                None => sessions.push((session, vec![entry])),
+            }
+        }
+        sessions
+    }
+
+    /// Record a new download and return its id
+    pub fn add(&mut self, filename: String, path: PathBuf, url: String) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.entries.push(HistoryEntry {
+            id,
+            filename,
+            path,
+            url,
+            downloaded_at: unix_now(),
+            bpm: None,
+            key: None,
+            first_downbeat: None,
+            loudness: None,
+            trim: None,
+            quality: None,
+            parent: None,
+            clip: None,
+            session: self.session,
+            fingerprint: None,
+            tags: None,
+            duplicate_of: None,
+            analyzing: false,
+        });
+        id
+    }
+
+    /// Record a track split out of the mix `parent` and return its id
+    pub fn add_child(&mut self, parent: u64, filename: String, path: PathBuf, url: String) -> u64 {
+        let id = self.add(filename, path, url);
+        if let Some(entry) = self.get_mut(id) {
+            entry.parent = Some(parent);
+        }
+        id
+    }
+
+    /// Tracks split out of the mix `parent`, in order
+    pub fn children(&self, parent: u64) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(move |e| e.parent == Some(parent))
+    }
+
+    /// Find a crate by name, ignoring case
+    pub fn find_crate(&self, name: &str) -> Option<usize> {
+        self.crates
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Create a crate, or find the one that already has this name; returns its index
+    pub fn create_crate(&mut self, name: &str) -> usize {
+        if let Some(index) = self.find_crate(name) {
+            return index;
+        }
+        self.crates.push(Crate {
+            name: name.trim().to_string(),
+            tracks: Vec::new(),
+        });
+        self.crates.len() - 1
+    }
+
+    /// Look up an entry by id
+    pub fn get(&self, id: u64) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Look up an entry by id
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut HistoryEntry> {
+        self.entries.iter_mut().find(|e| e.id == id)
+    }
+}
+
+/// Format Unix seconds as "YYYY-MM-DD HH:MM" (UTC)
+pub fn format_timestamp(secs: u64) -> String {
+    let (date, minutes) = (secs / 86_400, secs % 86_400 / 60);
+    format!(
+        "{} {:02}:{:02}",
+        format_date(date * 86_400),
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Format Unix seconds as "YYYY-MM-DD" (UTC)
+pub fn format_date(secs: u64) -> String {
+    let (year, month, day) = civil_date(secs);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Year, month and day of Unix seconds (UTC)
+pub fn civil_date(secs: u64) -> (i64, i64, i64) {
+    // Civil-from-days, after Howard Hinnant's date algorithms
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Current time as Unix seconds
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silent_tracks_survive_a_save() {
+        let dir = std::env::temp_dir().join(format!("dj-cli-history-{}", std::process::id()));
+        let path = dir.join("history.json");
+        let mut store = HistoryStore::default();
+        store.entries.push(HistoryEntry {
+            id: 0,
+            filename: "Silence.mp3".to_string(),
+            path: dir.join("Silence.mp3"),
+            url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            downloaded_at: 0,
+            bpm: None,
+            key: None,
+            first_downbeat: None,
+            loudness: Some(LoudnessInfo {
+                integrated_lufs: None,
+                true_peak_dbtp: None,
+                range_lu: 0.0,
+                gain_db: 0.0,
+            }),
+            trim: None,
+            quality: None,
+            parent: None,
+            clip: None,
+            session: None,
+            fingerprint: None,
+            tags: None,
+            duplicate_of: None,
+            analyzing: false,
+        });
+        store.save_to(&path).unwrap();
+
+        let loaded = HistoryStore::load_from(&path);
+        assert_eq!(loaded.entries.len(), 1);
+        assert_eq!(loaded.entries[0].loudness, store.entries[0].loudness);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_history_is_kept_aside() {
+        let dir = std::env::temp_dir().join(format!("dj-cli-bad-history-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.json");
+        std::fs::write(&path, r#"{"entries": [{"id": null}]}"#).unwrap();
+
+        let store = HistoryStore::load_from(&path);
+        assert!(store.entries.is_empty());
+        let backup = store.backup.clone().expect("no backup");
+        assert_eq!(
+            std::fs::read_to_string(&backup).unwrap(),
+            r#"{"entries": [{"id": null}]}"#
+        );
+
+        // Saving starts a new file without touching the backup
+        store.save_to(&path).unwrap();
+        assert!(backup.exists() && path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn krate(tracks: &[u64]) -> Crate {
+        Crate {
+            name: "Friday Warmup".to_string(),
+            tracks: tracks.to_vec(),
+        }
+    }
+
+    #[test]
+    fn crates_hold_each_track_once() {
+        let mut krate = krate(&[]);
+        assert!(krate.add(4));
+        assert!(krate.add(2));
+        assert!(!krate.add(4));
+        assert_eq!(krate.tracks, [4, 2]);
+    }
+
+    #[test]
+    fn moving_tracks_stops_at_the_ends() {
+        let mut krate = krate(&[1, 2, 3]);
+        assert_eq!(krate.move_track(2, true), 1);
+        assert_eq!(krate.tracks, [1, 3, 2]);
+        assert_eq!(krate.move_track(0, true), 0);
+        assert_eq!(krate.move_track(2, false), 2);
+        assert_eq!(krate.tracks, [1, 3, 2]);
+        assert_eq!(krate.move_track(0, false), 1);
+        assert_eq!(krate.tracks, [3, 1, 2]);
+    }
+
+    #[test]
+    fn removing_keeps_the_cursor_in_the_crate() {
+        let mut krate = krate(&[1, 2, 3]);
+        assert_eq!(krate.remove(1), Some(1));
+        assert_eq!(krate.remove(1), Some(0));
+        assert_eq!(krate.tracks, [1]);
+        assert_eq!(krate.remove(5), None);
+        assert_eq!(krate.remove(0), Some(0));
+        assert!(krate.tracks.is_empty());
+        assert_eq!(krate.remove(0), None);
+    }
+
+    #[test]
+    fn crate_names_ignore_case() {
+        let mut store = HistoryStore::default();
+        assert_eq!(store.create_crate(" Friday Warmup "), 0);
+        assert_eq!(store.create_crate("Peak Time"), 1);
+        assert_eq!(store.create_crate("friday warmup"), 0);
+        assert_eq!(store.crates[0].name, "Friday Warmup");
+        assert_eq!(store.find_crate("PEAK TIME"), Some(1));
+        assert_eq!(store.find_crate("Closing"), None);
+    }
+}
@@ -0,0 +1,416 @@
+//! Key bindings for the main screen. Every global action has a name, a description for the
+//! help overlay and default keys; `[keys]` in the config file rebinds any of them, e.g.
+//! `download_128 = ["ctrl+1", "alt+1"]`.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+use tracing::warn;
+
+use crate::config::KeyList;
+
+/// Something a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Back,
+    NextPane,
+    PreviousPane,
+    Submit,
+    Download128,
+    Download256,
+    ToggleBitrate,
+    Retry,
+    ExtractUrl,
+    ToggleTrim,
+    ToggleSplit,
+    SearchHistory,
+    SavePlaylist,
+    OpenLibrary,
+    OpenCrates,
+    ClearInput,
+    DeleteChar,
+    PasteHint,
+    Help,
+}
+
+impl Action {
+    /// In the order the help overlay lists them
+    pub const ALL: [Action; 20] = [
+        Action::Submit,
+        Action::Download128,
+        Action::Download256,
+        Action::ToggleBitrate,
+        Action::Retry,
+        Action::ExtractUrl,
+        Action::ToggleTrim,
+        Action::ToggleSplit,
+        Action::SearchHistory,
+        Action::SavePlaylist,
+        Action::OpenLibrary,
+        Action::OpenCrates,
+        Action::ClearInput,
+        Action::DeleteChar,
+        Action::PasteHint,
+        Action::NextPane,
+        Action::PreviousPane,
+        Action::Help,
+        Action::Back,
+        Action::Quit,
+    ];
+
+    /// Name under `[keys]` in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Back => "back",
+            Action::NextPane => "next_pane",
+            Action::PreviousPane => "previous_pane",
+            Action::Submit => "submit",
+            Action::Download128 => "download_128",
+            Action::Download256 => "download_256",
+            Action::ToggleBitrate => "toggle_bitrate",
+            Action::Retry => "retry",
+            Action::ExtractUrl => "extract_url",
+            Action::ToggleTrim => "toggle_trim",
+            Action::ToggleSplit => "toggle_split",
+            Action::SearchHistory => "search_history",
+            Action::SavePlaylist => "save_playlist",
+            Action::OpenLibrary => "open_library",
+            Action::OpenCrates => "open_crates",
+            Action::ClearInput => "clear_input",
+            Action::DeleteChar => "delete_char",
+            Action::PasteHint => "paste_hint",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Back => "Close search results, or quit",
+            Action::NextPane => "Focus the next pane",
+            Action::PreviousPane => "Focus the previous pane",
+            Action::Submit => "Download a URL, or search YouTube for other text",
+            Action::Download128 => "Download at 128kbps",
+            Action::Download256 => "Download at 256kbps",
+            Action::ToggleBitrate => "Switch Enter between 128 and 256kbps",
+            Action::Retry => "Retry the last failed download",
+            Action::ExtractUrl => "Clean the input and extract the URL from it",
+            Action::ToggleTrim => "Toggle silence trimming for the next download",
+            Action::ToggleSplit => "Toggle splitting the next download into tracks",
+            Action::SearchHistory => "Search the download history",
+            Action::SavePlaylist => "Save this session's playlist now",
+            Action::OpenLibrary => "Open the library table",
+            Action::OpenCrates => "Open the crate panel",
+            Action::ClearInput => "Clear the input",
+            Action::DeleteChar => "Remove the last character",
+            Action::PasteHint => "Explain how to paste",
+            Action::Help => "Show this help",
+        }
+    }
+
+    /// Keys bound out of the box; Ctrl+digit has Alt+digit beside it for terminals that can't send it
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["ctrl+c"],
+            Action::Back => &["esc"],
+            Action::NextPane => &["tab"],
+            Action::PreviousPane => &["shift+tab"],
+            Action::Submit => &["enter"],
+            Action::Download128 => &["ctrl+1", "alt+1"],
+            Action::Download256 => &["ctrl+2", "alt+2"],
+            Action::ToggleBitrate => &["ctrl+f"],
+            Action::Retry => &["ctrl+r"],
+            Action::ExtractUrl => &["f5"],
+            Action::ToggleTrim => &["ctrl+t"],
+            Action::ToggleSplit => &["ctrl+s"],
+            Action::SearchHistory => &["/"],
+            Action::SavePlaylist => &["ctrl+p"],
+            Action::OpenLibrary => &["ctrl+l"],
+            Action::OpenCrates => &["ctrl+k"],
+            Action::ClearInput => &["delete"],
+            Action::DeleteChar => &["backspace"],
+            Action::PasteHint => &["ctrl+v"],
+            Action::Help => &["?", "f1"],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A key with its modifiers, as bindings compare them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            // Shift is already in the character, and terminals disagree on whether they report it too
+            KeyCode::Char(c) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                if modifiers.is_empty() {
+                    KeyCode::Char(c)
+                } else {
+                    KeyCode::Char(c.to_ascii_lowercase())
+                }
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    /// "ctrl+1", "alt+shift+up", "f5", "?"; modifier and key names ignore case
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        // A lone "+" is the key itself, not a separator
+        let (modifier_text, key) = match text.rsplit_once('+') {
+            Some((modifiers, "")) if modifiers.ends_with('+') || modifiers.is_empty() => {
+                (modifiers.trim_end_matches('+'), "+")
+            }
+            Some((modifiers, key)) => (modifiers, key),
+            None => ("", text),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for name in modifier_text.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= match name.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "option" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "space" => KeyCode::Char(' '),
+                name => {
+                    let number = name.strip_prefix('f')?.parse().ok()?;
+                    if !(1..=24).contains(&number) {
+                        return None;
+                    }
+                    KeyCode::F(number)
+                }
+            },
+        };
+        Some(Self::new(code, modifiers))
+    }
+
+    /// A character with no Ctrl or Alt, which the input box would otherwise take
+    pub fn is_printable(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && self.modifiers.is_empty()
+    }
+}
+
+impl From<&KeyEvent> for Key {
+    fn from(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) if self.modifiers.is_empty() => write!(f, "{c}"),
+            KeyCode::Char(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            KeyCode::BackTab => f.write_str("Shift+Tab"),
+            KeyCode::PageUp => f.write_str("PgUp"),
+            KeyCode::PageDown => f.write_str("PgDn"),
+            KeyCode::Up => f.write_str("↑"),
+            KeyCode::Down => f.write_str("↓"),
+            KeyCode::Left => f.write_str("←"),
+            KeyCode::Right => f.write_str("→"),
+            KeyCode::Enter => f.write_str("Enter"),
+            KeyCode::Esc => f.write_str("Esc"),
+            KeyCode::Tab => f.write_str("Tab"),
+            KeyCode::Backspace => f.write_str("Backspace"),
+            KeyCode::Delete => f.write_str("Delete"),
+            KeyCode::Insert => f.write_str("Insert"),
+            KeyCode::Home => f.write_str("Home"),
+            KeyCode::End => f.write_str("End"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// Which action each key performs
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(Key, Action)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::new(&HashMap::new())
+    }
+}
+
+impl KeyMap {
+    /// The default bindings with `[keys]` applied; a key given to one action is taken
+    /// from whichever action had it before
+    pub fn new(overrides: &HashMap<String, KeyList>) -> Self {
+        let mut bindings: Vec<(Key, Action)> = Action::ALL
+            .into_iter()
+            .flat_map(|action| {
+                action
+                    .default_keys()
+                    .iter()
+                    .filter_map(|text| Key::parse(text))
+                    .map(move |key| (key, action))
+            })
+            .collect();
+
+        let mut names: Vec<&String> = overrides.keys().collect();
+        names.sort();
+        for name in names {
+            let Some(action) = Action::from_name(name) else {
+                warn!("Ignoring key binding for unknown action '{}'", name);
+                continue;
+            };
+            let keys: Vec<Key> = overrides[name]
+                .iter()
+                .filter_map(|text| {
+                    let key = Key::parse(text);
+                    if key.is_none() {
+                        warn!("Ignoring unknown key '{}' for {}", text, name);
+                    }
+                    key
+                })
+                .collect();
+            bindings.retain(|(key, bound)| *bound != action && !keys.contains(key));
+            bindings.extend(keys.into_iter().map(|key| (key, action)));
+        }
+        Self { bindings }
+    }
+
+    /// The action bound to a key press, if any
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        let key = Key::from(event);
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, action)| *action)
+    }
+
+    /// Keys bound to an action, in the order they were given
+    pub fn keys(&self, action: Action) -> Vec<Key> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// The keys for an action as the help and hints show them, e.g. "Ctrl+1 / Alt+1"
+    pub fn label(&self, action: Action) -> Option<String> {
+        let keys = self.keys(action);
+        (!keys.is_empty()).then(|| {
+            keys.iter()
+                .map(Key::to_string)
+                .collect::<Vec<_>>()
+                .join(" / ")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parses_and_shows_keys() {
+        for (text, shown) in [
+            ("ctrl+1", "Ctrl+1"),
+            ("Alt+Shift+Up", "Alt+Shift+↑"),
+            ("F5", "F5"),
+            ("?", "?"),
+            ("ctrl++", "Ctrl++"),
+            ("shift+tab", "Shift+Tab"),
+            ("space", "Space"),
+            ("CTRL+K", "Ctrl+K"),
+        ] {
+            let key = Key::parse(text).unwrap_or_else(|| panic!("{text} should parse"));
+            assert_eq!(key.to_string(), shown);
+        }
+        for text in ["hyper+x", "f99", "banana", ""] {
+            assert_eq!(Key::parse(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn matches_key_events_however_the_terminal_reports_shift() {
+        let keymap = KeyMap::default();
+        let help = press(KeyCode::Char('?'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.action(&help), Some(Action::Help));
+        let back_tab = press(KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(keymap.action(&back_tab), Some(Action::PreviousPane));
+        let alt_one = press(KeyCode::Char('1'), KeyModifiers::ALT);
+        assert_eq!(keymap.action(&alt_one), Some(Action::Download128));
+        let plain_one = press(KeyCode::Char('1'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(&plain_one), None);
+    }
+
+    #[test]
+    fn config_rebinds_actions() {
+        let overrides = HashMap::from([
+            ("download_128".to_string(), KeyList::Many(vec!["f7".into()])),
+            ("help".to_string(), KeyList::One("ctrl+p".into())),
+            ("nonsense".to_string(), KeyList::One("f8".into())),
+        ]);
+        let keymap = KeyMap::new(&overrides);
+        assert_eq!(keymap.label(Action::Download128).as_deref(), Some("F7"));
+        assert_eq!(keymap.label(Action::Help).as_deref(), Some("Ctrl+P"));
+        // Ctrl+P moved to help, so saving the playlist has no key left
+        assert_eq!(keymap.label(Action::SavePlaylist), None);
+        let ctrl_one = press(KeyCode::Char('1'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(&ctrl_one), None);
+    }
+}
@@ -0,0 +1,453 @@
+//! Local music library: everything in the configured folders, not just what dj-cli
+//! downloaded. Scans are incremental - a file is only probed again when its
+//! modification time or size changes - and the results are cached in the history store.
+
+use color_eyre::{Result, eyre::eyre};
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::UNIX_EPOCH;
+use tracing::{info, warn};
+
+use crate::analysis::fingerprint::{self, Fingerprint};
+use crate::history::unix_now;
+use crate::shutdown;
+
+/// File types the scanner picks up
+pub const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "aiff", "aif", "wav"];
+/// ffprobe runs at once during a scan
+const PROBE_CONCURRENCY: usize = 8;
+
+/// One audio file found in a library folder
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryFile {
+    pub path: PathBuf,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub bpm: Option<f32>,
+    /// Key as tagged; notations vary between tools ("Am", "8A", "A minor")
+    pub key: Option<String>,
+    /// Length in seconds
+    pub duration: Option<f64>,
+    /// Upper-case container name, e.g. "MP3" or "FLAC"
+    pub format: String,
+    /// Unix seconds when a scan first found the file
+    pub added: u64,
+    /// Modification time (Unix seconds) and size when it was last probed
+    pub modified: u64,
+    pub size: u64,
+    /// Acoustic fingerprint, when duplicate detection is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+}
+
+impl LibraryFile {
+    /// Title tag, falling back to the file name
+    pub fn display_title(&self) -> String {
+        self.title.clone().unwrap_or_else(|| {
+            self.path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// What a scan found
+#[derive(Debug, Clone, Default)]
+pub struct ScanReport {
+    /// Every file now in the library, sorted by path
+    pub files: Vec<LibraryFile>,
+    /// Files probed because they were new or changed
+    pub probed: usize,
+    /// Cached files that no longer exist
+    pub removed: usize,
+}
+
+/// Scan `folders` recursively, reusing `cached` entries for files that haven't changed.
+/// New and changed files are fingerprinted too when `fingerprints` is set.
+pub async fn scan(
+    folders: Vec<PathBuf>,
+    cached: Vec<LibraryFile>,
+    fingerprints: bool,
+) -> Result<ScanReport> {
+    let found = tokio::task::spawn_blocking(move || {
+        let mut found = Vec::new();
+        for folder in &folders {
+            if let Err(e) = walk(folder, &mut found) {
+                warn!("Could not scan {}: {}", folder.display(), e);
+            }
+        }
+        found
+    })
+    .await?;
+
+    let mut cached: HashMap<PathBuf, LibraryFile> =
+        cached.into_iter().map(|f| (f.path.clone(), f)).collect();
+    let mut files = Vec::new();
+    let mut changed = Vec::new();
+    for (path, modified, size) in found {
+        match cached.remove(&path) {
+            Some(file) if is_current(&file, modified, size, fingerprints) => files.push(file),
+            previous => changed.push((path, modified, size, previous.map(|f| f.added))),
+        }
+    }
+    let removed = cached.len();
+    let probed = changed.len();
+
+    let now = unix_now();
+    let mut fresh: Vec<LibraryFile> = stream::iter(changed)
+        .map(|(path, modified, size, added)| async move {
+            let mut file = match probe(&path).await {
+                Ok(file) => LibraryFile { modified, ..file },
+                Err(e) => {
+                    // Leave the modification time unset so the next scan tries again
+                    warn!("Could not read tags from {}: {}", path.display(), e);
+                    LibraryFile {
+                        path: path.clone(),
+                        ..LibraryFile::default()
+                    }
+                }
+            };
+            if fingerprints && file.modified != 0 {
+                match fingerprint::fingerprint(&path).await {
+                    Ok(fingerprint) => file.fingerprint = Some(fingerprint),
+                    Err(e) => warn!("Could not fingerprint {}: {}", path.display(), e),
+                }
+            }
+            file.format = format_name(&path);
+            file.added = added.unwrap_or(now);
+            file.size = size;
+            file
+        })
+        .buffer_unordered(PROBE_CONCURRENCY)
+        .collect()
+        .await;
+    files.append(&mut fresh);
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    info!(
+        "Library scan: {} files, {} probed, {} gone",
+        files.len(),
+        probed,
+        removed
+    );
+    Ok(ScanReport {
+        files,
+        probed,
+        removed,
+    })
+}
+
+/// Whether a cached entry still describes the file on disk, so it needn't be probed again.
+/// Files scanned before duplicate detection was on are read again for a fingerprint.
+fn is_current(file: &LibraryFile, modified: u64, size: u64, fingerprints: bool) -> bool {
+    file.modified == modified && file.size == size && (!fingerprints || file.fingerprint.is_some())
+}
+
+/// Collect audio files under `dir` with their modification time and size
+fn walk(dir: &Path, found: &mut Vec<(PathBuf, u64, u64)>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            // Skip hidden folders such as .Trashes and DJ software databases like _Serato_
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with('.') && !name.starts_with('_') {
+                walk(&path, found)?;
+            }
+        } else if file_type.is_file() && is_audio(&path) {
+            let metadata = entry.metadata()?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            found.push((path, modified, metadata.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the scanner picks this file up
+fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// "MP3", "FLAC", "AIFF" or "WAV"
+fn format_name(path: &Path) -> String {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("aif") => "AIFF".to_string(),
+        Some(ext) => ext.to_ascii_uppercase(),
+        None => String::new(),
+    }
+}
+
+/// The parts of `ffprobe -show_format` we read
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProbeOutput {
+    format: ProbeFormat,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProbeFormat {
+    duration: Option<String>,
+    tags: HashMap<String, String>,
+}
+
+/// Read tags and duration with ffprobe, which handles ID3, Vorbis comments and RIFF INFO alike
+pub async fn probe(path: &Path) -> Result<LibraryFile> {
+    let mut cmd = shutdown::command("ffprobe");
+    cmd.args(["-v", "error", "-print_format", "json", "-show_format"])
+        .arg(path);
+
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|_| eyre!("ffprobe not found. Please install: brew install ffmpeg"))?;
+    if !output.status.success() {
+        return Err(eyre!("ffprobe could not read {}", path.display()));
+    }
+
+    let probe: ProbeOutput = serde_json::from_slice(&output.stdout)?;
+    // Tag names differ in case between containers ("TITLE" in FLAC, "title" in MP3)
+    let tags: HashMap<String, String> = probe
+        .format
+        .tags
+        .into_iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    let tag = |names: &[&str]| names.iter().find_map(|name| tags.get(*name).cloned());
+
+    Ok(LibraryFile {
+        path: path.to_path_buf(),
+        artist: tag(&["artist", "album_artist"]),
+        title: tag(&["title"]),
+        album: tag(&["album"]),
+        genre: tag(&["genre"]),
+        bpm: tag(&["tbpm", "bpm"]).and_then(|bpm| bpm.parse().ok()),
+        key: tag(&["tkey", "initialkey", "key"]),
+        duration: probe.format.duration.and_then(|d| d.parse().ok()),
+        ..LibraryFile::default()
+    })
+}
+
+/// Columns the library table can be sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortColumn {
+    #[default]
+    Artist,
+    Title,
+    Bpm,
+    Key,
+    Duration,
+    Format,
+    Added,
+}
+
+impl SortColumn {
+    /// Every column, in table order
+    pub const ALL: [SortColumn; 7] = [
+        SortColumn::Artist,
+        SortColumn::Title,
+        SortColumn::Bpm,
+        SortColumn::Key,
+        SortColumn::Duration,
+        SortColumn::Format,
+        SortColumn::Added,
+    ];
+
+    /// Column header
+    pub fn name(self) -> &'static str {
+        match self {
+            SortColumn::Artist => "Artist",
+            SortColumn::Title => "Title",
+            SortColumn::Bpm => "BPM",
+            SortColumn::Key => "Key",
+            SortColumn::Duration => "Time",
+            SortColumn::Format => "Format",
+            SortColumn::Added => "Added",
+        }
+    }
+
+    /// The column after this one, wrapping round
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|c| *c == self)
+            .unwrap_or_default();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Order two files by this column; missing values sort last
+    pub fn compare(self, a: &LibraryFile, b: &LibraryFile) -> std::cmp::Ordering {
+        fn text(value: &Option<String>) -> Option<String> {
+            value.as_ref().map(|v| v.to_lowercase())
+        }
+        fn missing_last<T: PartialOrd>(a: Option<T>, b: Option<T>) -> std::cmp::Ordering {
+            use std::cmp::Ordering;
+            match (a, b) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        match self {
+            SortColumn::Artist => missing_last(text(&a.artist), text(&b.artist)).then_with(|| {
+                a.display_title()
+                    .to_lowercase()
+                    .cmp(&b.display_title().to_lowercase())
+            }),
+            SortColumn::Title => a
+                .display_title()
+                .to_lowercase()
+                .cmp(&b.display_title().to_lowercase()),
+            SortColumn::Bpm => missing_last(a.bpm, b.bpm),
+            SortColumn::Key => missing_last(text(&a.key), text(&b.key)),
+            SortColumn::Duration => missing_last(a.duration, b.duration),
+            SortColumn::Format => a.format.cmp(&b.format),
+            SortColumn::Added => a.added.cmp(&b.added),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    fn file(name: &str) -> LibraryFile {
+        LibraryFile {
+            path: PathBuf::from(name),
+            format: "MP3".to_string(),
+            ..LibraryFile::default()
+        }
+    }
+
+    #[test]
+    fn unchanged_files_are_not_probed_again() {
+        let cached = LibraryFile {
+            modified: 1_700_000_000,
+            size: 4_096,
+            ..file("a.mp3")
+        };
+        assert!(is_current(&cached, 1_700_000_000, 4_096, false));
+        assert!(!is_current(&cached, 1_700_000_001, 4_096, false));
+        assert!(!is_current(&cached, 1_700_000_000, 4_097, false));
+
+        // Turning duplicate detection on reads files without a fingerprint again
+        assert!(!is_current(&cached, 1_700_000_000, 4_096, true));
+        let fingerprinted = LibraryFile {
+            fingerprint: Some(Fingerprint(vec![1, 2, 3])),
+            ..cached
+        };
+        assert!(is_current(&fingerprinted, 1_700_000_000, 4_096, true));
+    }
+
+    #[tokio::test]
+    async fn rescans_keep_cached_entries_and_drop_missing_ones() {
+        let dir = std::env::temp_dir().join(format!("dj-cli-library-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join(".Trashes")).unwrap();
+        std::fs::write(dir.join("Kept.mp3"), "audio").unwrap();
+        std::fs::write(dir.join(".Trashes").join("Hidden.mp3"), "audio").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not audio").unwrap();
+
+        let first = scan(vec![dir.clone()], Vec::new(), false).await.unwrap();
+        assert_eq!(first.files.len(), 1);
+        assert_eq!(first.probed, 1);
+
+        // Pretend an earlier probe read the tags, and that a file has since been deleted
+        let mut cached = first.files[0].clone();
+        let metadata = std::fs::metadata(dir.join("Kept.mp3")).unwrap();
+        cached.modified = metadata
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        cached.title = Some("Kept".to_string());
+        let gone = file(&dir.join("Gone.mp3").to_string_lossy());
+
+        let second = scan(vec![dir.clone()], vec![cached.clone(), gone], false)
+            .await
+            .unwrap();
+        assert_eq!(second.probed, 0);
+        assert_eq!(second.removed, 1);
+        assert_eq!(second.files, [cached]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sorting_puts_missing_values_last() {
+        let slow = LibraryFile {
+            bpm: Some(120.0),
+            ..file("b.mp3")
+        };
+        let fast = LibraryFile {
+            bpm: Some(128.0),
+            ..file("a.mp3")
+        };
+        let unknown = file("c.mp3");
+
+        let mut files = vec![unknown.clone(), fast.clone(), slow.clone()];
+        files.sort_by(|a, b| SortColumn::Bpm.compare(a, b));
+        assert_eq!(files, [slow, fast, unknown]);
+    }
+
+    #[test]
+    fn sorting_by_artist_falls_back_to_title() {
+        let tagged = |artist: &str, title: &str| LibraryFile {
+            artist: Some(artist.to_string()),
+            title: Some(title.to_string()),
+            ..file("x.mp3")
+        };
+        let column = SortColumn::Artist;
+        assert_eq!(
+            column.compare(&tagged("adam beyer", "Z"), &tagged("Amelie Lens", "A")),
+            Ordering::Less
+        );
+        assert_eq!(
+            column.compare(&tagged("Amelie Lens", "b"), &tagged("amelie lens", "A")),
+            Ordering::Greater
+        );
+        // Untagged titles come from the file name
+        assert_eq!(
+            SortColumn::Title.compare(&file("alpha.mp3"), &tagged("X", "Beta")),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn sort_columns_cycle() {
+        let mut column = SortColumn::Artist;
+        for _ in 0..SortColumn::ALL.len() {
+            column = column.next();
+        }
+        assert_eq!(column, SortColumn::Artist);
+        assert_eq!(SortColumn::Added.next(), SortColumn::Artist);
+    }
+}
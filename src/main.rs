@@ -2,10 +2,32 @@ use color_eyre::Result;
 use tracing::{error, info};
 // use tracing_subscriber;
 
+pub mod analysis;
 pub mod app;
+pub mod cli;
+pub mod clip;
+pub mod config;
+pub mod download;
+pub mod duplicates;
+pub mod export;
+pub mod filter;
+pub mod history;
+pub mod keymap;
+pub mod library;
+pub mod naming;
+pub mod postprocess;
+pub mod routing;
+pub mod shutdown;
+pub mod tags;
+pub mod template;
+pub mod theme;
+pub mod tracklist;
 pub mod ui;
+pub mod ytdlp;
 
 use app::App;
+use clap::Parser;
+use cli::Cli;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -15,16 +37,30 @@ async fn main() -> Result<()> {
     // Disable logging to keep TUI clean
     // tracing_subscriber::fmt::init();
 
+    // Subcommands run without the TUI
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        return cli::run(command).await;
+    }
+
     info!("Starting DJ CLI");
 
     // Initialize terminal
     let terminal = ratatui::init();
+    // Bracketed paste lets multi-line tracklists arrive as a single paste event
+    crossterm::execute!(std::io::stdout(), crossterm::event::EnableBracketedPaste)?;
+    let app = App::new();
+    let mouse = app.config.ui.mouse;
+    if mouse {
+        crossterm::execute!(std::io::stdout(), crossterm::event::EnableMouseCapture)?;
+    }
+    shutdown::install_panic_hook(mouse);
 
     // Run the app
-    let app_result = App::new().run(terminal).await;
+    let app_result = app.run(terminal).await;
 
     // Restore terminal
-    ratatui::restore();
+    shutdown::restore_terminal(mouse);
 
     if let Err(e) = &app_result {
         error!("Application error: {}", e);
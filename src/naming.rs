@@ -0,0 +1,180 @@
+//! Safe file names: templated, Unicode-normalised, and acceptable to FAT32/exFAT
+//! USB sticks as well as the local filesystem.
+
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::template;
+
+/// Names FAT (and Windows) reserve for devices, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Most filesystems cap a name at 255 bytes; leave room for an extension and a collision suffix
+const MAX_NAME_BYTES: usize = 240;
+
+/// Brackets left empty by placeholders without a value
+static EMPTY_BRACKETS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\(\s*\)|\[\s*\]|\{\s*\}").expect("valid empty brackets regex"));
+
+/// Spaces just inside brackets, left by a missing value next to a present one
+static BRACKET_PADDING_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([(\[{])\s+|\s+([)\]}])").expect("valid bracket padding regex"));
+
+/// Separators stranded at either end by placeholders without a value
+static DANGLING_SEPARATOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[\s\-–—_.,]+|[\s\-–—_,]+$").expect("valid dangling separator regex")
+});
+
+/// Render a file name template, tidying up around placeholders that had no value
+pub fn render(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let rendered = template::render(template, lookup);
+    let rendered = rendered.split_whitespace().collect::<Vec<_>>().join(" ");
+    let rendered = BRACKET_PADDING_RE.replace_all(&rendered, "$1$2");
+    let rendered = EMPTY_BRACKETS_RE.replace_all(&rendered, "");
+    let rendered = rendered.split_whitespace().collect::<Vec<_>>().join(" ");
+    DANGLING_SEPARATOR_RE
+        .replace_all(&rendered, "")
+        .into_owned()
+}
+
+/// Turn any text into a file name (without extension) that works on FAT32/exFAT, NTFS, APFS and ext4:
+/// NFC-normalised, no reserved characters or device names, no trailing dots or spaces,
+/// and at most `max_chars` characters
+pub fn sanitize(name: &str, max_chars: usize) -> String {
+    let cleaned: String = name
+        .nfc()
+        .filter_map(|c| match c {
+            '/' | '\\' | ':' | '|' => Some('-'),
+            '"' => Some('\''),
+            '*' | '?' | '<' | '>' => None,
+            c if c.is_whitespace() => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let mut name = String::new();
+    for c in cleaned.chars().take(max_chars.max(1)) {
+        if name.len() + c.len_utf8() > MAX_NAME_BYTES {
+            break;
+        }
+        name.push(c);
+    }
+
+    // FAT drops trailing dots and spaces; a leading dot hides the file on macOS and Linux
+    let name = name
+        .trim_start_matches(['.', ' '])
+        .trim_end_matches(['.', ' ', '-'])
+        .to_string();
+
+    let device = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(device))
+    {
+        return format!("{name}_");
+    }
+    if name.is_empty() {
+        return "untitled".to_string();
+    }
+    name
+}
+
+/// First free path for `stem.ext` in `dir`: the name itself, then `stem (2).ext`, `stem (3).ext`, ...
+/// Names are compared ignoring case, as FAT and exFAT do, so `Track.mp3` and `track.mp3`
+/// can't end up side by side and then overwrite each other on a stick.
+/// `current` is the file being renamed, which never collides with itself.
+pub fn unique_path(dir: &Path, stem: &str, ext: &str, current: Option<&Path>) -> PathBuf {
+    let file_name = |n: u32| match (n, ext.is_empty()) {
+        (1, true) => stem.to_string(),
+        (1, false) => format!("{stem}.{ext}"),
+        (n, true) => format!("{stem} ({n})"),
+        (n, false) => format!("{stem} ({n}).{ext}"),
+    };
+
+    let current = current
+        .filter(|path| path.parent() == Some(dir))
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().to_lowercase());
+    let taken: HashSet<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_lowercase())
+        .filter(|name| Some(name) != current.as_ref())
+        .collect();
+
+    (1..)
+        .map(file_name)
+        .find(|name| !taken.contains(&name.to_lowercase()))
+        .map(|name| dir.join(name))
+        .expect("an unbounded range always has a free name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_characters_fat_rejects() {
+        assert_eq!(
+            sanitize("AC/DC: Back in Black?", 100),
+            "AC-DC- Back in Black"
+        );
+        assert_eq!(sanitize("Say \"When\" <Live>*", 100), "Say 'When' Live");
+        assert_eq!(sanitize("Tab\tand\nnewline\u{7}", 100), "Tab and newline");
+    }
+
+    #[test]
+    fn avoids_device_names_and_trailing_dots() {
+        assert_eq!(sanitize("CON", 100), "CON_");
+        assert_eq!(sanitize("nul.txt", 100), "nul.txt_");
+        assert_eq!(sanitize("Console", 100), "Console");
+        assert_eq!(sanitize("Ends with dots... ", 100), "Ends with dots");
+        assert_eq!(sanitize("  .hidden", 100), "hidden");
+        assert_eq!(sanitize("???", 100), "untitled");
+    }
+
+    #[test]
+    fn truncates_on_a_character_boundary() {
+        assert_eq!(sanitize("Beyoncé - Halo", 7), "Beyoncé");
+        // Within the byte budget, a multi-byte character is never cut in half
+        let long = "é".repeat(200);
+        let name = sanitize(&long, 1000);
+        assert!(name.len() <= MAX_NAME_BYTES);
+        assert_eq!(name.chars().count(), MAX_NAME_BYTES / 2);
+        // Decomposed accents are composed first
+        assert_eq!(sanitize("Beyonce\u{301}", 100), "Beyoncé");
+    }
+
+    #[test]
+    fn numbers_names_that_are_taken_in_any_case() {
+        let dir = std::env::temp_dir().join(format!("dj-cli-naming-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(
+            unique_path(&dir, "Track", "mp3", None),
+            dir.join("Track.mp3")
+        );
+
+        std::fs::write(dir.join("track.MP3"), b"").unwrap();
+        std::fs::write(dir.join("Track (2).mp3"), b"").unwrap();
+        assert_eq!(
+            unique_path(&dir, "Track", "mp3", None),
+            dir.join("Track (3).mp3")
+        );
+        // A file being renamed doesn't collide with itself, whatever its case
+        assert_eq!(
+            unique_path(&dir, "Track", "mp3", Some(&dir.join("track.MP3"))),
+            dir.join("Track.mp3")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+//! Fade-in/out applied to clips so they don't start or stop mid-waveform.
+
+use color_eyre::Result;
+use std::path::Path;
+
+use crate::config::ClipConfig;
+
+/// Apply the configured fades to a clip in place
+pub async fn process(path: &Path, config: &ClipConfig, bitrate: u32) -> Result<()> {
+    if config.fade_in <= 0.0 && config.fade_out <= 0.0 {
+        return Ok(());
+    }
+
+    let duration = super::probe_duration(path).await?;
+    let mut filters = Vec::new();
+    if config.fade_in > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={:.3}", config.fade_in));
+    }
+    if config.fade_out > 0.0 {
+        // Never start the fade-out before the beginning of a very short clip
+        let start = (duration - config.fade_out as f64).max(0.0);
+        filters.push(format!(
+            "afade=t=out:st={start:.3}:d={:.3}",
+            config.fade_out
+        ));
+    }
+
+    super::apply_filter(path, &filters.join(","), bitrate).await
+}
@@ -0,0 +1,164 @@
+//! EBU R128 loudness measurement and normalisation via ffmpeg's `loudnorm` filter.
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use tracing::info;
+
+use crate::config::{LoudnessConfig, LoudnessMode};
+use crate::shutdown;
+use crate::tags;
+
+/// Measured loudness of a track
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessInfo {
+    /// Integrated loudness in LUFS; none for digital silence, which measures as -inf
+    pub integrated_lufs: Option<f32>,
+    /// True peak in dBTP; none for digital silence
+    pub true_peak_dbtp: Option<f32>,
+    /// Loudness range in LU
+    pub range_lu: f32,
+    /// Gain applied (normalize mode) or recommended (tags mode), in dB
+    pub gain_db: f32,
+}
+
+/// The subset of loudnorm's JSON summary we care about (it reports numbers as strings)
+#[derive(Debug, Deserialize)]
+struct LoudnormSummary {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+}
+
+/// Measure a file, then tag or normalise it according to `config`
+pub async fn process(path: &Path, config: &LoudnessConfig, bitrate: u32) -> Result<LoudnessInfo> {
+    let mut info = measure(path).await?;
+    info.gain_db = gain_to_target(&info, config);
+    info!(
+        "Loudness of {}: {:?} LUFS, {:?} dBTP, gain {:+.1} dB",
+        path.display(),
+        info.integrated_lufs,
+        info.true_peak_dbtp,
+        info.gain_db
+    );
+
+    match config.mode {
+        LoudnessMode::Off => {}
+        LoudnessMode::Tags => {
+            let tag_path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || tags::write_replaygain(&tag_path, &info)).await??;
+        }
+        LoudnessMode::Normalize => {
+            // Skip re-encoding when we're already within a tenth of a dB
+            if info.gain_db.abs() >= 0.1 {
+                let filter = format!("volume={:.2}dB", info.gain_db);
+                super::apply_filter(path, &filter, bitrate).await?;
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+/// Gain that brings the track to the target loudness without pushing the true peak past the ceiling
+fn gain_to_target(info: &LoudnessInfo, config: &LoudnessConfig) -> f32 {
+    // Digital silence has no loudness to correct; leave it alone
+    let (Some(lufs), Some(peak)) = (info.integrated_lufs, info.true_peak_dbtp) else {
+        return 0.0;
+    };
+    let to_target = config.target_lufs - lufs;
+    let to_ceiling = config.true_peak_ceiling - peak;
+    to_target.min(to_ceiling)
+}
+
+/// Run ffmpeg's loudnorm analysis pass and parse its JSON summary from stderr
+pub async fn measure(path: &Path) -> Result<LoudnessInfo> {
+    let mut cmd = shutdown::command("ffmpeg");
+    cmd.args(["-hide_banner", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-af", "loudnorm=print_format=json", "-f", "null", "-"]);
+
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|_| eyre!("ffmpeg not found. Please install: brew install ffmpeg"))?;
+
+    if !output.status.success() {
+        return Err(eyre!("ffmpeg could not measure {}", path.display()));
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (Some(start), Some(end)) = (stderr.rfind('{'), stderr.rfind('}')) else {
+        return Err(eyre!("No loudness summary in ffmpeg output"));
+    };
+    let summary: LoudnormSummary = serde_json::from_str(&stderr[start..=end])?;
+
+    Ok(LoudnessInfo {
+        integrated_lufs: parse_level(&summary.input_i)?,
+        true_peak_dbtp: parse_level(&summary.input_tp)?,
+        range_lu: parse_level(&summary.input_lra)?.unwrap_or_default(),
+        gain_db: 0.0,
+    })
+}
+
+/// A level from the loudnorm summary; none when it's -inf or otherwise not a finite number,
+/// which JSON (and so the history file) can't hold
+fn parse_level(value: &str) -> Result<Option<f32>> {
+    let level: f32 = value
+        .trim()
+        .parse()
+        .map_err(|_| eyre!("Unexpected loudness value '{}'", value))?;
+    Ok(level.is_finite().then_some(level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measured(lufs: Option<f32>, peak: Option<f32>) -> LoudnessInfo {
+        LoudnessInfo {
+            integrated_lufs: lufs,
+            true_peak_dbtp: peak,
+            range_lu: 6.0,
+            gain_db: 0.0,
+        }
+    }
+
+    #[test]
+    fn gain_reaches_the_target_without_passing_the_ceiling() {
+        let config = LoudnessConfig {
+            mode: LoudnessMode::Normalize,
+            target_lufs: -9.0,
+            true_peak_ceiling: -1.0,
+        };
+        // Plenty of headroom: all the way to the target
+        assert_eq!(
+            gain_to_target(&measured(Some(-14.0), Some(-6.0)), &config),
+            5.0
+        );
+        // The peak would clip first, so stop at the ceiling
+        assert_eq!(
+            gain_to_target(&measured(Some(-14.0), Some(-3.0)), &config),
+            2.0
+        );
+        // Too loud already: turn it down
+        assert_eq!(
+            gain_to_target(&measured(Some(-6.0), Some(-0.5)), &config),
+            -3.0
+        );
+        // Silence is left alone
+        assert_eq!(gain_to_target(&measured(None, None), &config), 0.0);
+    }
+
+    #[test]
+    fn silence_reads_as_no_level() {
+        assert_eq!(parse_level(" -inf ").unwrap(), None);
+        assert_eq!(parse_level("-14.52").unwrap(), Some(-14.52));
+        assert!(parse_level("loud").is_err());
+    }
+}
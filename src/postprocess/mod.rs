@@ -0,0 +1,221 @@
+//! Post-download processing run on a background task after `download_mp3`.
+
+use color_eyre::{Result, eyre::eyre};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tracing::{info, warn};
+
+use crate::analysis::fingerprint::{self, Fingerprint};
+use crate::analysis::quality::{self, QualityInfo};
+use crate::analysis::{self, TrackAnalysis};
+use crate::app::DownloadOptions;
+use crate::config::Config;
+use crate::shutdown;
+use crate::tags::{self, TagFields};
+use loudness::LoudnessInfo;
+use trim::TrimInfo;
+
+pub mod fade;
+pub mod loudness;
+pub mod rename;
+pub mod split;
+pub mod trim;
+
+/// Everything the background pipeline learned about a download
+#[derive(Debug, Clone, Default)]
+pub struct ProcessReport {
+    /// Silence removed from either end
+    pub trim: Option<TrimInfo>,
+    /// Loudness measured before any gain was applied
+    pub loudness: Option<LoudnessInfo>,
+    /// Source format and measured bandwidth
+    pub quality: Option<QualityInfo>,
+    /// Tempo and key analysis
+    pub analysis: TrackAnalysis,
+    /// Acoustic fingerprint, for duplicate detection
+    pub fingerprint: Option<Fingerprint>,
+    /// Where the file ended up after renaming, if it moved
+    pub renamed: Option<PathBuf>,
+    /// Tags as the finished file carries them
+    pub tags: Option<TagFields>,
+}
+
+/// Run every enabled stage on a finished download: editing stages first
+/// (they may re-encode the file), then analysis, which writes its own tags,
+/// and finally renaming, which may use the analysis
+pub async fn run(
+    path: PathBuf,
+    url: String,
+    config: Config,
+    options: DownloadOptions,
+) -> Result<ProcessReport> {
+    let mut report = ProcessReport::default();
+
+    // A failed editing stage shouldn't cost us the BPM and key
+    if options.trim_silence {
+        match trim::process(&path, &config.trim, options.bitrate).await {
+            Ok(info) => report.trim = Some(info),
+            Err(e) => warn!("Trim stage failed for {}: {}", path.display(), e),
+        }
+    }
+
+    if let Some(clip) = &options.clip {
+        if let Err(e) = fade::process(&path, &config.clip, options.bitrate).await {
+            warn!("Fade stage failed for {}: {}", path.display(), e);
+        }
+
+        let (tag_path, clip, url) = (path.clone(), clip.clone(), url.clone());
+        tokio::task::spawn_blocking(move || tags::write_clip(&tag_path, &clip, &url)).await??;
+    }
+
+    if config.loudness.enabled() {
+        match loudness::process(&path, &config.loudness, options.bitrate).await {
+            Ok(info) => report.loudness = Some(info),
+            Err(e) => warn!("Loudness stage failed for {}: {}", path.display(), e),
+        }
+    }
+
+    if config.quality.enabled {
+        match quality::check(&path, options.source.clone(), &config.quality).await {
+            Ok(info) => report.quality = Some(info),
+            Err(e) => warn!("Quality check failed for {}: {}", path.display(), e),
+        }
+    }
+
+    let notation = config.analysis.key_notation;
+    match analysis::analyze_track(path.clone(), config.analysis).await {
+        Ok(analysis) => report.analysis = analysis,
+        Err(e) => warn!("Analysis failed for {}: {}", path.display(), e),
+    }
+
+    if config.duplicates.enabled {
+        match fingerprint::fingerprint(&path).await {
+            Ok(fingerprint) => report.fingerprint = Some(fingerprint),
+            Err(e) => warn!("Fingerprinting failed for {}: {}", path.display(), e),
+        }
+    }
+
+    let named = rename::process(
+        &path,
+        &config.naming,
+        &report.analysis,
+        notation,
+        options.clip.as_ref(),
+    )
+    .await?;
+    let tag_path = named.clone();
+    match tokio::task::spawn_blocking(move || tags::read_fields(&tag_path)).await? {
+        Ok(fields) => report.tags = Some(fields),
+        Err(e) => warn!("Could not read tags back from {}: {}", named.display(), e),
+    }
+    report.renamed = (named != path).then_some(named);
+    Ok(report)
+}
+
+/// Duration of a media file in seconds, via ffprobe
+pub async fn probe_duration(path: &Path) -> Result<f64> {
+    let mut cmd = shutdown::command("ffprobe");
+    cmd.args([
+        "-v",
+        "error",
+        "-show_entries",
+        "format=duration",
+        "-of",
+        "default=noprint_wrappers=1:nokey=1",
+    ])
+    .arg(path);
+
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|_| eyre!("ffprobe not found. Please install: brew install ffmpeg"))?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|_| eyre!("Could not read the duration of {}", path.display()))
+}
+
+/// ffmpeg encoder arguments matching the file's current format
+pub fn encoder_args(path: &Path, bitrate: u32) -> Vec<String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("mp3")
+        .to_ascii_lowercase();
+    let bitrate_arg = format!("{bitrate}k");
+
+    let args: &[&str] = match ext.as_str() {
+        "m4a" | "aac" => &["-c:a", "aac", "-b:a", &bitrate_arg],
+        "opus" => &["-c:a", "libopus", "-b:a", &bitrate_arg],
+        "ogg" => &["-c:a", "libvorbis", "-b:a", &bitrate_arg],
+        "flac" => &["-c:a", "flac"],
+        "wav" => &["-c:a", "pcm_s16le"],
+        "aif" | "aiff" => &["-c:a", "pcm_s16be", "-write_id3v2", "1"],
+        _ => &["-c:a", "libmp3lame", "-b:a", &bitrate_arg],
+    };
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+/// ffmpeg's name for the container of a file with this extension, for output files whose
+/// own name doesn't say
+fn muxer(ext: &str) -> &'static str {
+    match ext {
+        "m4a" => "ipod",
+        "aac" => "adts",
+        "opus" => "opus",
+        "ogg" => "ogg",
+        "flac" => "flac",
+        "wav" => "wav",
+        "aif" | "aiff" => "aiff",
+        _ => "mp3",
+    }
+}
+
+/// Re-encode `path` in place through an ffmpeg audio filter, keeping tags and cover art
+pub async fn apply_filter(path: &Path, filter: &str, bitrate: u32) -> Result<()> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("mp3")
+        .to_ascii_lowercase();
+    // Not named like audio, so nothing watching the folder for new tracks mistakes it for one
+    let tmp = path.with_extension(format!("{ext}.dj-cli-tmp"));
+    // Don't leave a half-written file next to the original
+    let partial = shutdown::Partial::new(&tmp);
+
+    let mut cmd = shutdown::command("ffmpeg");
+    cmd.args(["-v", "error", "-nostdin", "-y", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-map", "0:v?", "-c:v", "copy"])
+        .args(["-map_metadata", "0", "-af", filter])
+        .args(encoder_args(path, bitrate))
+        .args(["-f", muxer(&ext)])
+        .arg(&tmp);
+
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let status = cmd
+        .status()
+        .await
+        .map_err(|_| eyre!("ffmpeg not found. Please install: brew install ffmpeg"))?;
+
+    if !status.success() {
+        return Err(eyre!(
+            "ffmpeg failed to apply '{}' to {}",
+            filter,
+            path.display()
+        ));
+    }
+
+    tokio::fs::rename(&tmp, path).await?;
+    partial.finish();
+    info!("Applied '{}' to {}", filter, path.display());
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! Final stage: give the file its templated, sanitised name.
+
+use color_eyre::{Result, eyre::eyre};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tracing::info;
+
+use crate::analysis::TrackAnalysis;
+use crate::clip::Clip;
+use crate::config::{KeyNotation, NamingConfig};
+use crate::naming;
+use crate::tags::{self, TagFields};
+use crate::tracklist;
+
+/// A trailing "(Extended Mix)", "[Club Edit]", "(Artist Remix)" and the like
+static MIX_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\s*[(\[]([^()\[\]]*\b(?:mix|edit|remix|version|dub|bootleg|rework|vip|remaster(?:ed)?)\b[^()\[\]]*)[)\]]\s*$",
+    )
+    .expect("valid mix regex")
+});
+
+/// Rename `path` according to the naming config and return where it ended up
+pub async fn process(
+    path: &Path,
+    config: &NamingConfig,
+    analysis: &TrackAnalysis,
+    notation: KeyNotation,
+    clip: Option<&Clip>,
+) -> Result<PathBuf> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| eyre!("Bad file name: {}", path.display()))?
+        .to_string();
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let name = match &config.template {
+        Some(template) => {
+            let tag_path = path.to_path_buf();
+            let fields =
+                tokio::task::spawn_blocking(move || tags::read_fields(&tag_path)).await??;
+            let mut name = naming::render(template, |field| {
+                value(field, &fields, &stem, analysis, notation)
+            });
+            // Keep clips apart from the full track they were cut from
+            if let Some(clip) = clip {
+                name.push_str(&format!(" [{}]", clip.file_label()));
+            }
+            name
+        }
+        // No template: keep the name yt-dlp gave it, just made safe
+        None => stem.clone(),
+    };
+
+    let name = naming::sanitize(&name, config.max_length);
+    let target = naming::unique_path(dir, &name, &ext, Some(path));
+    if target != path {
+        tokio::fs::rename(path, &target).await?;
+        info!("Renamed {} to {}", path.display(), target.display());
+    }
+    Ok(target)
+}
+
+/// Value of one file name placeholder
+fn value(
+    field: &str,
+    fields: &TagFields,
+    stem: &str,
+    analysis: &TrackAnalysis,
+    notation: KeyNotation,
+) -> Option<String> {
+    let full_title = fields.title.clone().unwrap_or_else(|| stem.to_string());
+    let (parsed_artist, track) = tracklist::parse_artist_title(&full_title);
+    let mix = MIX_RE
+        .captures(&track)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().trim().to_string());
+
+    let value = match field {
+        "artist" => parsed_artist.or_else(|| fields.artist.clone()),
+        "title" => Some(MIX_RE.replace(&track, "").trim().to_string()),
+        "mix" => mix,
+        "video_title" => Some(full_title),
+        "album" => fields.album.clone(),
+        "genre" => fields.genre.clone(),
+        "year" => fields.year.map(|year| year.to_string()),
+        "bpm" => analysis.bpm.map(|bpm| format!("{}", bpm.round() as u32)),
+        "key" => analysis.key.map(|key| key.format(notation)),
+        _ => None,
+    };
+    value.filter(|v| !v.trim().is_empty())
+}
@@ -0,0 +1,164 @@
+//! Cutting a long mix into one file per track.
+
+use color_eyre::{Result, eyre::eyre};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tracing::info;
+
+use crate::naming;
+use crate::shutdown;
+use crate::tags;
+use crate::tracklist::Segment;
+
+/// What to split a download into
+#[derive(Debug, Clone)]
+pub struct SplitPlan {
+    pub segments: Vec<Segment>,
+    /// Title of the source mix, used as the album tag
+    pub album: String,
+}
+
+impl SplitPlan {
+    /// The plan for the same mix with `seconds` cut off its start, as trimming does. Tracklist
+    /// and chapter times count from the start of the upload, so they move back by as much;
+    /// anything that now ends before the start is dropped.
+    pub fn shifted(&self, seconds: f64) -> SplitPlan {
+        let segments = self
+            .segments
+            .iter()
+            .filter(|segment| segment.end.is_none_or(|end| end > seconds))
+            .map(|segment| Segment {
+                start: (segment.start - seconds).max(0.0),
+                end: segment.end.map(|end| end - seconds),
+                ..segment.clone()
+            })
+            .collect();
+        SplitPlan {
+            segments,
+            album: self.album.clone(),
+        }
+    }
+}
+
+/// A file produced by splitting
+#[derive(Debug, Clone)]
+pub struct SplitTrack {
+    pub path: PathBuf,
+    pub filename: String,
+}
+
+/// Cut `path` into one tagged file per segment, in a folder named after the mix.
+/// Names are limited to `max_length` characters.
+pub async fn split(path: &Path, plan: &SplitPlan, max_length: usize) -> Result<Vec<SplitTrack>> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| eyre!("Bad file name: {}", path.display()))?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("mp3");
+    let dir = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(naming::sanitize(stem, max_length));
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let total = plan.segments.len();
+    let mut tracks = Vec::with_capacity(total);
+    for (index, segment) in plan.segments.iter().enumerate() {
+        let number = index + 1;
+        let label = match &segment.artist {
+            Some(artist) => format!("{artist} - {}", segment.title),
+            None => segment.title.clone(),
+        };
+        let name = naming::sanitize(&format!("{number:02} - {label}"), max_length);
+        let out = naming::unique_path(&dir, &name, ext, None);
+        let filename = out
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        cut(path, segment, &out).await?;
+
+        let (tag_path, segment, album) = (out.clone(), segment.clone(), plan.album.clone());
+        tokio::task::spawn_blocking(move || {
+            tags::write_track(&tag_path, &segment, &album, number, total)
+        })
+        .await??;
+
+        info!("Split track {}/{}: {}", number, total, out.display());
+        tracks.push(SplitTrack {
+            path: out,
+            filename,
+        });
+    }
+
+    Ok(tracks)
+}
+
+/// Copy one segment out of the source without re-encoding
+async fn cut(source: &Path, segment: &Segment, out: &Path) -> Result<()> {
+    let partial = shutdown::Partial::new(out);
+    let mut cmd = shutdown::command("ffmpeg");
+    cmd.args(["-v", "error", "-nostdin", "-y"])
+        .args(["-ss", &format!("{:.3}", segment.start)]);
+    if let Some(end) = segment.end {
+        cmd.args(["-t", &format!("{:.3}", end - segment.start)]);
+    }
+    cmd.arg("-i")
+        .arg(source)
+        .args(["-map", "0:a:0", "-map", "0:v?", "-c", "copy"])
+        .args(["-map_metadata", "0"])
+        .arg(out);
+
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let status = cmd
+        .status()
+        .await
+        .map_err(|_| eyre!("ffmpeg not found. Please install: brew install ffmpeg"))?;
+
+    if !status.success() {
+        return Err(eyre!("ffmpeg failed to cut '{}'", segment.title));
+    }
+    partial.finish();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, end: Option<f64>, title: &str) -> Segment {
+        Segment {
+            start,
+            end,
+            artist: None,
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn trimming_moves_the_cut_points_back() {
+        let plan = SplitPlan {
+            segments: vec![
+                segment(0.0, Some(1.5), "Silent intro"),
+                segment(1.5, Some(200.0), "First"),
+                segment(200.0, None, "Second"),
+            ],
+            album: "Mix".to_string(),
+        };
+
+        let shifted = plan.shifted(2.5);
+        let times: Vec<(f64, Option<f64>, &str)> = shifted
+            .segments
+            .iter()
+            .map(|s| (s.start, s.end, s.title.as_str()))
+            .collect();
+        assert_eq!(
+            times,
+            [(0.0, Some(197.5), "First"), (197.5, None, "Second")]
+        );
+        assert_eq!(plan.shifted(0.0).segments, plan.segments);
+    }
+}
@@ -0,0 +1,119 @@
+//! Leading/trailing silence detection and trimming.
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::info;
+
+use crate::analysis::{self, ANALYSIS_SAMPLE_RATE};
+use crate::config::TrimConfig;
+
+/// Window used to measure level, in seconds
+const WINDOW_SECONDS: f32 = 0.02;
+/// Don't bother re-encoding for less than this much silence
+const MIN_TRIM_SECONDS: f32 = 0.05;
+
+/// How much silence was cut from each end, in seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrimInfo {
+    pub leading: f32,
+    pub trailing: f32,
+}
+
+/// Detect silence at both ends of `path` and cut it off in place
+pub async fn process(path: &Path, config: &TrimConfig, bitrate: u32) -> Result<TrimInfo> {
+    let samples = analysis::decode_mono(path, ANALYSIS_SAMPLE_RATE).await?;
+    let duration = samples.len() as f32 / ANALYSIS_SAMPLE_RATE as f32;
+
+    let threshold = config.threshold_db;
+    let Some((start, end)) = tokio::task::spawn_blocking(move || {
+        detect_bounds(&samples, ANALYSIS_SAMPLE_RATE, threshold)
+    })
+    .await?
+    else {
+        // Entirely below the threshold - trimming would leave nothing
+        info!("{} is silent throughout, not trimming", path.display());
+        return Ok(TrimInfo::default());
+    };
+
+    let trim = TrimInfo {
+        leading: start,
+        trailing: (duration - end).max(0.0),
+    };
+    if trim.leading < MIN_TRIM_SECONDS && trim.trailing < MIN_TRIM_SECONDS {
+        return Ok(TrimInfo::default());
+    }
+
+    let filter = format!("atrim=start={start:.3}:end={end:.3},asetpts=PTS-STARTPTS");
+    super::apply_filter(path, &filter, bitrate).await?;
+    info!(
+        "Trimmed {:.2}s leading and {:.2}s trailing silence from {}",
+        trim.leading,
+        trim.trailing,
+        path.display()
+    );
+
+    Ok(trim)
+}
+
+/// First and last moment (in seconds) where the windowed level rises above `threshold_db`
+fn detect_bounds(samples: &[f32], sample_rate: u32, threshold_db: f32) -> Option<(f32, f32)> {
+    let window = ((sample_rate as f32 * WINDOW_SECONDS) as usize).max(1);
+    let threshold = 10f32.powf(threshold_db / 20.0);
+
+    let loud = |chunk: &[f32]| {
+        let rms = (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt();
+        rms > threshold
+    };
+
+    let chunks: Vec<&[f32]> = samples.chunks(window).collect();
+    let first = chunks.iter().position(|c| loud(c))?;
+    let last = chunks.iter().rposition(|c| loud(c))?;
+
+    let to_seconds = |chunk_index: usize| (chunk_index * window) as f32 / sample_rate as f32;
+    let end = to_seconds(last + 1).min(samples.len() as f32 / sample_rate as f32);
+    Some((to_seconds(first), end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 22050;
+
+    /// `silence` seconds of near-silence, `tone` seconds of a 440 Hz tone, then `tail` more silence
+    fn padded_tone(silence: f32, tone: f32, tail: f32) -> Vec<f32> {
+        let len = |seconds: f32| (seconds * RATE as f32) as usize;
+        // A faint hiss well under the threshold, like a real recording's noise floor
+        let hiss = |i: usize| if i.is_multiple_of(2) { 1e-4 } else { -1e-4 };
+        let mut out: Vec<f32> = (0..len(silence)).map(hiss).collect();
+        out.extend(
+            (0..len(tone))
+                .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / RATE as f32).sin()),
+        );
+        out.extend((0..len(tail)).map(hiss));
+        out
+    }
+
+    #[test]
+    fn finds_where_the_music_starts_and_stops() {
+        let (start, end) = detect_bounds(&padded_tone(1.5, 4.0, 2.0), RATE, -60.0).unwrap();
+        assert!((start - 1.5).abs() <= WINDOW_SECONDS, "start {start}");
+        assert!((end - 5.5).abs() <= WINDOW_SECONDS, "end {end}");
+    }
+
+    #[test]
+    fn music_to_the_edges_is_left_alone() {
+        let (start, end) = detect_bounds(&padded_tone(0.0, 3.0, 0.0), RATE, -60.0).unwrap();
+        assert_eq!(start, 0.0);
+        assert!((end - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn silence_has_no_bounds() {
+        assert_eq!(
+            detect_bounds(&padded_tone(3.0, 0.0, 0.0), RATE, -60.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Output folder routing: pick where a download lands from its resolved metadata.
+
+use std::path::PathBuf;
+
+use crate::config::{OutputConfig, RouteRule};
+use crate::naming;
+use crate::template;
+use crate::tracklist;
+use crate::ytdlp::VideoInfo;
+
+/// Shown in place of placeholders the metadata has no value for
+const UNKNOWN: &str = "Unknown";
+
+/// Longest folder name a template value turns into
+const MAX_FOLDER_CHARS: usize = 100;
+
+impl RouteRule {
+    /// Whether every condition set on the rule holds; a rule without conditions matches anything
+    pub fn matches(&self, url: &str, info: &VideoInfo) -> bool {
+        let same = |a: &str, b: &str| a.trim().eq_ignore_ascii_case(b.trim());
+
+        let source_ok = self.source.as_deref().is_none_or(|source| {
+            same(source, &source_name(url, info))
+                || url.to_lowercase().contains(&source.to_lowercase())
+        });
+        let genre_ok = self
+            .genre
+            .as_deref()
+            .is_none_or(|genre| info.genre.as_deref().is_some_and(|g| same(genre, g)));
+        let uploader_ok = self
+            .uploader
+            .as_deref()
+            .is_none_or(|uploader| info.channel().is_some_and(|c| same(uploader, c)));
+        let title_ok = self.title_contains.as_deref().is_none_or(|needle| {
+            info.title
+                .to_lowercase()
+                .contains(&needle.trim().to_lowercase())
+        });
+
+        source_ok && genre_ok && uploader_ok && title_ok
+    }
+}
+
+/// Folder for a download: the first matching route, or the default folder.
+/// Without metadata no route can match.
+pub fn output_dir(config: &OutputConfig, url: &str, info: Option<&VideoInfo>) -> PathBuf {
+    let base = expand_home(&config.dir);
+    let Some((route, info)) = info.and_then(|info| {
+        config
+            .routes
+            .iter()
+            .find(|route| route.matches(url, info))
+            .map(|route| (route, info))
+    }) else {
+        return base;
+    };
+
+    let dir = template::render(&route.dir, |name| {
+        // Values become single path components, so they must be valid folder names on any drive
+        let value = field(url, info, name).unwrap_or_else(|| UNKNOWN.to_string());
+        Some(naming::sanitize(&value, MAX_FOLDER_CHARS))
+    });
+
+    // Relative routes live under the default folder
+    let dir = expand_home(&dir);
+    if dir.is_absolute() {
+        dir
+    } else {
+        base.join(dir)
+    }
+}
+
+/// Value of one template placeholder
+pub fn field(url: &str, info: &VideoInfo, name: &str) -> Option<String> {
+    let value = match name {
+        "title" => Some(info.title.clone()),
+        "artist" => info
+            .artist
+            .clone()
+            .or_else(|| tracklist::parse_artist_title(&info.title).0)
+            .or_else(|| info.channel().map(str::to_string)),
+        "uploader" => info.channel().map(str::to_string),
+        "genre" => info.genre.clone(),
+        "album" => info.album.clone(),
+        "source" => Some(source_name(url, info)),
+        "year" => info
+            .release_year
+            .map(|year| year.to_string())
+            .or_else(|| Some(info.upload_date()?.get(..4)?.to_string())),
+        "month" => Some(info.upload_date()?.get(5..7)?.to_string()),
+        "day" => Some(info.upload_date()?.get(8..10)?.to_string()),
+        "date" => info.upload_date(),
+        _ => None,
+    };
+    value.filter(|v| !v.trim().is_empty())
+}
+
+/// Lower-case site name, e.g. "youtube" or "soundcloud"
+fn source_name(url: &str, info: &VideoInfo) -> String {
+    match &info.extractor_key {
+        Some(key) => key.to_lowercase(),
+        None if url.contains("soundcloud.com") => "soundcloud".to_string(),
+        None => "youtube".to_string(),
+    }
+}
+
+/// Expand a leading `~` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    let home = || std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    match path.strip_prefix('~') {
+        Some("") => PathBuf::from(home()),
+        Some(rest) if rest.starts_with('/') => PathBuf::from(home()).join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YOUTUBE: &str = "https://www.youtube.com/watch?v=abc";
+    const SOUNDCLOUD: &str = "https://soundcloud.com/someone/edit";
+
+    fn rule(dir: &str) -> RouteRule {
+        RouteRule {
+            source: None,
+            genre: None,
+            uploader: None,
+            title_contains: None,
+            dir: dir.to_string(),
+        }
+    }
+
+    fn info() -> VideoInfo {
+        VideoInfo {
+            title: "Kerri Chandler - Rain (Club Remix)".to_string(),
+            channel: Some("Deep House Archive".to_string()),
+            genre: Some("House".to_string()),
+            upload_date: Some("20240315".to_string()),
+            extractor_key: Some("Youtube".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rule_conditions() {
+        let info = info();
+        assert!(rule("x").matches(YOUTUBE, &info));
+
+        let genre = RouteRule {
+            genre: Some(" house ".to_string()),
+            ..rule("x")
+        };
+        assert!(genre.matches(YOUTUBE, &info));
+
+        let title = RouteRule {
+            title_contains: Some("remix".to_string()),
+            uploader: Some("deep house archive".to_string()),
+            ..rule("x")
+        };
+        assert!(title.matches(YOUTUBE, &info));
+
+        // Every condition has to hold
+        let wrong_uploader = RouteRule {
+            uploader: Some("Someone Else".to_string()),
+            ..title
+        };
+        assert!(!wrong_uploader.matches(YOUTUBE, &info));
+    }
+
+    #[test]
+    fn source_matches_site_or_url() {
+        let soundcloud = RouteRule {
+            source: Some("SoundCloud".to_string()),
+            ..rule("x")
+        };
+        assert!(!soundcloud.matches(YOUTUBE, &info()));
+
+        let resolved = VideoInfo {
+            extractor_key: Some("Soundcloud".to_string()),
+            ..info()
+        };
+        assert!(soundcloud.matches(SOUNDCLOUD, &resolved));
+
+        // Without an extractor the site comes from the URL
+        let unresolved = VideoInfo {
+            extractor_key: None,
+            ..info()
+        };
+        assert!(soundcloud.matches(SOUNDCLOUD, &unresolved));
+
+        let part_of_url = RouteRule {
+            source: Some("someone/".to_string()),
+            ..rule("x")
+        };
+        assert!(part_of_url.matches(SOUNDCLOUD, &resolved));
+    }
+
+    #[test]
+    fn template_fields() {
+        let info = info();
+        assert_eq!(
+            field(YOUTUBE, &info, "artist").as_deref(),
+            Some("Kerri Chandler")
+        );
+        assert_eq!(field(YOUTUBE, &info, "source").as_deref(), Some("youtube"));
+        assert_eq!(field(YOUTUBE, &info, "year").as_deref(), Some("2024"));
+        assert_eq!(field(YOUTUBE, &info, "month").as_deref(), Some("03"));
+        assert_eq!(field(YOUTUBE, &info, "date").as_deref(), Some("2024-03-15"));
+        assert_eq!(field(YOUTUBE, &info, "album"), None);
+        assert_eq!(field(YOUTUBE, &info, "nonsense"), None);
+
+        let released = VideoInfo {
+            release_year: Some(1998),
+            ..info
+        };
+        assert_eq!(field(YOUTUBE, &released, "year").as_deref(), Some("1998"));
+    }
+
+    #[test]
+    fn first_matching_route_picks_the_folder() {
+        let config = OutputConfig {
+            dir: "/music".to_string(),
+            routes: vec![
+                RouteRule {
+                    genre: Some("Techno".to_string()),
+                    ..rule("/techno")
+                },
+                RouteRule {
+                    genre: Some("House".to_string()),
+                    ..rule("House/{year}/{album}")
+                },
+                rule("/everything"),
+            ],
+        };
+        let info = info();
+        assert_eq!(
+            output_dir(&config, YOUTUBE, Some(&info)),
+            PathBuf::from("/music/House/2024/Unknown")
+        );
+        assert_eq!(output_dir(&config, YOUTUBE, None), PathBuf::from("/music"));
+    }
+
+    #[test]
+    fn template_values_become_valid_folder_names() {
+        let config = OutputConfig {
+            dir: "/music".to_string(),
+            routes: vec![rule("{uploader}")],
+        };
+        let info = VideoInfo {
+            channel: Some("AC/DC: Live? <Official>.".to_string()),
+            ..info()
+        };
+        assert_eq!(
+            output_dir(&config, YOUTUBE, Some(&info)),
+            PathBuf::from("/music/AC-DC- Live Official")
+        );
+    }
+
+    #[test]
+    fn expands_home() {
+        let home = PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()));
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/Music/House"), home.join("Music/House"));
+        // Other users' homes and absolute or relative paths are left alone
+        assert_eq!(expand_home("~dj/Music"), PathBuf::from("~dj/Music"));
+        assert_eq!(expand_home("/srv/music"), PathBuf::from("/srv/music"));
+        assert_eq!(expand_home("Remixes"), PathBuf::from("Remixes"));
+    }
+}
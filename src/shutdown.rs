@@ -0,0 +1,166 @@
+//! Ending the app cleanly, however it ends. Child processes die with the task that started
+//! them, files they were half-way through writing are removed, and the terminal is put back
+//! on every way out: quitting, a signal, an error or a panic.
+
+use color_eyre::{Result, eyre::eyre};
+use futures::FutureExt;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+/// A command for a child process (yt-dlp, ffmpeg, ffprobe). Dropping the task waiting on it
+/// kills the process, so aborting background work on quit doesn't leave it running.
+pub fn command(program: &str) -> tokio::process::Command {
+    let mut cmd = tokio::process::Command::new(program);
+    cmd.kill_on_drop(true);
+    cmd
+}
+
+/// A file or folder a child process is writing. It's removed when the guard drops unless the
+/// work got as far as `finish`, which covers both failures and tasks aborted on quit.
+#[derive(Debug)]
+pub struct Partial(Option<PathBuf>);
+
+impl Partial {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self(Some(path.into()))
+    }
+
+    /// The work is done; leave the file where it is
+    pub fn finish(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for Partial {
+    fn drop(&mut self) {
+        let Some(path) = self.0.take() else {
+            return;
+        };
+        let removed = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match removed {
+            Ok(()) => info!("Removed unfinished {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not remove unfinished {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Put the terminal back the way it was found
+pub fn restore_terminal(mouse: bool) {
+    if mouse {
+        let _ = crossterm::execute!(std::io::stdout(), crossterm::event::DisableMouseCapture);
+    }
+    let _ = crossterm::execute!(std::io::stdout(), crossterm::event::DisableBracketedPaste);
+    ratatui::restore();
+}
+
+/// Restore the terminal before a panic message prints, so it's readable and the shell
+/// isn't left in raw mode. Only a panic on the main thread, which runs the UI, ends the app;
+/// one in a background task is logged and reported by `catch_panic` while the UI carries on.
+pub fn install_panic_hook(mouse: bool) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic| {
+        if std::thread::current().name() == Some("main") {
+            restore_terminal(mouse);
+            previous(panic);
+        } else {
+            error!("Background task panicked: {}", panic);
+        }
+    }));
+}
+
+/// Run background work, turning a panic into an error so the job reports a failure
+/// instead of leaving the UI waiting on it forever
+pub async fn catch_panic<T>(work: impl Future<Output = Result<T>>) -> Result<T> {
+    match AssertUnwindSafe(work).catch_unwind().await {
+        Ok(result) => result,
+        Err(panic) => Err(eyre!("internal error: {}", panic_message(panic.as_ref()))),
+    }
+}
+
+/// The text a panic was raised with
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// SIGTERM, SIGHUP and SIGINT, for the UI loop to select on. Ctrl+C in the app arrives as a
+/// key press in raw mode; SIGINT here is one sent from outside.
+pub struct Signals {
+    #[cfg(unix)]
+    term: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    pub fn new() -> Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+            Ok(Self {
+                term: signal(SignalKind::terminate())?,
+                hangup: signal(SignalKind::hangup())?,
+                interrupt: signal(SignalKind::interrupt())?,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// The name of the next signal to arrive; never resolves where there are none to wait for
+    pub async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.term.recv() => "SIGTERM",
+                _ = self.hangup.recv() => "SIGHUP",
+                _ = self.interrupt.recv() => "SIGINT",
+            }
+        }
+        #[cfg(not(unix))]
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_files_go_unless_finished() {
+        let dir = std::env::temp_dir().join(format!("dj-cli-partial-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("work")).unwrap();
+        std::fs::write(dir.join("work/track.webm.part"), b"half").unwrap();
+        std::fs::write(dir.join("done.mp3"), b"whole").unwrap();
+
+        drop(Partial::new(dir.join("work")));
+        assert!(!dir.join("work").exists());
+        Partial::new(dir.join("done.mp3")).finish();
+        assert!(dir.join("done.mp3").exists());
+        // Already gone is fine
+        drop(Partial::new(dir.join("missing.mp3")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_panicking_job_fails_instead_of_vanishing() {
+        let result: Result<()> = catch_panic(async { panic!("no tracks in {}", "mix.mp3") }).await;
+        let error = result.unwrap_err().to_string();
+        assert_eq!(error, "internal error: no tracks in mix.mp3");
+
+        assert_eq!(catch_panic(async { Ok(7) }).await.unwrap(), 7);
+    }
+}
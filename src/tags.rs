@@ -0,0 +1,171 @@
+use color_eyre::Result;
+use id3::frame::{Comment, ExtendedText};
+use id3::{Tag, TagLike, Version};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::info;
+
+use crate::analysis::TrackAnalysis;
+use crate::analysis::quality::QualityInfo;
+use crate::clip::Clip;
+use crate::config::KeyNotation;
+use crate::postprocess::loudness::LoudnessInfo;
+use crate::tracklist::Segment;
+
+/// Read the existing ID3 tag (or start a fresh one), apply `edit` and write it back
+fn update_tag(path: &Path, edit: impl FnOnce(&mut Tag)) -> Result<()> {
+    let existing = id3::no_tag_ok(Tag::read_from_path(path))?;
+    let version = existing
+        .as_ref()
+        .map(|tag| tag.version())
+        .unwrap_or(Version::Id3v24);
+    let mut tag = existing.unwrap_or_default();
+
+    edit(&mut tag);
+    tag.write_to_path(path, version)?;
+    Ok(())
+}
+
+/// The text tags a file name template or a search can draw on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagFields {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<i32>,
+    /// Every comment frame, one per line
+    pub comment: Option<String>,
+}
+
+/// Read title, artist, album, genre, year and comments from the file's tag, if it has one
+pub fn read_fields(path: &Path) -> Result<TagFields> {
+    let Some(tag) = id3::no_tag_ok(Tag::read_from_path(path))? else {
+        return Ok(TagFields::default());
+    };
+    Ok(TagFields {
+        title: tag.title().map(str::to_string),
+        artist: tag.artist().map(str::to_string),
+        album: tag.album().map(str::to_string),
+        genre: tag.genre_parsed().map(|g| g.into_owned()),
+        year: tag
+            .year()
+            .or_else(|| tag.date_recorded().map(|date| date.year)),
+        comment: Some(
+            tag.comments()
+                .map(|c| c.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+        .filter(|text| !text.is_empty()),
+    })
+}
+
+/// Write analysis results: tempo to TBPM (DJ software expects a whole number) and key to TKEY
+pub fn write_analysis(path: &Path, analysis: &TrackAnalysis, notation: KeyNotation) -> Result<()> {
+    let bpm = analysis.bpm.map(|bpm| (bpm.round() as u32).to_string());
+    let key = analysis.key.map(|key| key.format(notation));
+    if bpm.is_none() && key.is_none() {
+        return Ok(());
+    }
+
+    info!(
+        "Writing TBPM={:?} TKEY={:?} to {}",
+        bpm,
+        key,
+        path.display()
+    );
+    update_tag(path, |tag| {
+        if let Some(bpm) = bpm {
+            tag.set_text("TBPM", bpm);
+        }
+        if let Some(key) = key {
+            tag.set_text("TKEY", key);
+        }
+    })
+}
+
+/// Write ReplayGain track gain and peak as TXXX frames, the form most players and DJ software read
+pub fn write_replaygain(path: &Path, info: &LoudnessInfo) -> Result<()> {
+    let gain = format!("{:.2} dB", info.gain_db);
+    // ReplayGain peaks are linear amplitude, not dB
+    let peak = format!(
+        "{:.6}",
+        info.true_peak_dbtp
+            .map_or(0.0, |dbtp| 10f32.powf(dbtp / 20.0))
+    );
+    info!(
+        "Writing REPLAYGAIN_TRACK_GAIN={} REPLAYGAIN_TRACK_PEAK={} to {}",
+        gain,
+        peak,
+        path.display()
+    );
+    update_tag(path, |tag| {
+        tag.add_frame(ExtendedText {
+            description: "REPLAYGAIN_TRACK_GAIN".to_string(),
+            value: gain,
+        });
+        tag.add_frame(ExtendedText {
+            description: "REPLAYGAIN_TRACK_PEAK".to_string(),
+            value: peak,
+        });
+    })
+}
+
+/// Note the source format and measured bandwidth in a comment DJ software shows alongside the track
+pub fn write_quality(path: &Path, quality: &QualityInfo) -> Result<()> {
+    let summary = quality.summary();
+    info!(
+        "Writing quality comment '{}' to {}",
+        summary,
+        path.display()
+    );
+    update_tag(path, |tag| {
+        tag.add_frame(Comment {
+            lang: "eng".to_string(),
+            description: "Quality".to_string(),
+            text: summary,
+        });
+    })
+}
+
+/// Retitle a clip so it stands apart from the full track, and note where it came from
+pub fn write_clip(path: &Path, clip: &Clip, url: &str) -> Result<()> {
+    let label = clip.label();
+    update_tag(path, |tag| {
+        let title = match &clip.title {
+            Some(chapter) => chapter.clone(),
+            None => match tag.title() {
+                Some(original) => format!("{original} ({label})"),
+                None => format!("Clip ({label})"),
+            },
+        };
+        info!("Tagging clip {} as '{}'", path.display(), title);
+        tag.set_title(title);
+        tag.add_frame(Comment {
+            lang: "eng".to_string(),
+            description: "Clip".to_string(),
+            text: format!("{label} of {url}"),
+        });
+    })
+}
+
+/// Tag one track cut out of a mix: artist/title from the tracklist, the mix as album
+pub fn write_track(
+    path: &Path,
+    segment: &Segment,
+    album: &str,
+    number: usize,
+    total: usize,
+) -> Result<()> {
+    update_tag(path, |tag| {
+        if let Some(artist) = &segment.artist {
+            tag.set_artist(artist.clone());
+        }
+        tag.set_title(segment.title.clone());
+        tag.set_album(album);
+        tag.set_track(number as u32);
+        tag.set_total_tracks(total as u32);
+    })
+}
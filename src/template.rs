@@ -0,0 +1,23 @@
+//! `{placeholder}` templates used for output folders and file names.
+
+/// Fill each `{name}` in `template` from `lookup`. Placeholders `lookup` doesn't know render
+/// empty; a `{` without a closing `}` is kept as written.
+pub fn render(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            out.push_str(&rest[open..]);
+            return out;
+        };
+        let name = after[..close].trim();
+        out.push_str(&lookup(name).unwrap_or_default());
+        rest = &after[close + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,385 @@
+//! Colours for the TUI. A theme gives each role in the interface (text, accents, the
+//! focused pane, warnings...) a style; the built-in ones can be tweaked or replaced in the
+//! config. Colours are then fitted to what the terminal can show, and `NO_COLOR` turns
+//! them off altogether.
+
+use ratatui::style::{Color, Modifier, Style};
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::config::{ColorMode, ThemeConfig, UiConfig};
+
+/// Built-in theme names, as the config refers to them
+const BUILT_IN: [&str; 4] = ["dark", "light", "high-contrast", "monochrome"];
+
+/// A style for each role in the interface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// The logo
+    pub banner: Style,
+    /// Ordinary text
+    pub text: Style,
+    /// Labels, hints and unfocused borders
+    pub muted: Style,
+    /// Headings, overlay borders and analysis results
+    pub accent: Style,
+    /// The focused pane and input, and work in progress
+    pub focus: Style,
+    /// Finished downloads and ticks
+    pub success: Style,
+    /// Problems worth a look
+    pub warning: Style,
+    /// Highlighted row in the focused list
+    pub selection: Style,
+    /// Highlighted row in other lists
+    pub selection_inactive: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    /// Bright colours on a dark background
+    pub fn dark() -> Self {
+        let fg = |r, g, b| Style::default().fg(Color::Rgb(r, g, b));
+        Self {
+            banner: fg(0, 255, 0).add_modifier(Modifier::BOLD),
+            text: fg(255, 255, 255),
+            muted: fg(128, 128, 128),
+            accent: fg(0, 255, 255),
+            focus: fg(255, 255, 0),
+            success: fg(0, 255, 0),
+            warning: fg(255, 128, 0),
+            selection: Style::default().bg(Color::Rgb(64, 64, 64)),
+            selection_inactive: Style::default().bg(Color::Rgb(32, 32, 32)),
+        }
+    }
+
+    /// Darker colours that stay readable on a white background
+    pub fn light() -> Self {
+        let fg = |r, g, b| Style::default().fg(Color::Rgb(r, g, b));
+        Self {
+            banner: fg(0, 120, 0).add_modifier(Modifier::BOLD),
+            text: Style::default(),
+            muted: fg(110, 110, 110),
+            accent: fg(0, 110, 140),
+            focus: fg(170, 90, 0),
+            success: fg(0, 120, 0),
+            warning: fg(190, 60, 0),
+            selection: Style::default().bg(Color::Rgb(200, 200, 200)),
+            selection_inactive: Style::default().bg(Color::Rgb(230, 230, 230)),
+        }
+    }
+
+    /// The 16 basic colours at full strength, with a reversed highlight
+    pub fn high_contrast() -> Self {
+        let fg = |color| Style::default().fg(color);
+        Self {
+            banner: fg(Color::LightGreen).add_modifier(Modifier::BOLD),
+            text: fg(Color::White),
+            muted: fg(Color::Gray),
+            accent: fg(Color::LightCyan).add_modifier(Modifier::BOLD),
+            focus: fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+            success: fg(Color::LightGreen),
+            warning: fg(Color::LightRed).add_modifier(Modifier::BOLD),
+            selection: Style::default().fg(Color::Black).bg(Color::LightYellow),
+            selection_inactive: Style::default().fg(Color::Black).bg(Color::Gray),
+        }
+    }
+
+    /// No colours at all; bold, dim and reverse video do the work
+    pub fn monochrome() -> Self {
+        let plain = Style::default();
+        Self {
+            banner: plain.add_modifier(Modifier::BOLD),
+            text: plain,
+            muted: plain.add_modifier(Modifier::DIM),
+            accent: plain.add_modifier(Modifier::BOLD),
+            focus: plain.add_modifier(Modifier::BOLD),
+            success: plain,
+            warning: plain.add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            selection: plain.add_modifier(Modifier::REVERSED),
+            selection_inactive: plain.add_modifier(Modifier::UNDERLINED),
+        }
+    }
+
+    /// A built-in theme by name
+    pub fn built_in(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" | "high_contrast" => Some(Self::high_contrast()),
+            "monochrome" | "mono" => Some(Self::monochrome()),
+            _ => None,
+        }
+    }
+
+    /// The theme the config picks, fitted to this terminal
+    pub fn load(config: &UiConfig) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let depth = match config.colors {
+            ColorMode::Auto => detect_depth(),
+            mode => mode,
+        };
+        Self::resolve(config, no_color, depth)
+    }
+
+    /// The configured theme with user overrides, then reduced to `depth` colours;
+    /// `no_color` wins over everything
+    pub fn resolve(config: &UiConfig, no_color: bool, depth: ColorMode) -> Self {
+        if no_color || depth == ColorMode::None {
+            return Self::monochrome();
+        }
+        let theme = Self::named(&config.theme, config, 0);
+        theme.fit(depth)
+    }
+
+    /// A built-in or user theme; user themes may build on another theme
+    fn named(name: &str, config: &UiConfig, depth: usize) -> Self {
+        if let Some(user) = config.themes.get(name) {
+            // A theme can't be its own base, however indirectly
+            let base = match &user.base {
+                Some(base) if depth < 8 => Self::named(base, config, depth + 1),
+                _ => Self::dark(),
+            };
+            return base.with_overrides(user);
+        }
+        Self::built_in(name).unwrap_or_else(|| {
+            warn!(
+                "Unknown theme '{}', using dark (built in: {})",
+                name,
+                BUILT_IN.join(", ")
+            );
+            Self::dark()
+        })
+    }
+
+    /// Replace the colours a user theme sets; anything it leaves out comes from `self`
+    fn with_overrides(mut self, user: &ThemeConfig) -> Self {
+        let foregrounds = [
+            (&mut self.banner, &user.banner),
+            (&mut self.text, &user.text),
+            (&mut self.muted, &user.muted),
+            (&mut self.accent, &user.accent),
+            (&mut self.focus, &user.focus),
+            (&mut self.success, &user.success),
+            (&mut self.warning, &user.warning),
+        ];
+        for (style, value) in foregrounds {
+            if let Some(color) = value.as_deref().and_then(parse_color) {
+                *style = style.fg(color);
+            }
+        }
+        let backgrounds = [
+            (&mut self.selection, &user.selection),
+            (&mut self.selection_inactive, &user.selection_inactive),
+        ];
+        for (style, value) in backgrounds {
+            if let Some(color) = value.as_deref().and_then(parse_color) {
+                *style = style.bg(color);
+            }
+        }
+        self
+    }
+
+    /// Swap colours the terminal can't show for the nearest ones it can
+    fn fit(self, depth: ColorMode) -> Self {
+        let style = |style: Style| Style {
+            fg: style.fg.map(|c| reduce(c, depth)),
+            bg: style.bg.map(|c| reduce(c, depth)),
+            ..style
+        };
+        Self {
+            banner: style(self.banner),
+            text: style(self.text),
+            muted: style(self.muted),
+            accent: style(self.accent),
+            focus: style(self.focus),
+            success: style(self.success),
+            warning: style(self.warning),
+            selection: style(self.selection),
+            selection_inactive: style(self.selection_inactive),
+        }
+    }
+
+    /// Border for a pane or list, picked out while it has the focus
+    pub fn border(&self, focused: bool) -> Style {
+        if focused { self.focus } else { self.muted }
+    }
+
+    /// Row highlight, stronger in the focused list
+    pub fn highlight(&self, focused: bool) -> Style {
+        let style = if focused {
+            self.selection
+        } else {
+            self.selection_inactive
+        };
+        style.add_modifier(Modifier::BOLD)
+    }
+}
+
+/// "#rrggbb", a colour name ("yellow", "lightcyan") or a 256-colour index
+fn parse_color(value: &str) -> Option<Color> {
+    let color = Color::from_str(value.trim()).ok();
+    if color.is_none() {
+        warn!("Ignoring unknown colour '{}' in theme", value);
+    }
+    color
+}
+
+/// How many colours the terminal shows, going by the environment
+fn detect_depth() -> ColorMode {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    let term = std::env::var("TERM").unwrap_or_default();
+    if colorterm.contains("truecolor") || colorterm.contains("24bit") {
+        ColorMode::Truecolor
+    } else if term == "dumb" {
+        ColorMode::None
+    } else if term.contains("256") {
+        ColorMode::Ansi256
+    } else {
+        ColorMode::Ansi16
+    }
+}
+
+/// xterm's values for the 16 basic colours
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+/// Channel levels of the 6x6x6 cube in the 256-colour palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The nearest colour at `depth`; only true colours need reducing
+fn reduce(color: Color, depth: ColorMode) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    match depth {
+        ColorMode::Auto | ColorMode::Truecolor => color,
+        ColorMode::Ansi256 => Color::Indexed(nearest_256(r, g, b)),
+        ColorMode::Ansi16 => nearest_16(r, g, b),
+        ColorMode::None => Color::Reset,
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_16(r: u8, g: u8, b: u8) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// Index of the nearest cube colour or grey in the 256-colour palette
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, l)| (i32::from(**l) - i32::from(v)).abs())
+            .map(|(i, _)| i as u8)
+            .unwrap_or_default()
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (
+        CUBE_LEVELS[ri as usize],
+        CUBE_LEVELS[gi as usize],
+        CUBE_LEVELS[bi as usize],
+    );
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // The grey ramp runs from 8 to 238 in steps of 10
+    let average = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let step = (average.saturating_sub(3) / 10).min(23);
+    let grey = 8 + 10 * step;
+    if distance((grey, grey, grey), (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + step
+    } else {
+        cube_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn colours_fit_the_terminal() {
+        assert_eq!(
+            reduce(Color::Rgb(255, 255, 0), ColorMode::Ansi16),
+            Color::LightYellow
+        );
+        assert_eq!(
+            reduce(Color::Rgb(128, 128, 128), ColorMode::Ansi16),
+            Color::DarkGray
+        );
+        assert_eq!(
+            reduce(Color::Rgb(0, 255, 255), ColorMode::Ansi256),
+            Color::Indexed(51)
+        );
+        assert_eq!(
+            reduce(Color::Rgb(64, 64, 64), ColorMode::Ansi256),
+            Color::Indexed(238)
+        );
+        assert_eq!(reduce(Color::Yellow, ColorMode::Ansi256), Color::Yellow);
+        assert_eq!(
+            reduce(Color::Rgb(1, 2, 3), ColorMode::Truecolor),
+            Color::Rgb(1, 2, 3)
+        );
+    }
+
+    #[test]
+    fn user_themes_override_their_base() {
+        let theme = ThemeConfig {
+            base: Some("light".to_string()),
+            accent: Some("#ff00ff".to_string()),
+            selection: Some("blue".to_string()),
+            focus: Some("not a colour".to_string()),
+            ..ThemeConfig::default()
+        };
+        let config = UiConfig {
+            theme: "mine".to_string(),
+            themes: HashMap::from([("mine".to_string(), theme)]),
+            ..UiConfig::default()
+        };
+        let resolved = Theme::resolve(&config, false, ColorMode::Truecolor);
+        assert_eq!(resolved.accent.fg, Some(Color::Rgb(255, 0, 255)));
+        assert_eq!(resolved.selection.bg, Some(Color::Blue));
+        assert_eq!(resolved.focus, Theme::light().focus);
+        assert_eq!(resolved.text, Theme::light().text);
+    }
+
+    #[test]
+    fn no_color_turns_colours_off() {
+        let config = UiConfig::default();
+        let theme = Theme::resolve(&config, true, ColorMode::Truecolor);
+        assert_eq!(theme, Theme::monochrome());
+        for style in [theme.text, theme.accent, theme.selection, theme.warning] {
+            assert_eq!((style.fg, style.bg), (None, None));
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Timestamped tracklists: parsing from pasted text or video descriptions,
+//! and turning chapters into split points.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::clip::parse_time;
+use crate::ytdlp::Chapter;
+
+/// Timestamp at the start of a line: `[01] 12:34 Artist - Title`
+static LEADING_TIME_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(?:\d{1,3}[.)]\s*)?[\[(]?((?:\d{1,2}:)?\d{1,2}:\d{2})[\])]?\s*[-–—.:|]?\s*(.+?)\s*$",
+    )
+    .expect("valid leading timestamp regex")
+});
+
+/// Timestamp at the end of a line: `01. Artist - Title (12:34)`
+static TRAILING_TIME_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(?:\d{1,3}[.)]\s*)?(.+?)\s*[-–—|]?\s*[\[(]?((?:\d{1,2}:)?\d{1,2}:\d{2})[\])]?\s*$",
+    )
+    .expect("valid trailing timestamp regex")
+});
+
+/// Track-number prefixes left over in chapter titles: `01.`, `1)`, `#3`
+static NUMBER_PREFIX_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*#?\d{1,3}[.)]?\s+").expect("valid number prefix regex"));
+
+/// One track inside a longer mix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// Start in seconds
+    pub start: f64,
+    /// End in seconds, or `None` to run to the end of the file
+    pub end: Option<f64>,
+    pub artist: Option<String>,
+    pub title: String,
+}
+
+/// Parse a timestamped tracklist; returns nothing unless at least two lines have timestamps
+pub fn parse(text: &str) -> Vec<Segment> {
+    let mut starts: Vec<(f64, String)> = text
+        .lines()
+        .filter_map(|line| {
+            let (time, label) = if let Some(c) = LEADING_TIME_RE.captures(line) {
+                (c.get(1)?.as_str(), c.get(2)?.as_str())
+            } else {
+                let c = TRAILING_TIME_RE.captures(line)?;
+                (c.get(2)?.as_str(), c.get(1)?.as_str())
+            };
+            Some((parse_time(time)?, label.to_string()))
+        })
+        .collect();
+
+    if starts.len() < 2 {
+        return Vec::new();
+    }
+
+    starts.sort_by(|a, b| a.0.total_cmp(&b.0));
+    starts.dedup_by(|a, b| a.0 == b.0);
+
+    // Each track runs until the next one starts; the last runs to the end
+    let ends: Vec<Option<f64>> = starts
+        .iter()
+        .skip(1)
+        .map(|(start, _)| Some(*start))
+        .chain(std::iter::once(None))
+        .collect();
+
+    starts
+        .into_iter()
+        .zip(ends)
+        .map(|((start, label), end)| segment(start, end, &label))
+        .collect()
+}
+
+/// Split points from the video's chapter markers
+pub fn from_chapters(chapters: &[Chapter]) -> Vec<Segment> {
+    chapters
+        .iter()
+        .map(|c| segment(c.start_time, Some(c.end_time), &c.title))
+        .collect()
+}
+
+/// Build a segment, parsing artist and title out of its label
+fn segment(start: f64, end: Option<f64>, label: &str) -> Segment {
+    let (artist, title) = parse_artist_title(label);
+    Segment {
+        start,
+        end,
+        artist,
+        title,
+    }
+}
+
+/// Split "Artist - Title" (any dash style), dropping a leading track number
+pub fn parse_artist_title(label: &str) -> (Option<String>, String) {
+    let label = NUMBER_PREFIX_RE.replace(label, "");
+    let label = label.trim();
+
+    for separator in [" - ", " – ", " — ", " -- "] {
+        if let Some((artist, title)) = label.split_once(separator) {
+            let (artist, title) = (artist.trim(), title.trim());
+            if !artist.is_empty() && !title.is_empty() {
+                return (Some(artist.to_string()), title.to_string());
+            }
+        }
+    }
+
+    (None, label.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (start, end, artist, title) of each segment
+    fn summary(segments: &[Segment]) -> Vec<(f64, Option<f64>, Option<&str>, &str)> {
+        segments
+            .iter()
+            .map(|s| (s.start, s.end, s.artist.as_deref(), s.title.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_leading_timestamps() {
+        let text = "Tracklist:\n\
+                    00:00 Intro\n\
+                    [03:15] Artist One - First Track\n\
+                    07:40 - Artist Two – Second Track\n\
+                    Thanks for listening!";
+        assert_eq!(
+            summary(&parse(text)),
+            [
+                (0.0, Some(195.0), None, "Intro"),
+                (195.0, Some(460.0), Some("Artist One"), "First Track"),
+                (460.0, None, Some("Artist Two"), "Second Track"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_trailing_timestamps() {
+        let text = "01. Artist One - First Track (0:00)\n\
+                    02. Artist Two - Second Track [4:05]\n\
+                    03. Artist Three - Third Track - 9:30";
+        assert_eq!(
+            summary(&parse(text)),
+            [
+                (0.0, Some(245.0), Some("Artist One"), "First Track"),
+                (245.0, Some(570.0), Some("Artist Two"), "Second Track"),
+                (570.0, None, Some("Artist Three"), "Third Track"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_hour_long_mixes() {
+        let text = "58:00 Artist One - Late One\n\
+                    1:02:30 Artist Two - Later One\n\
+                    1:10:05 Artist Three - Closer";
+        let segments = parse(text);
+        assert_eq!(
+            segments.iter().map(|s| s.start).collect::<Vec<_>>(),
+            [3480.0, 3750.0, 4205.0]
+        );
+        assert_eq!(segments[1].end, Some(4205.0));
+    }
+
+    #[test]
+    fn needs_two_timestamped_lines() {
+        assert!(parse("Great mix! Check out 12:00 - wow").is_empty());
+        assert!(parse("No timestamps\nanywhere here").is_empty());
+    }
+}
@@ -1,192 +1,1099 @@
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap,
+    },
 };
 // Removed ratatui_input for simplicity
 
-use crate::app::{App, DownloadStatus};
+use crate::app::{
+    App, CrateColumn, CratePanel, DownloadStatus, Focus, HistoryFilter, LibraryView, PreviewState,
+    Quitting, SearchState,
+};
+use crate::history::{self, HistoryEntry};
+use crate::keymap::{Action, Key};
+use crate::library::{LibraryFile, SortColumn};
+use crate::theme::Theme;
+
+/// Terminals at least this tall get the banner
+const BANNER_MIN_HEIGHT: u16 = 40;
+/// Terminals at least this wide get the details and log beside the history
+const WIDE_MIN_WIDTH: u16 = 100;
+/// Most queue rows shown before the queue pane scrolls
+const QUEUE_MAX_ROWS: u16 = 6;
+/// Frames of the spinner shown beside work in progress
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Where everything sits on screen; shared by rendering and mouse hit-testing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScreenLayout {
+    pub banner: Rect,
+    pub input: Rect,
+    pub buttons: Rect,
+    /// Panes, or whichever overlay is open
+    pub body: Rect,
+    pub hints: Rect,
+    pub queue: Rect,
+    pub history: Rect,
+    /// Empty when the details pane is hidden
+    pub details: Rect,
+    /// Empty when the log pane is hidden
+    pub log: Rect,
+}
+
+impl ScreenLayout {
+    /// Lay out a screen of `area` for the app's current state
+    pub fn new(area: Rect, app: &App) -> Self {
+        // The banner only shows when there's room to spare; the panes get everything else
+        let banner_height = if area.height >= BANNER_MIN_HEIGHT {
+            8
+        } else {
+            0
+        };
+        let [banner, input, buttons, body, hints] = Layout::vertical([
+            Constraint::Length(banner_height),
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+        let mut layout = Self {
+            banner,
+            input,
+            buttons,
+            body,
+            hints,
+            ..Self::default()
+        };
+
+        let downloading = matches!(app.download_status, DownloadStatus::Downloading);
+        let queue_rows = (app.queue.len() as u16 + u16::from(downloading)).clamp(1, QUEUE_MAX_ROWS);
+        let queue_height = Constraint::Length(queue_rows + 2);
+        if body.width >= WIDE_MIN_WIDTH {
+            let [left, right] =
+                Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .areas(body);
+            [layout.queue, layout.history] =
+                Layout::vertical([queue_height, Constraint::Min(0)]).areas(left);
+            [layout.details, layout.log] =
+                Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .areas(right);
+        } else {
+            // One column: the details and log share the bottom, showing the log only while it's focused
+            let [queue, history, lower] =
+                Layout::vertical([queue_height, Constraint::Min(3), Constraint::Percentage(35)])
+                    .areas(body);
+            layout.queue = queue;
+            layout.history = history;
+            if app.focus == Focus::Log {
+                layout.log = lower;
+            } else {
+                layout.details = lower;
+            }
+        }
+        layout
+    }
+
+    /// The pane under a screen position
+    pub fn pane_at(&self, position: Position) -> Option<Focus> {
+        [
+            (self.input, Focus::Input),
+            (self.queue, Focus::Queue),
+            (self.history, Focus::History),
+            (self.details, Focus::Details),
+            (self.log, Focus::Log),
+        ]
+        .into_iter()
+        .find(|(area, _)| area.contains(position))
+        .map(|(_, pane)| pane)
+    }
+}
+
+/// Clickable buttons under the input box
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    /// Download (or search for) the input
+    Download,
+    /// Switch the bitrate Enter downloads at
+    Format,
+    /// Queue the last failed download again
+    Retry,
+    /// Drop the highlighted queued download
+    Cancel,
+}
+
+/// Each button's label and where it sits within the button bar `area`
+pub fn buttons(area: Rect, app: &App) -> Vec<(Button, String, Rect)> {
+    let labels = [
+        (Button::Download, "[ Download ]".to_string()),
+        (Button::Format, format!("[ MP3 {}k ]", app.bitrate)),
+        (Button::Retry, "[ Retry ]".to_string()),
+        (Button::Cancel, "[ Cancel ]".to_string()),
+    ];
+    let mut x = area.x + 1;
+    labels
+        .into_iter()
+        .map(|(button, label)| {
+            let width = label.chars().count() as u16;
+            let rect = Rect::new(x, area.y, width, 1).intersection(area);
+            x += width + 1;
+            (button, label, rect)
+        })
+        .collect()
+}
+
+/// Which row of a bordered list at `area` is under screen row `y`, given the highlighted
+/// row; mirrors how the list scrolls to keep the highlight in view
+pub fn list_row(area: Rect, selected: usize, y: u16) -> Option<usize> {
+    let rows = area.height.saturating_sub(2) as usize;
+    if y <= area.y || y as usize > area.y as usize + rows {
+        return None;
+    }
+    let first = (selected + 1).saturating_sub(rows);
+    Some(first + (y - area.y - 1) as usize)
+}
 
 /// Render the main UI
 pub fn render(frame: &mut Frame, app: &App) {
-    let area = frame.area();
+    let theme = &app.theme;
+    let layout = ScreenLayout::new(frame.area(), app);
+    let body = layout.body;
 
-    // Create main layout - dynamic constraints based on what needs to be shown
-    let has_download_activity = matches!(app.download_status, DownloadStatus::Downloading)
-        || !app.download_history.is_empty();
+    if layout.banner.height > 0 {
+        render_banner(frame, theme, layout.banner);
+    }
+    render_input(frame, app, layout.input);
+    render_buttons(frame, app, layout.buttons);
 
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(8), // Title (6 lines + 2 for borders)
-            Constraint::Length(1), // Spacing
-            Constraint::Length(3), // Input box
-            Constraint::Length(1), // Spacing
-            Constraint::Length(4), // Instructions
-            Constraint::Length(if has_download_activity { 4 } else { 0 }), // Download status (conditional)
-            Constraint::Min(0),                                            // Remaining space
-        ])
-        .split(area);
+    // The quit question, help, the download preview, history search, library, crate panel or
+    // search results take over the panes while they're open
+    if app.quitting == Some(Quitting::Confirming) {
+        render_quit(frame, app, body);
+    } else if let Some(scroll) = app.help {
+        render_help(frame, app, scroll, body);
+    } else if let Some(preview) = &app.preview {
+        render_preview(frame, app, preview, body);
+    } else if let Some(filter) = &app.history_filter {
+        render_history_filter(frame, app, filter, body);
+    } else if let Some(view) = &app.library {
+        render_library(frame, app, view, body);
+    } else if let Some(panel) = &app.crates {
+        render_crates(frame, app, panel, body);
+    } else if let Some(search) = &app.search {
+        render_search(frame, app, search, body);
+    } else {
+        render_queue(frame, app, layout.queue);
+        render_history(frame, app, layout.history);
+        if layout.details.height > 0 {
+            render_details(frame, app, layout.details);
+        }
+        if layout.log.height > 0 {
+            render_log(frame, app, layout.log);
+        }
+    }
+
+    let hints_line = Paragraph::new(Span::styled(key_hints(app), theme.muted));
+    frame.render_widget(hints_line, layout.hints);
+}
 
-    // Title - Bright neon cyan (classic terminal green alternative)
+/// The button bar; Retry and Cancel are dimmed while there's nothing for them to do
+fn render_buttons(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    for (button, label, rect) in buttons(area, app) {
+        let enabled = match button {
+            Button::Download => !app.input.trim().is_empty(),
+            Button::Format => true,
+            Button::Retry => app.last_failed.is_some(),
+            Button::Cancel => !app.queue.is_empty(),
+        };
+        let style = if enabled {
+            theme.accent.add_modifier(Modifier::BOLD)
+        } else {
+            theme.muted
+        };
+        frame.render_widget(Paragraph::new(Span::styled(label, style)), rect);
+    }
+}
+
+/// The DJ CLI logo
+fn render_banner(frame: &mut Frame, theme: &Theme, area: Rect) {
     let title_text = vec![
         Line::from(vec![Span::styled(
             " _____       _    _____ _      _____ ",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
         Line::from(vec![Span::styled(
             "|  __ \\     | |  / ____| |    |_   _|",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
         Line::from(vec![Span::styled(
             "| |  | |    | | | |    | |      | |  ",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
         Line::from(vec![Span::styled(
             "| |  | |_   | | | |    | |      | |  ",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
         Line::from(vec![Span::styled(
             "| |__| | |__| | | |____| |____ _| |_ ",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
         Line::from(vec![Span::styled(
             "|_____/ \\____/   \\_____|______|_____|",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
         Line::from(vec![Span::styled(
             "                                     ",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
         Line::from(vec![Span::styled(
             "                                     ",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
+            theme.banner,
         )]),
     ];
 
     let title = Paragraph::new(title_text)
-        .style(
-            Style::default()
-                .fg(Color::Rgb(0, 255, 0))
-                .add_modifier(Modifier::BOLD),
-        )
+        .style(theme.banner)
         .alignment(ratatui::layout::Alignment::Center);
-    frame.render_widget(title, chunks[0]);
+    frame.render_widget(title, area);
+}
 
-    // Input box - Bright yellow when focused
+/// Render the URL input box, with the per-download options in its title
+fn render_input(frame: &mut Frame, app: &App, area: Rect) {
     let input_style = if app.is_input_focused() {
-        Style::default().fg(Color::Rgb(255, 255, 0)) // Bright yellow
+        app.theme.focus
     } else {
-        Style::default().fg(Color::Rgb(255, 255, 255)) // Bright white
+        app.theme.text
     };
 
-    let input_block = Block::default()
-        .borders(Borders::ALL)
-        .title("YouTube URL")
-        .border_style(if app.is_input_focused() {
-            Style::default().fg(Color::Rgb(255, 255, 0)) // Bright yellow border
+    // Per-download options ride along in the title
+    let mut flags = Vec::new();
+    if app.trim_silence {
+        flags.push("trim silence");
+    }
+    if app.split_mix {
+        flags.push(if app.pending_tracklist.is_some() {
+            "split by pasted tracklist"
         } else {
-            Style::default().fg(Color::Rgb(128, 128, 128)) // Gray
+            "split by chapters"
         });
+    }
+    let crate_flag = app
+        .target_crate
+        .as_ref()
+        .map(|name| format!("into crate '{name}'"));
+    flags.extend(crate_flag.as_deref());
+    let input_title = if flags.is_empty() {
+        "YouTube URL".to_string()
+    } else {
+        format!("YouTube URL  [{}]", flags.join(", "))
+    };
+    let input_block = Block::default()
+        .borders(Borders::ALL)
+        .title(input_title)
+        .border_style(app.theme.border(app.is_input_focused()));
 
     let input_widget = Paragraph::new(app.input_value())
         .style(input_style)
         .block(input_block);
-    frame.render_widget(input_widget, chunks[2]);
+    frame.render_widget(input_widget, area);
+}
 
-    // Instructions section
-    let instructions_text = vec![
-        Line::from(vec![Span::styled(
-            "📋 HOW TO USE:",
-            Style::default()
-                .fg(Color::Rgb(0, 255, 255))
-                .add_modifier(Modifier::BOLD),
-        )]),
+/// The current frame of the busy spinner; it only moves while `App::is_busy`
+fn spinner(app: &App) -> &'static str {
+    SPINNER[app.spinner % SPINNER.len()]
+}
+
+/// A pane's border, picked out while it has the focus
+fn pane_block(app: &App, pane: Focus, title: String) -> Block<'static> {
+    Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(app.theme.border(app.focus == pane))
+}
+
+/// The running download, then everything waiting behind it
+fn render_queue(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let mut items = Vec::new();
+    if let DownloadStatus::Downloading = &app.download_status {
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled(format!("🎵 {} ", spinner(app)), theme.focus),
+            Span::styled("Downloading...", theme.focus.add_modifier(Modifier::BOLD)),
+        ])));
+    }
+    let offset = items.len();
+    for download in &app.queue {
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled("⏳ ", theme.muted),
+            Span::styled(download.title.clone(), theme.text),
+            Span::styled(format!("  {} kbps", download.bitrate), theme.muted),
+        ])));
+    }
+    if items.is_empty() {
+        items.push(ListItem::new(Span::styled(
+            "Nothing downloading",
+            theme.muted,
+        )));
+    }
+
+    let title = if app.queue.is_empty() {
+        "Queue".to_string()
+    } else {
+        format!("Queue ({} waiting)", app.queue.len())
+    };
+    let list = List::new(items)
+        .block(pane_block(app, Focus::Queue, title))
+        .highlight_style(highlight_style(app, Focus::Queue));
+    let selected = (!app.queue.is_empty()).then_some(app.panes.queue + offset);
+    let mut state = ListState::default().with_selected(selected);
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Every download, newest first, with what analysis found
+fn render_history(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let rows = app.history_rows();
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|entry| {
+            let indent = if entry.parent.is_some() { "  " } else { "" };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{indent}✅ "), theme.success),
+                Span::styled(entry.filename.clone(), theme.success),
+                Span::styled(
+                    download_label(app, entry),
+                    theme.accent.add_modifier(Modifier::BOLD),
+                ),
+            ]))
+        })
+        .collect();
+
+    let title = format!("History ({})", rows.len());
+    let list = List::new(items)
+        .block(pane_block(app, Focus::History, title))
+        .highlight_style(highlight_style(app, Focus::History));
+    let selected = (!rows.is_empty()).then(|| app.panes.history.min(rows.len() - 1));
+    let mut state = ListState::default().with_selected(selected);
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Row highlight, brighter in the focused pane
+fn highlight_style(app: &App, pane: Focus) -> Style {
+    app.theme.highlight(app.focus == pane)
+}
+
+/// Tempo, key, cue, loudness and warnings for a download in the history pane
+fn download_label(app: &App, download: &HistoryEntry) -> String {
+    let mut label = String::new();
+    if let Some(bpm) = download.bpm {
+        label.push_str(&format!("  {bpm:.1} BPM"));
+    }
+    if let Some(key) = download.key {
+        let notation = app.config.analysis.key_notation;
+        label.push_str(&format!("  {}", key.format(notation)));
+    }
+    if let Some(cue) = download.first_downbeat {
+        label.push_str(&format!("  cue {cue:.2}s"));
+    }
+    if let Some(loudness) = download.loudness {
+        match loudness.integrated_lufs {
+            Some(lufs) => label.push_str(&format!("  {lufs:.1} LUFS")),
+            None => label.push_str("  silent"),
+        }
+    }
+    if let Some(quality) = &download.quality
+        && quality.low_quality
+    {
+        let cutoff = quality.cutoff_hz.unwrap_or_default() / 1000.0;
+        label.push_str(&format!("  ⚠️ low-quality source ({cutoff:.1} kHz)"));
+    }
+    if let Some(existing) = &download.duplicate_of {
+        label.push_str(&format!("  🔁 already have as {existing}"));
+    }
+    let tracks = app.history.children(download.id).count();
+    if tracks > 0 {
+        label.push_str(&format!("  [{tracks} tracks]"));
+    }
+    if download.analyzing {
+        label.push_str(&format!("  {} analysing...", spinner(app)));
+    }
+    label
+}
+
+/// Everything known about the highlighted download
+fn render_details(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let block = pane_block(app, Focus::Details, "Details".to_string());
+    let Some(entry) = app.selected_entry() else {
+        let empty =
+            Paragraph::new(Span::styled("Downloads show up here", theme.muted)).block(block);
+        frame.render_widget(empty, area);
+        return;
+    };
+
+    let label = |name: &str, value: String| {
         Line::from(vec![
-            Span::styled("1. ", Style::default().fg(Color::Rgb(255, 255, 0))), // Bright yellow
-            Span::raw("Paste URL, press "),
-            Span::styled(
-                "Enter",
-                Style::default()
-                    .fg(Color::Rgb(0, 255, 0))
-                    .add_modifier(Modifier::BOLD),
-            ), // Bright green
-            Span::raw(" to download"),
-        ]),
+            Span::styled(format!("{name:<11}"), theme.muted),
+            Span::styled(value, theme.text),
+        ])
+    };
+    let mut lines = vec![
+        label("File", entry.filename.clone()),
+        label(
+            "Folder",
+            entry
+                .path
+                .parent()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+        ),
+        label("Source", entry.url.clone()),
+        label("Downloaded", history::format_timestamp(entry.downloaded_at)),
+    ];
+    if let Some(bpm) = entry.bpm {
+        lines.push(label("BPM", format!("{bpm:.1}")));
+    }
+    if let Some(key) = entry.key {
+        lines.push(label("Key", key.format(app.config.analysis.key_notation)));
+    }
+    if let Some(cue) = entry.first_downbeat {
+        lines.push(label("Downbeat", format!("{cue:.2}s")));
+    }
+    if let Some(loudness) = entry.loudness {
+        let text = match (loudness.integrated_lufs, loudness.true_peak_dbtp) {
+            (Some(lufs), Some(peak)) => format!(
+                "{lufs:.1} LUFS, {peak:.1} dBTP peak, {:+.1} dB gain",
+                loudness.gain_db
+            ),
+            _ => "Silent".to_string(),
+        };
+        lines.push(label("Loudness", text));
+    }
+    if let Some(trim) = entry.trim {
+        lines.push(label(
+            "Trimmed",
+            format!("{:.1}s start, {:.1}s end", trim.leading, trim.trailing),
+        ));
+    }
+    if let Some(quality) = &entry.quality {
+        lines.push(label("Quality", quality.summary()));
+    }
+    let crates: Vec<&str> = app
+        .history
+        .crates
+        .iter()
+        .filter(|c| c.tracks.contains(&entry.id))
+        .map(|c| c.name.as_str())
+        .collect();
+    if !crates.is_empty() {
+        lines.push(label("Crates", crates.join(", ")));
+    }
+    if let Some(parent) = entry.parent.and_then(|id| app.history.get(id)) {
+        lines.push(label("Split from", parent.filename.clone()));
+    }
+    if let Some(existing) = &entry.duplicate_of {
+        lines.push(label("Duplicate", format!("sounds like {existing}")));
+    }
+
+    let details = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((app.panes.details_scroll, 0));
+    frame.render_widget(details, area);
+}
+
+/// Status messages, newest at the bottom
+fn render_log(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let height = area.height.saturating_sub(2) as usize;
+    let end = app.log.len().saturating_sub(app.panes.log_scroll as usize);
+    let start = end.saturating_sub(height);
+    let lines: Vec<Line> = app.log[start..end]
+        .iter()
+        .map(|message| Line::from(Span::styled(message.clone(), theme.text)))
+        .collect();
+
+    let title = if app.panes.log_scroll > 0 {
+        format!("Log ({} newer below)", app.panes.log_scroll)
+    } else {
+        "Log".to_string()
+    };
+    let log = Paragraph::new(lines).block(pane_block(app, Focus::Log, title));
+    frame.render_widget(log, area);
+}
+
+/// Keys for whatever has the focus, shown along the bottom
+fn key_hints(app: &App) -> String {
+    if app.quitting == Some(Quitting::Confirming) {
+        return " y quit now  w finish first  n keep going".to_string();
+    }
+    if app.help.is_some() {
+        return " ↑/↓ PgUp/PgDn scroll  any other key closes".to_string();
+    }
+    if app.preview.is_some()
+        || app.history_filter.is_some()
+        || app.library.is_some()
+        || app.crates.is_some()
+    {
+        return " Esc close".to_string();
+    }
+    let pane_hint = |keys: &str| {
+        format!(
+            " {keys}  {}",
+            bound_hints(app, &[(Action::NextPane, "next pane")])
+        )
+    };
+    match app.focus {
+        Focus::Input if app.search.is_some() && app.input.is_empty() => format!(
+            " ↑/↓ select  Space mark  {}",
+            bound_hints(
+                app,
+                &[
+                    (Action::Submit, "download"),
+                    (Action::Back, "close results"),
+                    (Action::NextPane, "next pane"),
+                ]
+            )
+        ),
+        Focus::Input => format!(
+            " {}",
+            bound_hints(
+                app,
+                &[
+                    (Action::Submit, "download"),
+                    (Action::Help, "help"),
+                    (Action::ExtractUrl, "clean paste"),
+                    (Action::SearchHistory, "search history"),
+                    (Action::OpenLibrary, "library"),
+                    (Action::OpenCrates, "crates"),
+                    (Action::NextPane, "next pane"),
+                    (Action::Back, "quit"),
+                ]
+            )
+        ),
+        Focus::Queue => pane_hint("↑/↓ select  K/J move  d remove") + "  Esc back to input",
+        Focus::History => {
+            pane_hint("↑/↓ PgUp/PgDn select  Enter add to crate  / search") + "  Esc back to input"
+        }
+        Focus::Details => pane_hint("↑/↓ PgUp/PgDn scroll") + "  Esc back to input",
+        Focus::Log => pane_hint("↑/↓ PgUp/PgDn scroll  End newest") + "  Esc back to input",
+    }
+}
+
+/// "Enter download  F5 clean paste", skipping actions with no key
+fn bound_hints(app: &App, hints: &[(Action, &str)]) -> String {
+    hints
+        .iter()
+        .filter_map(|(action, text)| {
+            let key = app.keymap.keys(*action).first()?.to_string();
+            Some(format!("{key} {text}"))
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// The first key bound to an action, or "-" if it has none
+fn first_key(app: &App, action: Action) -> String {
+    app.keymap
+        .keys(action)
+        .first()
+        .map(Key::to_string)
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// What quitting now would cut short, and the choices
+fn render_quit(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let (downloads, processing) = app.unfinished_work();
+    let count = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+    let mut unfinished = Vec::new();
+    if downloads > 0 {
+        unfinished.push(format!(
+            "{} running or queued",
+            count(downloads, "download")
+        ));
+    }
+    if processing > 0 {
+        unfinished.push(format!(
+            "{} still being processed",
+            count(processing, "track")
+        ));
+    }
+
+    let choice = |keys: &str, text: &str| {
         Line::from(vec![
-            Span::styled("2. ", Style::default().fg(Color::Rgb(255, 255, 0))), // Bright yellow
-            Span::raw("Press "),
             Span::styled(
-                "F5",
-                Style::default()
-                    .fg(Color::Rgb(0, 255, 0))
-                    .add_modifier(Modifier::BOLD),
-            ), // Bright green
-            Span::raw(" to clean pasted text"),
-        ]),
+                format!("{keys:<11}"),
+                theme.focus.add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(text.to_string(), theme.text),
+        ])
+    };
+    let lines = vec![
+        Line::from(Span::styled(
+            format!("⚠️ {}", unfinished.join(" and ")),
+            theme.warning.add_modifier(Modifier::BOLD),
+        )),
+        Line::default(),
+        choice(
+            "y / Enter",
+            "Quit now - stop them and remove unfinished files",
+        ),
+        choice("w", "Finish the running download and processing, then quit"),
+        choice("n / Esc", "Keep going"),
     ];
 
-    let instructions = Paragraph::new(instructions_text)
-        .style(Style::default().fg(Color::Rgb(255, 255, 255))) // Bright white
-        .block(Block::default().borders(Borders::ALL).title("How to Use"));
-    frame.render_widget(instructions, chunks[4]);
+    let dialog = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Quit?")
+                .border_style(theme.warning),
+        )
+        .wrap(Wrap { trim: true });
+    frame.render_widget(dialog, area);
+}
 
-    // Download status and history (only when there's activity)
-    if has_download_activity {
-        let mut status_lines = Vec::new();
+/// Every global key binding, generated from the key map so remapped keys show as they are
+fn render_help(frame: &mut Frame, app: &App, scroll: u16, area: Rect) {
+    let theme = &app.theme;
+    let labels: Vec<(Action, String)> = Action::ALL
+        .into_iter()
+        .map(|action| {
+            let label = app
+                .keymap
+                .label(action)
+                .unwrap_or_else(|| "(unbound)".to_string());
+            (action, label)
+        })
+        .collect();
+    let width = labels
+        .iter()
+        .map(|(_, label)| label.chars().count())
+        .max()
+        .unwrap_or_default();
 
-        // Show current download status if downloading
-        if let DownloadStatus::Downloading = &app.download_status {
-            status_lines.push(Line::from(vec![
-                Span::styled("🎵 ", Style::default().fg(Color::Rgb(255, 255, 0))),
+    let mut lines: Vec<Line> = labels
+        .into_iter()
+        .map(|(action, label)| {
+            Line::from(vec![
                 Span::styled(
-                    "Downloading...",
-                    Style::default()
-                        .fg(Color::Rgb(255, 255, 0))
-                        .add_modifier(Modifier::BOLD),
+                    format!("{label:<width$}  "),
+                    theme.focus.add_modifier(Modifier::BOLD),
                 ),
-            ]));
+                Span::styled(action.description(), theme.text),
+                Span::styled(format!("  ({})", action.name()), theme.muted),
+            ])
+        })
+        .collect();
+    lines.push(Line::default());
+    lines.push(Line::from(Span::styled(
+        "Keys for the focused pane, the search results and each panel are shown on the bottom line.",
+        theme.muted,
+    )));
+    lines.push(Line::from(Span::styled(
+        "Rebind any of the above under [keys] in the config file, e.g. download_128 = [\"ctrl+1\", \"alt+1\"]",
+        theme.muted,
+    )));
+
+    let help = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Keys")
+                .border_style(theme.accent),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    frame.render_widget(help, area);
+}
+
+/// Render the details of a URL waiting for download confirmation
+fn render_preview(frame: &mut Frame, app: &App, preview: &PreviewState, area: Rect) {
+    let theme = &app.theme;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Download Preview")
+        .border_style(theme.accent);
+
+    let label = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{name:<10}"), theme.muted),
+            Span::styled(value, theme.text),
+        ])
+    };
+
+    let mut lines = Vec::new();
+    match (&preview.info, &preview.error) {
+        (Some(info), _) => {
+            lines.push(Line::from(Span::styled(
+                info.title.clone(),
+                theme.success.add_modifier(Modifier::BOLD),
+            )));
+            lines.push(label(
+                "Uploader",
+                info.channel().unwrap_or("unknown").to_string(),
+            ));
+            lines.push(label(
+                "Duration",
+                info.duration
+                    .map(crate::clip::format_time)
+                    .unwrap_or_else(|| "--:--".to_string()),
+            ));
+            lines.push(label(
+                "Uploaded",
+                info.upload_date().unwrap_or_else(|| "unknown".to_string()),
+            ));
+            lines.push(label(
+                "Audio",
+                match info.best_audio() {
+                    Some(format) => format!(
+                        "{} {:.0}kbps{}",
+                        format.acodec.as_deref().unwrap_or("unknown"),
+                        format.abr.unwrap_or_default(),
+                        format
+                            .asr
+                            .map(|asr| format!(" {:.1}kHz", f64::from(asr) / 1000.0))
+                            .unwrap_or_default()
+                    ),
+                    None => "unknown".to_string(),
+                },
+            ));
+            lines.push(label(
+                "Thumbnail",
+                match info.best_thumbnail() {
+                    Some(thumb) => format!(
+                        "{}x{} {}",
+                        thumb.width.unwrap_or_default(),
+                        thumb.height.unwrap_or_default(),
+                        thumb.url
+                    ),
+                    None => info.thumbnail.clone().unwrap_or_else(|| "none".to_string()),
+                },
+            ));
+        }
+        (None, Some(error)) => {
+            lines.push(Line::from(Span::styled(
+                format!("⚠️ {error}"),
+                theme.warning,
+            )));
+        }
+        (None, None) => {
+            lines.push(Line::from(Span::styled(
+                format!("🔍 {} Fetching details...", spinner(app)),
+                theme.focus,
+            )));
         }
+    }
 
-        // Show recent downloads (last 2 to keep it compact)
-        for download in app.download_history.iter().rev().take(2) {
-            // Wrap long filenames - truncate at 50 chars and add ...
-            let display_name = if download.len() > 50 {
-                format!("{}...", &download[..47])
-            } else {
-                download.clone()
+    lines.push(label("Output", format!("MP3 {}kbps", preview.bitrate)));
+    if let Some(abr) = preview.upscaled_from() {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "⚠️ Source is {abr:.0}kbps - encoding at {}kbps only makes the file bigger",
+                preview.bitrate
+            ),
+            theme.warning.add_modifier(Modifier::BOLD),
+        )));
+    }
+    lines.push(Line::from(vec![
+        Span::styled("Enter", theme.success.add_modifier(Modifier::BOLD)),
+        Span::raw(" download  "),
+        Span::styled("Esc", theme.success.add_modifier(Modifier::BOLD)),
+        Span::raw(" cancel  "),
+        Span::styled(
+            format!(
+                "{}/{}",
+                first_key(app, Action::Download128),
+                first_key(app, Action::Download256)
+            ),
+            theme.success.add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" 128/256kbps"),
+    ]));
+
+    let details = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
+    frame.render_widget(details, area);
+}
+
+/// Render the downloads matching the history search, best match first
+fn render_history_filter(frame: &mut Frame, app: &App, filter: &HistoryFilter, area: Rect) {
+    let theme = &app.theme;
+    let matches = filter.matches(&app.history);
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|entry| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{}  ", history::format_date(entry.downloaded_at)),
+                    theme.muted,
+                ),
+                Span::styled(entry.filename.clone(), theme.text),
+                Span::styled(analysis_label(app, entry), theme.accent),
+            ]))
+        })
+        .collect();
+
+    let title = format!(
+        "History /{}_ - {} of {}  (bpm:120-128 key:8A format:mp3 crate:name, Enter open in crates, Esc close)",
+        filter.query,
+        matches.len(),
+        app.history.entries.len()
+    );
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(theme.accent),
+        )
+        .highlight_style(theme.highlight(true));
+    let mut state = ListState::default().with_selected(Some(filter.selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Render the local library as a sortable, filterable table
+fn render_library(frame: &mut Frame, app: &App, view: &LibraryView, area: Rect) {
+    let theme = &app.theme;
+    let files = view.visible(&app.history.library);
+
+    let mut title = format!(
+        "Library - {} of {} files",
+        files.len(),
+        app.history.library.len()
+    );
+    if view.filtering {
+        title.push_str(&format!("  filter: {}_", view.filter));
+    } else if !view.filter.is_empty() {
+        title.push_str(&format!("  filter: {}", view.filter));
+    }
+    if app.library_scanning {
+        title.push_str(&format!("  {} scanning...", spinner(app)));
+    }
+    title.push_str("  (/ filter, 1-7 or s sort, r reverse, Ctrl+R rescan)");
+
+    let header = Row::new(SortColumn::ALL.map(|column| {
+        let arrow = match (view.sort == column, view.descending) {
+            (true, false) => " ▲",
+            (true, true) => " ▼",
+            (false, _) => "",
+        };
+        Cell::from(format!("{}{arrow}", column.name()))
+    }))
+    .style(theme.accent.add_modifier(Modifier::BOLD));
+
+    let rows = files.iter().map(|file| library_row(&app.theme, file));
+    let widths = [
+        Constraint::Percentage(25),
+        Constraint::Percentage(35),
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(10),
+    ];
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(theme.accent),
+        )
+        .row_highlight_style(theme.highlight(true));
+    let mut state = TableState::default().with_selected(Some(view.selected));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+/// One library table row
+fn library_row(theme: &Theme, file: &LibraryFile) -> Row<'static> {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    Row::new([
+        Cell::from(text(&file.artist)),
+        Cell::from(file.display_title()),
+        Cell::from(file.bpm.map(|bpm| format!("{bpm:.1}")).unwrap_or_default()),
+        Cell::from(text(&file.key)),
+        Cell::from(
+            file.duration
+                .map(crate::clip::format_time)
+                .unwrap_or_default(),
+        ),
+        Cell::from(file.format.clone()),
+        Cell::from(history::format_date(file.added)),
+    ])
+    .style(theme.text)
+}
+
+/// Render the crate panel: downloads, crates, and the tracks in the highlighted crate
+fn render_crates(frame: &mut Frame, app: &App, panel: &CratePanel, area: Rect) {
+    let theme = &app.theme;
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(45),
+            Constraint::Percentage(20),
+            Constraint::Percentage(35),
+        ])
+        .split(area);
+
+    let block = |title: String, column: CrateColumn| {
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(theme.border(panel.column == column))
+    };
+    let highlight = theme.highlight(true);
+    let selected_crate = app.history.crates.get(panel.crate_selected);
+
+    // Every download, newest first; ones already in the highlighted crate are ticked
+    let history: Vec<ListItem> = app
+        .history
+        .entries
+        .iter()
+        .rev()
+        .map(|entry| {
+            let in_crate = selected_crate.is_some_and(|c| c.tracks.contains(&entry.id));
+            let indent = if entry.parent.is_some() { "  " } else { "" };
+            ListItem::new(Line::from(vec![
+                Span::styled(if in_crate { "✓ " } else { "  " }, theme.success),
+                Span::styled(format!("{indent}{}", entry.filename), theme.text),
+                Span::styled(analysis_label(app, entry), theme.accent),
+            ]))
+        })
+        .collect();
+    let history_title = "History - Enter adds to crate".to_string();
+    let list = List::new(history)
+        .block(block(history_title, CrateColumn::History))
+        .highlight_style(highlight);
+    let mut state = ListState::default().with_selected(Some(panel.history_selected));
+    frame.render_stateful_widget(list, columns[0], &mut state);
+
+    let crates: Vec<ListItem> = app
+        .history
+        .crates
+        .iter()
+        .map(|c| {
+            let target = app.target_crate.as_deref() == Some(c.name.as_str());
+            ListItem::new(Line::from(vec![
+                Span::styled(if target { "→ " } else { "  " }, theme.focus),
+                Span::styled(c.name.clone(), theme.text),
+                Span::styled(format!("  {}", c.tracks.len()), theme.muted),
+            ]))
+        })
+        .collect();
+    let crates_title = match &panel.new_name {
+        Some(name) => format!("New crate: {name}_"),
+        None => "Crates - n new, t target".to_string(),
+    };
+    let list = List::new(crates)
+        .block(block(crates_title, CrateColumn::Crates))
+        .highlight_style(highlight);
+    let mut state = ListState::default().with_selected(Some(panel.crate_selected));
+    frame.render_stateful_widget(list, columns[1], &mut state);
+
+    let tracks: Vec<ListItem> = selected_crate
+        .map(|c| c.tracks.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let line = match app.history.entries.iter().find(|e| e.id == *id) {
+                Some(entry) => Line::from(vec![
+                    Span::styled(format!("{:>2}. ", i + 1), theme.muted),
+                    Span::styled(entry.filename.clone(), theme.text),
+                    Span::styled(analysis_label(app, entry), theme.accent),
+                ]),
+                None => Line::from(Span::styled(
+                    format!("{:>2}. missing download #{id}", i + 1),
+                    theme.warning,
+                )),
             };
+            ListItem::new(line)
+        })
+        .collect();
+    let tracks_title = match selected_crate {
+        Some(c) => format!("{} - Shift+↑/↓ move, d remove, p playlist", c.name),
+        None => "No crates yet".to_string(),
+    };
+    let list = List::new(tracks)
+        .block(block(tracks_title, CrateColumn::Tracks))
+        .highlight_style(highlight);
+    let mut state = ListState::default().with_selected(Some(panel.track_selected));
+    frame.render_stateful_widget(list, columns[2], &mut state);
+}
 
-            status_lines.push(Line::from(vec![
-                Span::styled("✅ ", Style::default().fg(Color::Rgb(0, 255, 0))),
-                Span::styled(display_name, Style::default().fg(Color::Rgb(0, 255, 0))),
-            ]));
-        }
+/// Tempo and key after a track name, e.g. "  124.0 BPM  8A"
+fn analysis_label(app: &App, entry: &HistoryEntry) -> String {
+    let mut label = String::new();
+    if let Some(bpm) = entry.bpm {
+        label.push_str(&format!("  {bpm:.1} BPM"));
+    }
+    if let Some(key) = entry.key {
+        label.push_str(&format!(
+            "  {}",
+            key.format(app.config.analysis.key_notation)
+        ));
+    }
+    label
+}
+
+/// Render YouTube search results in the remaining space
+fn render_search(frame: &mut Frame, app: &App, search: &SearchState, area: Rect) {
+    let theme = &app.theme;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!("Search: {}", search.query))
+        .border_style(theme.accent);
 
-        let status_widget = Paragraph::new(status_lines)
-            .style(Style::default().fg(Color::Rgb(255, 255, 255)))
-            .block(Block::default().borders(Borders::ALL).title("Downloads"))
-            .wrap(Wrap { trim: true });
+    if search.loading {
+        let loading = Paragraph::new(Span::styled(
+            format!("🔎 {} Searching...", spinner(app)),
+            theme.focus,
+        ))
+        .block(block);
+        frame.render_widget(loading, area);
+        return;
+    }
+
+    let items: Vec<ListItem> = search
+        .results
+        .iter()
+        .zip(&search.marked)
+        .map(|(result, marked)| {
+            let mark = if *marked { "[x] " } else { "[ ] " };
+            let duration = result
+                .duration
+                .map(crate::clip::format_time)
+                .unwrap_or_else(|| "--:--".to_string());
+            let views = result.view_count.map(format_views).unwrap_or_default();
+
+            ListItem::new(Line::from(vec![
+                Span::styled(mark, theme.success),
+                Span::styled(result.title.clone(), theme.text),
+                Span::styled(format!("  {}", result.channel()), theme.accent),
+                Span::styled(format!("  {duration}  {views}"), theme.muted),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme.highlight(true));
+    let mut state = ListState::default().with_selected(Some(search.selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
 
-        let status_chunk = chunks[5];
-        frame.render_widget(status_widget, status_chunk);
+/// Compact view count, e.g. "1.2M views"
+fn format_views(views: u64) -> String {
+    match views {
+        v if v >= 1_000_000_000 => format!("{:.1}B views", v as f64 / 1e9),
+        v if v >= 1_000_000 => format!("{:.1}M views", v as f64 / 1e6),
+        v if v >= 1_000 => format!("{:.1}K views", v as f64 / 1e3),
+        v => format!("{v} views"),
     }
 }
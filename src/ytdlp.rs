@@ -0,0 +1,292 @@
+//! Metadata queries against yt-dlp (`--dump-json`).
+
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+use std::process::Stdio;
+use tracing::info;
+
+use crate::shutdown;
+
+/// A chapter marker from the video's metadata
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chapter {
+    pub title: String,
+    pub start_time: f64,
+    pub end_time: f64,
+}
+
+/// The parts of yt-dlp's info JSON we use
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct VideoInfo {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub duration: Option<f64>,
+    pub chapters: Option<Vec<Chapter>>,
+    pub uploader: Option<String>,
+    pub channel: Option<String>,
+    /// Upload date as `YYYYMMDD`
+    pub upload_date: Option<String>,
+    /// URL of the default thumbnail
+    pub thumbnail: Option<String>,
+    pub thumbnails: Option<Vec<Thumbnail>>,
+    pub formats: Option<Vec<Format>>,
+    /// Site yt-dlp resolved the URL with, e.g. "Youtube" or "Soundcloud"
+    pub extractor_key: Option<String>,
+    pub genre: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub release_year: Option<u32>,
+}
+
+/// One downloadable stream
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Format {
+    pub format_id: String,
+    pub ext: Option<String>,
+    /// Audio codec, or "none" for video-only streams
+    pub acodec: Option<String>,
+    /// Video codec, or "none" for audio-only streams
+    pub vcodec: Option<String>,
+    /// Average audio bitrate in kbps
+    pub abr: Option<f64>,
+    /// Audio sample rate in Hz
+    pub asr: Option<u32>,
+}
+
+impl Format {
+    /// Whether the stream carries audio and no video
+    pub fn is_audio_only(&self) -> bool {
+        let has = |codec: &Option<String>| codec.as_deref().is_some_and(|c| c != "none");
+        has(&self.acodec) && !has(&self.vcodec)
+    }
+}
+
+/// A thumbnail image offered for the video
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl VideoInfo {
+    /// Chapters, or an empty list when the video has none
+    pub fn chapters(&self) -> &[Chapter] {
+        self.chapters.as_deref().unwrap_or_default()
+    }
+
+    /// Channel name, falling back to the uploader
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref().or(self.uploader.as_deref())
+    }
+
+    /// Upload date as `YYYY-MM-DD`
+    pub fn upload_date(&self) -> Option<String> {
+        let date = self.upload_date.as_deref()?;
+        if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+            return Some(date.to_string());
+        }
+        Some(format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..]))
+    }
+
+    /// The highest-bitrate audio-only stream, which is what `bestaudio` will pick
+    pub fn best_audio(&self) -> Option<&Format> {
+        self.formats
+            .iter()
+            .flatten()
+            .filter(|f| f.is_audio_only() && f.abr.is_some())
+            .max_by(|a, b| {
+                a.abr
+                    .unwrap_or_default()
+                    .total_cmp(&b.abr.unwrap_or_default())
+            })
+    }
+
+    /// The largest thumbnail with known dimensions
+    pub fn best_thumbnail(&self) -> Option<&Thumbnail> {
+        self.thumbnails
+            .iter()
+            .flatten()
+            .filter(|t| t.width.is_some() && t.height.is_some())
+            .max_by_key(|t| t.width.unwrap_or_default() * t.height.unwrap_or_default())
+    }
+}
+
+/// Fetch metadata for a single video without downloading it
+pub async fn fetch_info(url: &str) -> Result<VideoInfo> {
+    let mut cmd = shutdown::command("yt-dlp");
+    cmd.args([
+        "--dump-json",
+        "--skip-download",
+        "--no-playlist",
+        "--no-warnings",
+        url,
+    ]);
+
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|_| eyre!("yt-dlp not found. Please install: brew install yt-dlp"))?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "Could not fetch video info. Check if the YouTube URL is valid and accessible."
+        ));
+    }
+
+    let info: VideoInfo = serde_json::from_slice(&output.stdout)?;
+    info!(
+        "Fetched info for {}: '{}' ({} chapters)",
+        info.id,
+        info.title,
+        info.chapters().len()
+    );
+    Ok(info)
+}
+
+/// One hit from a `ytsearchN:` query
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchResult {
+    pub id: String,
+    pub title: String,
+    pub channel: Option<String>,
+    pub uploader: Option<String>,
+    pub duration: Option<f64>,
+    pub view_count: Option<u64>,
+}
+
+impl SearchResult {
+    /// Watch URL for this result
+    pub fn url(&self) -> String {
+        format!("https://www.youtube.com/watch?v={}", self.id)
+    }
+
+    /// Channel name, falling back to the uploader
+    pub fn channel(&self) -> &str {
+        self.channel
+            .as_deref()
+            .or(self.uploader.as_deref())
+            .unwrap_or("unknown channel")
+    }
+}
+
+/// Search YouTube via yt-dlp, returning up to `count` results
+pub async fn search(query: &str, count: usize) -> Result<Vec<SearchResult>> {
+    let mut cmd = shutdown::command("yt-dlp");
+    let search_arg = format!("ytsearch{count}:{query}");
+    cmd.args([
+        "--dump-json",
+        "--flat-playlist", // Listing only - don't resolve every video
+        "--no-warnings",
+        &search_arg,
+    ]);
+
+    cmd.stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null());
+
+    let output = cmd
+        .output()
+        .await
+        .map_err(|_| eyre!("yt-dlp not found. Please install: brew install yt-dlp"))?;
+
+    if !output.status.success() {
+        return Err(eyre!("Search failed. Check your network connection."));
+    }
+
+    let results = parse_search(&String::from_utf8_lossy(&output.stdout));
+    info!("Search '{}' returned {} results", query, results.len());
+    Ok(results)
+}
+
+/// Search results from `--dump-json --flat-playlist` output: one JSON object per line
+fn parse_search(stdout: &str) -> Vec<SearchResult> {
+    stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|r: &SearchResult| !r.id.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two lines of `yt-dlp --dump-json --flat-playlist "ytsearch2:rick astley"`, trimmed
+    const SEARCH_OUTPUT: &str = r#"{"_type": "url", "ie_key": "Youtube", "id": "dQw4w9WgXcQ", "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ", "title": "Rick Astley - Never Gonna Give You Up (Official Music Video)", "description": null, "duration": 213.0, "channel_id": "UCuAXFkgsw1L7xaCfnd5JJOw", "channel": "Rick Astley", "channel_url": "https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw", "uploader": null, "thumbnails": [{"url": "https://i.ytimg.com/vi/dQw4w9WgXcQ/hq720.jpg", "height": 202, "width": 360}], "view_count": 1700000000, "live_status": null, "playlist_index": 1}
+{"_type": "url", "ie_key": "Youtube", "id": "yPYZpwSpKmA", "url": "https://www.youtube.com/watch?v=yPYZpwSpKmA", "title": "Rick Astley - Together Forever (Official Music Video)", "duration": null, "channel": null, "uploader": "RickAstleyVEVO", "view_count": null, "playlist_index": 2}
+"#;
+
+    #[test]
+    fn parses_search_results() {
+        let results = parse_search(SEARCH_OUTPUT);
+        assert_eq!(results.len(), 2);
+
+        let first = &results[0];
+        assert_eq!(first.url(), "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert_eq!(first.channel(), "Rick Astley");
+        assert_eq!(first.duration, Some(213.0));
+        assert_eq!(first.view_count, Some(1_700_000_000));
+
+        // Missing details fall back rather than dropping the result
+        let second = &results[1];
+        assert_eq!(second.channel(), "RickAstleyVEVO");
+        assert_eq!(second.duration, None);
+    }
+
+    #[test]
+    fn skips_lines_that_are_not_results() {
+        let output =
+            format!("not json\n{{\"_type\": \"url\", \"title\": \"No id\"}}\n{SEARCH_OUTPUT}");
+        assert_eq!(parse_search(&output).len(), 2);
+        assert!(parse_search("").is_empty());
+    }
+
+    /// `yt-dlp --dump-json --skip-download` for a DJ mix, trimmed to the fields we read
+    const INFO_OUTPUT: &str = r#"{"id": "xRFRZ0qu6Gg", "title": "Boiler Room: Deep House Mix", "description": "Recorded live.", "duration": 3612.0, "channel": "Boiler Room", "uploader": "Boiler Room", "upload_date": "20240315", "extractor_key": "Youtube", "thumbnail": "https://i.ytimg.com/vi/xRFRZ0qu6Gg/maxresdefault.jpg", "thumbnails": [{"url": "https://i.ytimg.com/vi/xRFRZ0qu6Gg/default.jpg", "height": 90, "width": 120, "id": "0"}, {"url": "https://i.ytimg.com/vi/xRFRZ0qu6Gg/maxresdefault.jpg", "height": 720, "width": 1280, "id": "1"}, {"url": "https://i.ytimg.com/vi_webp/xRFRZ0qu6Gg/maxresdefault.webp", "preference": -1, "id": "2"}], "chapters": [{"start_time": 0.0, "title": "Intro", "end_time": 95.0}, {"start_time": 95.0, "title": "Kerri Chandler - Rain", "end_time": 3612.0}], "formats": [{"format_id": "139", "ext": "m4a", "acodec": "mp4a.40.5", "vcodec": "none", "abr": 48.8, "asr": 22050}, {"format_id": "251", "ext": "webm", "acodec": "opus", "vcodec": "none", "abr": 135.2, "asr": 48000}, {"format_id": "140", "ext": "m4a", "acodec": "mp4a.40.2", "vcodec": "none", "abr": 129.5, "asr": 44100}, {"format_id": "18", "ext": "mp4", "acodec": "mp4a.40.2", "vcodec": "avc1.42001E", "abr": 256.0, "asr": 44100}, {"format_id": "sb0", "ext": "mhtml", "acodec": "none", "vcodec": "none"}], "webpage_url": "https://www.youtube.com/watch?v=xRFRZ0qu6Gg"}"#;
+
+    #[test]
+    fn parses_video_info() {
+        let info: VideoInfo = serde_json::from_str(INFO_OUTPUT).unwrap();
+        assert_eq!(info.title, "Boiler Room: Deep House Mix");
+        assert_eq!(info.duration, Some(3612.0));
+        assert_eq!(info.channel(), Some("Boiler Room"));
+        assert_eq!(info.upload_date().as_deref(), Some("2024-03-15"));
+
+        let chapters = info.chapters();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[1].title, "Kerri Chandler - Rain");
+        assert_eq!(chapters[1].start_time, 95.0);
+
+        // The muxed video format has a higher abr but isn't audio-only
+        let audio = info.best_audio().unwrap();
+        assert_eq!(audio.format_id, "251");
+        assert_eq!(audio.asr, Some(48000));
+
+        // The webp entry has no dimensions
+        let thumb = info.best_thumbnail().unwrap();
+        assert_eq!(thumb.width, Some(1280));
+    }
+
+    #[test]
+    fn video_info_fallbacks() {
+        let info: VideoInfo = serde_json::from_str(
+            r#"{"id": "abc", "title": "Untitled", "uploader": "someone", "upload_date": "NA"}"#,
+        )
+        .unwrap();
+        assert_eq!(info.channel(), Some("someone"));
+        assert_eq!(info.upload_date().as_deref(), Some("NA"));
+        assert!(info.chapters().is_empty());
+        assert!(info.best_audio().is_none());
+        assert!(info.best_thumbnail().is_none());
+    }
+}